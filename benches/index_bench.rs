@@ -0,0 +1,112 @@
+//! Indexing benchmark: `index --force` throughput with one session per
+//! transaction and with the default write batch. Run with
+//! `cargo bench --bench index_bench`; set `CCSEARCH_BENCH_SESSIONS` to change
+//! the corpus size (default 2000 sessions of 40 messages). Sessions are
+//! written without embeddings, so this measures parsing and database writes.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ccsearch::config::Config;
+use ccsearch::db::Database;
+use ccsearch::indexer::Indexer;
+
+const TURNS_PER_SESSION: usize = 20;
+
+fn main() {
+    let sessions: usize = std::env::var("CCSEARCH_BENCH_SESSIONS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2000);
+
+    // Session files are discovered under ~/.claude/projects
+    let home = tempfile::tempdir().expect("temp dir");
+    std::env::set_var("HOME", home.path());
+    let project = home.path().join(".claude/projects/-bench-project");
+    std::fs::create_dir_all(&project).expect("project dir");
+
+    eprintln!("Writing {} session files...", sessions);
+    let mut rng = fastrand::Rng::with_seed(42);
+    for s in 0..sessions {
+        write_session(&project, s, &mut rng);
+    }
+
+    let db = Database::open(&home.path().join("index.db"), &Config::default()).expect("open db");
+    index(&db, &Config::default(), false);
+
+    let single = Config {
+        write_batch_sessions: 1,
+        ..Config::default()
+    };
+    let per_session = index(&db, &single, true);
+    report("index --force, 1 session per commit", per_session, sessions);
+
+    let batched = index(&db, &Config::default(), true);
+    report(
+        &format!(
+            "index --force, {} sessions per commit",
+            Config::default().write_batch_sessions
+        ),
+        batched,
+        sessions,
+    );
+    eprintln!(
+        "Speedup: {:.1}×",
+        per_session.as_secs_f64() / batched.as_secs_f64()
+    );
+}
+
+fn index(db: &Database, config: &Config, force: bool) -> Duration {
+    let start = Instant::now();
+    let stats = Indexer::new(db, None, config, false)
+        .index_all(force, None)
+        .expect("index");
+    assert_eq!(stats.sessions_errored, 0);
+    start.elapsed()
+}
+
+fn report(name: &str, total: Duration, sessions: usize) {
+    eprintln!(
+        "{:<40} {:>10.2?} ({:.0} sessions/s)",
+        name,
+        total,
+        sessions as f64 / total.as_secs_f64()
+    );
+}
+
+/// Writes a JSONL session of alternating user prompts and assistant replies
+/// with tool calls
+fn write_session(project: &Path, index: usize, rng: &mut fastrand::Rng) {
+    let mut jsonl = String::new();
+    for turn in 0..TURNS_PER_SESSION {
+        let prompt = words(rng, 30);
+        let reply = words(rng, 120);
+        let file = format!("src/module_{}/file_{}.rs", rng.usize(0..50), turn);
+        writeln!(
+            jsonl,
+            r#"{{"type":"human","message":{{"role":"user","content":"{}"}}}}"#,
+            prompt
+        )
+        .unwrap();
+        writeln!(
+            jsonl,
+            r#"{{"type":"assistant","message":{{"role":"assistant","content":[{{"type":"text","text":"{}"}},{{"type":"tool_use","name":"Read","input":{{"file_path":"{}"}}}}]}}}}"#,
+            reply, file
+        )
+        .unwrap();
+    }
+    let path = project.join(format!("00000000-0000-4000-8000-{:012}.jsonl", index));
+    std::fs::write(path, jsonl).expect("session file");
+}
+
+fn words(rng: &mut fastrand::Rng, count: usize) -> String {
+    const WORDS: &str = "the index query session borrow checker timeout retry database \
+        vector token parser error handler config thread cache commit migration schema \
+        search result function struct trait async";
+    let words: Vec<&str> = WORDS.split_whitespace().collect();
+    (0..count)
+        .map(|_| words[rng.usize(0..words.len())])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,4 +1,122 @@
-// Placeholder for search benchmarks
+//! Search benchmarks for each vector quantization. Run with `cargo bench`; set
+//! `CCSEARCH_BENCH_CHUNKS` to change the corpus size (default 20000 chunks of
+//! 384 dimensions).
+
+use std::time::{Duration, Instant};
+
+use ccsearch::config::{Config, VectorQuantization};
+use ccsearch::db::Database;
+use ccsearch::indexer::parser::{Chunk, ChunkKind, ParsedSession};
+use ccsearch::search::filter::SearchFilter;
+
+const DIM: usize = 384;
+const CHUNKS_PER_SESSION: usize = 20;
+const QUERIES: usize = 20;
+
 fn main() {
-    eprintln!("Benchmarks not yet implemented");
+    let chunks: usize = std::env::var("CCSEARCH_BENCH_CHUNKS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20_000);
+
+    for quantization in [
+        VectorQuantization::None,
+        VectorQuantization::Int8,
+        VectorQuantization::Binary,
+    ] {
+        bench(chunks, quantization);
+    }
+}
+
+/// Times searches over the SQLite blobs, then over the memory-mapped store
+fn bench(chunks: usize, quantization: VectorQuantization) {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = Config {
+        vector_quantization: quantization,
+        ..Default::default()
+    };
+    let db = Database::open(&dir.path().join("index.db"), &config).expect("open db");
+    let mut rng = fastrand::Rng::with_seed(42);
+
+    eprintln!(
+        "\nBuilding a corpus of {} chunk embeddings (quantization: {})...",
+        chunks,
+        quantization.as_str()
+    );
+    fill(&db, &mut rng, chunks);
+    let queries: Vec<Vec<f32>> = (0..QUERIES).map(|_| random_unit(&mut rng)).collect();
+
+    let filter = SearchFilter::default();
+    let sqlite = time_searches(&db, &queries, &filter);
+    report("vec_search, SQLite blob scan", sqlite);
+
+    let start = Instant::now();
+    db.sync_vector_store().expect("build vector store");
+    eprintln!("Vector store built in {:.1?}", start.elapsed());
+
+    let mapped = time_searches(&db, &queries, &filter);
+    report("vec_search, memory-mapped store", mapped);
+    eprintln!(
+        "Speedup: {:.1}×",
+        sqlite.as_secs_f64() / mapped.as_secs_f64()
+    );
+}
+
+/// Stores `chunks` random embeddings across sessions, in one transaction
+fn fill(db: &Database, rng: &mut fastrand::Rng, chunks: usize) {
+    let now = chrono::Utc::now().to_rfc3339();
+    db.conn().execute_batch("BEGIN").expect("begin");
+    for s in 0..chunks.div_ceil(CHUNKS_PER_SESSION) {
+        let session_id = format!("session-{}", s);
+        let session = ParsedSession {
+            session_id: session_id.clone(),
+            project_path: "/bench".to_string(),
+            first_prompt: None,
+            summary: None,
+            slug: None,
+            git_branch: None,
+            message_count: 1,
+            created_at: now.clone(),
+            modified_at: now.clone(),
+            full_text: String::new(),
+        };
+        db.upsert_session(&session, 0, &now).expect("session");
+        let count = CHUNKS_PER_SESSION.min(chunks - s * CHUNKS_PER_SESSION);
+        for i in 0..count {
+            let chunk = Chunk {
+                kind: ChunkKind::Conversation,
+                text: String::new(),
+                first_message: None,
+                last_message: None,
+            };
+            let chunk_id = db
+                .insert_chunk(&session_id, i as i32, &chunk)
+                .expect("chunk");
+            db.upsert_chunk_embedding(chunk_id, &session_id, &random_unit(rng))
+                .expect("embedding");
+        }
+    }
+    db.conn().execute_batch("COMMIT").expect("commit");
+}
+
+/// Total time to run every query once (after one warm-up pass)
+fn time_searches(db: &Database, queries: &[Vec<f32>], filter: &SearchFilter) -> Duration {
+    for query in queries.iter().take(2) {
+        db.vec_search(query, 40, filter, None).expect("search");
+    }
+    let start = Instant::now();
+    for query in queries {
+        db.vec_search(query, 40, filter, None).expect("search");
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, total: Duration) {
+    eprintln!("{:<36} {:>10.2?} per query", name, total / QUERIES as u32);
+}
+
+fn random_unit(rng: &mut fastrand::Rng) -> Vec<f32> {
+    let v: Vec<f32> = (0..DIM).map(|_| rng.f32() - 0.5).collect();
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    v.iter().map(|x| x / norm).collect()
 }
@@ -1,11 +1,13 @@
 use clap::{Parser, Subcommand};
 
+use crate::search::filter::SessionKind;
+
 #[derive(Parser)]
 #[command(
     name = "ccsearch",
     about = "Hybrid search CLI for Claude Code chat sessions",
     version,
-    after_help = "Examples:\n  ccsearch \"authentication bug\"\n  ccsearch search \"refactor\" --days 7 --no-tui\n  ccsearch index --force\n  ccsearch list --days 30 --json"
+    after_help = "Examples:\n  ccsearch \"authentication bug\"\n  ccsearch search \"refactor\" --days 7 --no-tui\n  ccsearch \"flamegraph\" --file src/auth/token.rs\n  ccsearch index --force\n  ccsearch watch\n  ccsearch list --days 30 --json"
 )]
 pub struct Cli {
     #[command(subcommand)]
@@ -23,6 +25,9 @@ pub enum Commands {
     /// List sessions without searching
     List(ListArgs),
 
+    /// Keep the index up to date in the background as sessions change
+    Watch(WatchArgs),
+
     /// Show or edit configuration
     Config,
 }
@@ -32,14 +37,22 @@ pub struct SearchArgs {
     /// Search query
     pub query: String,
 
-    /// Only search sessions from the last N days
+    /// Only search sessions active in the last N days (0 = all time)
     #[arg(long, default_value_t = 30)]
     pub days: u32,
 
-    /// Filter to a specific project path
+    /// Only sessions whose project path contains this text (or matches this glob)
     #[arg(long)]
     pub project: Option<String>,
 
+    /// Only sessions on a git branch containing this text (or matching this glob)
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Only main sessions or only subagent sessions
+    #[arg(long, value_enum)]
+    pub kind: Option<SessionKind>,
+
     /// Maximum number of results
     #[arg(long, default_value_t = 20)]
     pub limit: usize,
@@ -60,6 +73,18 @@ pub struct SearchArgs {
     #[arg(long, conflicts_with = "exact")]
     pub semantic: bool,
 
+    /// Only sessions that used this tool (e.g. Edit, Bash, Grep)
+    #[arg(long)]
+    pub tool: Option<String>,
+
+    /// Only sessions where a tool touched a file path containing this text
+    #[arg(long)]
+    pub file: Option<String>,
+
+    /// Only sessions that ran a shell command containing this text
+    #[arg(long)]
+    pub command: Option<String>,
+
     /// BM25 weight in RRF fusion (default: 3.0)
     #[arg(long, default_value_t = 3.0)]
     pub bm25_weight: f64,
@@ -82,11 +107,15 @@ pub struct IndexArgs {
     /// Show per-session progress
     #[arg(long)]
     pub verbose: bool,
+
+    /// Only embed chunks that are missing vectors; don't scan for session changes
+    #[arg(long, conflicts_with = "force")]
+    pub embeddings_only: bool,
 }
 
 #[derive(Parser)]
 pub struct ListArgs {
-    /// Only list sessions from the last N days
+    /// Only list sessions active in the last N days (0 = all time)
     #[arg(long, default_value_t = 30)]
     pub days: u32,
 
@@ -98,3 +127,10 @@ pub struct ListArgs {
     #[arg(long)]
     pub json: bool,
 }
+
+#[derive(Parser)]
+pub struct WatchArgs {
+    /// Log each indexing pass
+    #[arg(long)]
+    pub verbose: bool,
+}
@@ -13,6 +13,33 @@ pub struct Config {
     #[serde(default = "default_rrf_k")]
     pub rrf_k: f64,
 
+    /// Reduce words to their stem in the keyword index ("migrating" matches "migration").
+    /// Changing this rebuilds the keyword index on next start.
+    #[serde(default)]
+    pub stemming: bool,
+
+    /// Diacritic folding in the keyword index: 0 keeps them, 1 folds them ("café"
+    /// matches "cafe"), 2 also folds the rarer cases 1 misses. Changing this rebuilds
+    /// the keyword index.
+    #[serde(default = "default_remove_diacritics")]
+    pub remove_diacritics: u8,
+
+    /// Also index Chinese/Japanese/Korean text as overlapping two-character pieces,
+    /// so words inside unsegmented CJK text can be found. Changing this rebuilds the
+    /// keyword index.
+    #[serde(default)]
+    pub cjk_bigrams: bool,
+
+    /// Maintain a trigram index over chunks for typo-tolerant matching.
+    /// Roughly doubles the size of the keyword index.
+    #[serde(default)]
+    pub trigram_index: bool,
+
+    /// RRF weight of typo-tolerant (trigram) matches, used when keyword search finds
+    /// few results. Needs `trigram_index`. 0 disables the fuzzy leg.
+    #[serde(default = "default_fuzzy_weight")]
+    pub fuzzy_weight: f64,
+
     #[serde(default = "default_max_results")]
     pub max_results: usize,
 
@@ -26,10 +53,112 @@ pub struct Config {
     #[serde(default = "default_chunk_size")]
     pub chunk_size: usize,
 
-    /// Overlap between consecutive chunks in characters
+    /// Overlap between consecutive chunks in characters (fixed strategy only)
     #[serde(default = "default_chunk_overlap")]
     pub chunk_overlap: usize,
 
+    /// How conversations are cut into chunks: "fixed" or "turns".
+    /// Changing this re-chunks every session on the next index run.
+    #[serde(default)]
+    pub chunk_strategy: ChunkStrategy,
+
+    /// Index tool output (compiler errors, test failures, stack traces) as separate chunks
+    #[serde(default)]
+    pub index_tool_results: bool,
+
+    /// Maximum characters kept from each tool result when `index_tool_results` is on
+    #[serde(default = "default_max_tool_result_chars")]
+    pub max_tool_result_chars: usize,
+
+    /// Number of token sequences embedded per ONNX inference call while indexing
+    #[serde(default = "default_embedding_batch_size")]
+    pub embedding_batch_size: usize,
+
+    /// ONNX Runtime intra-op threads for embedding. 0 lets ONNX Runtime decide.
+    #[serde(default = "default_embedding_threads")]
+    pub embedding_threads: usize,
+
+    /// Embedding model: "all-MiniLM-L6-v2", "bge-small-en-v1.5", "multilingual-e5-small",
+    /// or any name when `embedding_model_files` is set. Files live in
+    /// ~/.ccsearch/models/<name>. Changing this re-embeds every chunk on the next index run.
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+
+    /// Files of a custom embedding model: download URLs, or bare file names already in
+    /// its model directory. Must include an .onnx model and tokenizer.json.
+    #[serde(default)]
+    pub embedding_model_files: Vec<String>,
+
+    /// How token vectors become one embedding: "mean" or "cls" (first token).
+    /// Unset uses the model's own pooling (mean for custom models).
+    #[serde(default)]
+    pub embedding_pooling: Option<EmbeddingPooling>,
+
+    /// Text put before search queries when embedding them (e5: "query: ").
+    /// Unset uses the model's own prefix.
+    #[serde(default)]
+    pub embedding_query_prefix: Option<String>,
+
+    /// Text put before indexed conversation text when embedding it (e5: "passage: ").
+    /// Unset uses the model's own prefix.
+    #[serde(default)]
+    pub embedding_document_prefix: Option<String>,
+
+    /// Worker threads that parse and chunk sessions while indexing. 0 uses one per core.
+    #[serde(default)]
+    pub index_threads: usize,
+
+    /// Sessions written per database transaction while indexing. A session that
+    /// fails to write is rolled back on its own; 0 is treated as 1.
+    #[serde(default = "default_write_batch_sessions")]
+    pub write_batch_sessions: usize,
+
+    /// How long `ccsearch index` and `watch` wait for another process that is
+    /// indexing before giving up (ms). Searches never wait; they skip their
+    /// quick index check and search what is already committed.
+    #[serde(default = "default_index_lock_timeout_ms")]
+    pub index_lock_timeout_ms: u64,
+
+    /// How long a database statement waits for another process's write to
+    /// finish before failing (ms)
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+
+    /// What to do with indexed sessions whose JSONL file no longer exists:
+    /// "remove" deletes them from the index, "mark" keeps them but hides them from results
+    #[serde(default)]
+    pub orphan_policy: OrphanPolicy,
+
+    /// Also prune deleted sessions during the quick index check before each search
+    #[serde(default)]
+    pub prune_on_jit: bool,
+
+    /// After a search, embed chunks that JIT indexing stored without vectors
+    /// in a background `ccsearch index --embeddings-only` process
+    #[serde(default)]
+    pub backfill_after_search: bool,
+
+    /// Number of chunk embeddings at which `index` builds the ANN (IVF) index;
+    /// smaller corpora are searched by comparing every embedding
+    #[serde(default = "default_ann_min_vectors")]
+    pub ann_min_vectors: usize,
+
+    /// ANN lists scanned per vector search: higher finds more true nearest
+    /// neighbours, lower is faster. 0 disables the ANN index.
+    #[serde(default = "default_ann_probes")]
+    pub ann_probes: usize,
+
+    /// How chunk embeddings are stored: "none" (full precision), "int8" (about
+    /// 4× smaller) or "binary" (int8 plus a sign bit per dimension scanned first,
+    /// the best candidates rescored with int8). Turning it back off re-embeds
+    /// every chunk, since full precision can't be recovered.
+    #[serde(default)]
+    pub vector_quantization: VectorQuantization,
+
+    /// How long `ccsearch watch` waits after the last file change before indexing (ms)
+    #[serde(default = "default_watch_debounce_ms")]
+    pub watch_debounce_ms: u64,
+
     /// Recency boost half-life in days. Sessions this many days old get 50% boost.
     /// Set to 0 to disable recency boosting.
     #[serde(default = "default_recency_halflife")]
@@ -40,6 +169,92 @@ pub struct Config {
     pub exclude_projects: Vec<String>,
 }
 
+/// Strategy for splitting a conversation into search chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    /// Fixed-size character windows with overlap
+    #[default]
+    Fixed,
+    /// Whole user→assistant exchanges packed up to `chunk_size`;
+    /// oversized messages are split at paragraph or sentence boundaries
+    Turns,
+}
+
+impl ChunkStrategy {
+    /// Name as written in config
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fixed => "fixed",
+            Self::Turns => "turns",
+        }
+    }
+}
+
+/// How an embedding model's per-token vectors are reduced to one vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingPooling {
+    /// Average of all tokens, weighted by the attention mask (sentence-transformers, e5)
+    #[default]
+    Mean,
+    /// The first ([CLS]) token's vector (bge)
+    Cls,
+}
+
+impl EmbeddingPooling {
+    /// Name as written in config
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Cls => "cls",
+        }
+    }
+}
+
+/// Compact encoding of stored chunk embeddings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorQuantization {
+    /// Full-precision vectors
+    #[default]
+    None,
+    /// One signed byte per dimension plus a per-vector scale
+    Int8,
+    /// Int8, plus one sign bit per dimension compared by Hamming distance in a first pass
+    Binary,
+}
+
+impl VectorQuantization {
+    /// Name as written in config (also stored in index_meta)
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Int8 => "int8",
+            Self::Binary => "binary",
+        }
+    }
+
+    /// How many first-pass candidates per requested result are rescored with int8
+    pub fn rescore_factor(self) -> usize {
+        match self {
+            Self::None | Self::Int8 => 1,
+            Self::Binary => 10,
+        }
+    }
+}
+
+/// How to handle sessions whose source file was deleted or moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanPolicy {
+    /// Delete the session and everything derived from it
+    #[default]
+    Remove,
+    /// Keep the session but exclude it from search and listing
+    Mark,
+}
+
 fn default_bm25_weight() -> f64 {
     3.0
 }
@@ -49,6 +264,12 @@ fn default_vec_weight() -> f64 {
 fn default_rrf_k() -> f64 {
     60.0
 }
+fn default_remove_diacritics() -> u8 {
+    1
+}
+fn default_fuzzy_weight() -> f64 {
+    1.0
+}
 fn default_max_results() -> usize {
     20
 }
@@ -64,6 +285,36 @@ fn default_chunk_size() -> usize {
 fn default_chunk_overlap() -> usize {
     200
 }
+fn default_max_tool_result_chars() -> usize {
+    2000
+}
+fn default_embedding_batch_size() -> usize {
+    32
+}
+fn default_write_batch_sessions() -> usize {
+    64
+}
+fn default_index_lock_timeout_ms() -> u64 {
+    1000
+}
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+fn default_embedding_threads() -> usize {
+    0
+}
+fn default_embedding_model() -> String {
+    "all-MiniLM-L6-v2".to_string()
+}
+fn default_ann_min_vectors() -> usize {
+    20_000
+}
+fn default_ann_probes() -> usize {
+    16
+}
+fn default_watch_debounce_ms() -> u64 {
+    2000
+}
 fn default_recency_halflife() -> f64 {
     7.0
 }
@@ -74,11 +325,37 @@ impl Default for Config {
             bm25_weight: default_bm25_weight(),
             vec_weight: default_vec_weight(),
             rrf_k: default_rrf_k(),
+            stemming: false,
+            remove_diacritics: default_remove_diacritics(),
+            cjk_bigrams: false,
+            trigram_index: false,
+            fuzzy_weight: default_fuzzy_weight(),
             max_results: default_max_results(),
             default_days: default_days(),
             max_text_chars: default_max_text_chars(),
             chunk_size: default_chunk_size(),
             chunk_overlap: default_chunk_overlap(),
+            chunk_strategy: ChunkStrategy::default(),
+            index_tool_results: false,
+            max_tool_result_chars: default_max_tool_result_chars(),
+            embedding_batch_size: default_embedding_batch_size(),
+            embedding_threads: default_embedding_threads(),
+            embedding_model: default_embedding_model(),
+            embedding_model_files: Vec::new(),
+            embedding_pooling: None,
+            embedding_query_prefix: None,
+            embedding_document_prefix: None,
+            index_threads: 0,
+            write_batch_sessions: default_write_batch_sessions(),
+            index_lock_timeout_ms: default_index_lock_timeout_ms(),
+            busy_timeout_ms: default_busy_timeout_ms(),
+            orphan_policy: OrphanPolicy::default(),
+            prune_on_jit: false,
+            backfill_after_search: false,
+            ann_min_vectors: default_ann_min_vectors(),
+            ann_probes: default_ann_probes(),
+            vector_quantization: VectorQuantization::default(),
+            watch_debounce_ms: default_watch_debounce_ms(),
             recency_halflife: default_recency_halflife(),
             exclude_projects: Vec::new(),
         }
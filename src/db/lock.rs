@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a held lock is retried while waiting for it
+const RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Path of the lock file that belongs to a database file (`index.lock` next to `index.db`)
+pub fn lock_path(db_path: &Path) -> PathBuf {
+    db_path.with_extension("lock")
+}
+
+/// Exclusive advisory lock on the index, held by the one process writing to it.
+///
+/// The lock is an OS file lock, so it is released when the holder drops it or
+/// exits, however it exits. Only writers take it: readers search the last
+/// committed state of the database (SQLite WAL keeps that readable while a
+/// write is in progress) and never wait for the lock.
+pub struct IndexLock {
+    _file: Option<File>,
+}
+
+impl IndexLock {
+    /// Takes the lock at `path`, waiting up to `timeout` for another process to
+    /// release it. Returns None if it is still held after that.
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file {:?}", path))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Some(Self { _file: Some(file) })),
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", path))
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(RETRY_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// A lock that excludes nothing, for databases no other process can open
+    pub fn unshared() -> Self {
+        Self { _file: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_excludes_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = lock_path(&dir.path().join("index.db"));
+        assert_eq!(path, dir.path().join("index.lock"));
+
+        let held = IndexLock::acquire(&path, Duration::ZERO).unwrap();
+        assert!(held.is_some());
+
+        // A second handle on the file is refused, after waiting out the timeout
+        let start = Instant::now();
+        let busy = IndexLock::acquire(&path, Duration::from_millis(60)).unwrap();
+        assert!(busy.is_none());
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        drop(held);
+        assert!(IndexLock::acquire(&path, Duration::ZERO).unwrap().is_some());
+    }
+}
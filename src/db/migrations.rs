@@ -0,0 +1,357 @@
+use anyhow::{bail, Context, Result};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+use super::queries;
+use super::schema::{add_column_if_missing, table_exists};
+
+/// Schema version this build writes; the version of the last step in `MIGRATIONS`
+pub const SCHEMA_VERSION: u32 = 6;
+
+/// index_meta key holding the schema version of the database
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// One step of the schema history. Steps run in order, each in its own
+/// transaction together with the version bump, so a failed step leaves the
+/// database at the previous version.
+///
+/// Databases from before versioning (v0.2.0 and development builds after it)
+/// are treated as version 1 and may already have some of what a step adds, so
+/// steps create tables and columns only if they are missing.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+/// The schema history. Append a step (and bump `SCHEMA_VERSION`) to change the
+/// schema; never edit a released step. Keyword indexes and triggers, which are
+/// rebuilt from these tables according to config, are set up by `schema`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "sessions, chunks and embeddings (v0.2.0)",
+        up: initial_tables,
+    },
+    Migration {
+        version: 2,
+        description: "tool calls, messages and chunk kinds",
+        up: tool_calls_and_messages,
+    },
+    Migration {
+        version: 3,
+        description: "parse checkpoints and orphaned sessions",
+        up: checkpoints_and_orphans,
+    },
+    Migration {
+        version: 4,
+        description: "chunks pending embedding",
+        up: pending_embeddings,
+    },
+    Migration {
+        version: 5,
+        description: "identifier parts of chunks",
+        up: code_tokens,
+    },
+    Migration {
+        version: 6,
+        description: "ANN lists and vector store generation",
+        up: vector_indexes,
+    },
+];
+
+/// What `migrate` did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    /// Version found (0 for a new database)
+    pub from: u32,
+    /// Version after migrating
+    pub to: u32,
+    /// Copy of the database taken before migrating
+    pub backup: Option<PathBuf>,
+}
+
+/// Brings the database up to `SCHEMA_VERSION`. When `path` is given and an
+/// existing database needs migrating, it is first copied to `backup_path`.
+/// Fails on databases written by a newer build.
+pub fn migrate(conn: &Connection, path: Option<&Path>) -> Result<MigrationReport> {
+    let from = stored_version(conn)?;
+    let mut report = MigrationReport {
+        from,
+        to: from,
+        backup: None,
+    };
+    if from > SCHEMA_VERSION {
+        bail!(
+            "The index was created by a newer ccsearch (schema version {}, this build knows up to {}). \
+             Upgrade ccsearch, or delete the index to rebuild it.",
+            from,
+            SCHEMA_VERSION
+        );
+    }
+    if from == SCHEMA_VERSION {
+        return Ok(report);
+    }
+
+    if let Some(path) = path.filter(|_| from > 0) {
+        let backup = backup_path(path, from);
+        backup_database(conn, &backup)?;
+        report.backup = Some(backup);
+    }
+
+    for step in MIGRATIONS.iter().filter(|m| m.version > from) {
+        let tx = conn.unchecked_transaction()?;
+        (step.up)(&tx).with_context(|| {
+            format!(
+                "Schema migration to version {} ({}) failed",
+                step.version, step.description
+            )
+        })?;
+        queries::set_meta(&tx, SCHEMA_VERSION_KEY, &step.version.to_string())?;
+        tx.commit()?;
+        log::info!(
+            "Migrated index to schema version {}: {}",
+            step.version,
+            step.description
+        );
+        report.to = step.version;
+    }
+
+    Ok(report)
+}
+
+/// True if the database is at `SCHEMA_VERSION` and `migrate` has nothing to do
+pub fn is_current(conn: &Connection) -> Result<bool> {
+    Ok(stored_version(conn)? == SCHEMA_VERSION)
+}
+
+/// Where the copy of a database at schema version `version` is kept, e.g. `index.db.v1.bak`
+pub fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
+
+/// Version recorded in index_meta. Databases from before versioning have none:
+/// those with tables are version 1, empty ones 0.
+fn stored_version(conn: &Connection) -> Result<u32> {
+    let stored = if table_exists(conn, "index_meta")? {
+        queries::get_meta(conn, SCHEMA_VERSION_KEY)?
+    } else {
+        None
+    };
+    match stored {
+        Some(version) => version
+            .parse()
+            .with_context(|| format!("Invalid schema version {:?} in index_meta", version)),
+        None if table_exists(conn, "sessions")? => Ok(1),
+        None => Ok(0),
+    }
+}
+
+/// Writes a consistent copy of the database (WAL contents included) to `dest`
+fn backup_database(conn: &Connection, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        std::fs::remove_file(dest)
+            .with_context(|| format!("Failed to replace old backup {:?}", dest))?;
+    }
+    conn.execute("VACUUM INTO ?1", [dest.to_string_lossy()])
+        .with_context(|| format!("Failed to back up the index to {:?}", dest))?;
+    log::info!("Backed up the index to {:?} before migrating", dest);
+    Ok(())
+}
+
+fn initial_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- Session metadata
+        CREATE TABLE IF NOT EXISTS sessions (
+            session_id TEXT PRIMARY KEY,
+            project_path TEXT NOT NULL,
+            first_prompt TEXT,
+            summary TEXT,
+            slug TEXT,
+            git_branch TEXT,
+            message_count INTEGER,
+            created_at TEXT NOT NULL,
+            modified_at TEXT NOT NULL,
+            file_mtime INTEGER NOT NULL,
+            indexed_at TEXT NOT NULL,
+            full_text TEXT NOT NULL DEFAULT ''
+        );
+
+        -- Index metadata (schema version, settings the index was built with)
+        CREATE TABLE IF NOT EXISTS index_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        );
+
+        -- Conversation chunks for fine-grained search
+        CREATE TABLE IF NOT EXISTS chunks (
+            chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL,
+            text TEXT NOT NULL DEFAULT '',
+            UNIQUE(session_id, chunk_index)
+        );
+
+        CREATE TABLE IF NOT EXISTS session_embeddings (
+            session_id TEXT PRIMARY KEY,
+            embedding BLOB NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS chunk_embeddings (
+            chunk_id INTEGER PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            embedding BLOB NOT NULL
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+fn tool_calls_and_messages(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- Individual user/assistant messages; chunks reference them by ordinal
+        CREATE TABLE IF NOT EXISTS messages (
+            message_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            uuid TEXT,
+            parent_uuid TEXT,
+            ordinal INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            timestamp TEXT,
+            text TEXT NOT NULL DEFAULT '',
+            UNIQUE(session_id, ordinal)
+        );
+
+        -- Tool invocations (Read/Edit/Bash/Grep/...) extracted from tool_use blocks
+        CREATE TABLE IF NOT EXISTS tool_calls (
+            call_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            call_index INTEGER NOT NULL,
+            tool_name TEXT NOT NULL,
+            file_path TEXT,
+            command TEXT,
+            pattern TEXT,
+            UNIQUE(session_id, call_index)
+        );
+        ",
+    )?;
+    add_column_if_missing(
+        conn,
+        "chunks",
+        "kind",
+        "TEXT NOT NULL DEFAULT 'conversation'",
+    )?;
+    add_column_if_missing(conn, "chunks", "first_ordinal", "INTEGER")?;
+    add_column_if_missing(conn, "chunks", "last_ordinal", "INTEGER")?;
+    Ok(())
+}
+
+fn checkpoints_and_orphans(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- How far each session's JSONL file has been parsed (for append-only updates)
+        CREATE TABLE IF NOT EXISTS session_files (
+            session_id TEXT PRIMARY KEY,
+            byte_offset INTEGER NOT NULL,
+            line_count INTEGER NOT NULL,
+            message_count INTEGER NOT NULL,
+            prefix_hash TEXT NOT NULL
+        );
+        ",
+    )?;
+    add_column_if_missing(conn, "sessions", "orphaned", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+fn pending_embeddings(conn: &Connection) -> Result<()> {
+    if add_column_if_missing(
+        conn,
+        "chunks",
+        "embedding_pending",
+        "INTEGER NOT NULL DEFAULT 1",
+    )? {
+        // Chunks indexed before pending tracking: only those without a vector are pending
+        conn.execute_batch(
+            "UPDATE chunks SET embedding_pending = 0
+             WHERE chunk_id IN (SELECT chunk_id FROM chunk_embeddings)",
+        )?;
+    }
+    Ok(())
+}
+
+fn code_tokens(conn: &Connection) -> Result<()> {
+    // Filled in when `schema` rebuilds chunks_fts with the new column
+    add_column_if_missing(conn, "chunks", "code_tokens", "TEXT NOT NULL DEFAULT ''")?;
+    Ok(())
+}
+
+fn vector_indexes(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "chunk_embeddings", "list_id", "INTEGER")?;
+    conn.execute_batch(
+        "
+        -- Centroids of the IVF (inverted file) ANN index; chunk_embeddings.list_id
+        -- points at the nearest one (NULL until assigned)
+        CREATE TABLE IF NOT EXISTS ann_centroids (
+            list_id INTEGER PRIMARY KEY,
+            centroid BLOB NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_chunk_embeddings_list ON chunk_embeddings(list_id);
+
+        -- Any change to chunk_embeddings makes the memory-mapped vector store stale
+        INSERT OR IGNORE INTO index_meta (key, value) VALUES ('vector_generation', '0');
+        ",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        for (i, step) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(step.version, i as u32 + 1);
+        }
+        assert_eq!(MIGRATIONS.last().unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_new_database_migrates_without_backup() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(!is_current(&conn).unwrap());
+        let report = migrate(&conn, None).unwrap();
+        assert!(is_current(&conn).unwrap());
+        assert_eq!(report.from, 0);
+        assert_eq!(report.to, SCHEMA_VERSION);
+        assert!(report.backup.is_none());
+        assert_eq!(stored_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        // Already current: nothing to do
+        let report = migrate(&conn, None).unwrap();
+        assert_eq!(report.from, SCHEMA_VERSION);
+        assert_eq!(report.to, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_newer_schema_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn, None).unwrap();
+        queries::set_meta(&conn, SCHEMA_VERSION_KEY, &(SCHEMA_VERSION + 1).to_string()).unwrap();
+        let err = migrate(&conn, None).unwrap_err();
+        assert!(err.to_string().contains("newer ccsearch"));
+    }
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(
+            backup_path(Path::new("/home/u/.ccsearch/index.db"), 1),
+            PathBuf::from("/home/u/.ccsearch/index.db.v1.bak")
+        );
+    }
+}
@@ -1,20 +1,83 @@
+pub mod lock;
+pub mod migrations;
 pub mod queries;
 pub mod schema;
+pub mod vector_store;
 
 use anyhow::{Context, Result};
-use rusqlite::Connection;
-use std::path::Path;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::indexer::parser::ParsedSession;
+use lock::IndexLock;
+use schema::{IndexDrift, SchemaOptions};
+use vector_store::VectorStore;
+
+use crate::config::{Config, VectorQuantization};
+use crate::indexer::parser::{Chunk, ParseCheckpoint, ParsedMessage, ParsedSession, ToolCall};
+use crate::search::filter::SearchFilter;
+
+/// Prepared statements kept per connection
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// True if the database needs no setup: in WAL mode, at the current schema
+/// version, with keyword indexes and optional parts matching `options`
+fn is_current(conn: &Connection, options: &SchemaOptions) -> Result<bool> {
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+    Ok(journal_mode.eq_ignore_ascii_case("wal")
+        && migrations::is_current(conn)?
+        && schema::is_current(conn, options)?)
+}
+
+/// Brings the database up to date with `options`: the tables (backing up older
+/// databases first), then the keyword indexes and optional parts chosen in
+/// config, then the recorded indexing parameters. Only the holder of the index
+/// lock runs this. Returns the indexing parameters found changed.
+fn set_up(conn: &Connection, path: Option<&Path>, options: &SchemaOptions) -> Result<IndexDrift> {
+    if path.is_some() {
+        // Enable WAL mode for better concurrent access
+        conn.execute_batch("PRAGMA journal_mode=WAL;")?;
+    }
+    let report = migrations::migrate(conn, path)?;
+    if let Some(ref backup) = report.backup {
+        log::info!(
+            "Upgraded index from schema version {} to {} (backup at {:?})",
+            report.from,
+            report.to,
+            backup
+        );
+    }
+    schema::create_schema(conn, options)?;
+    schema::create_vec_table(conn, options)?;
+    schema::update_index_params(conn, options)
+}
 
 /// Main database handle wrapping rusqlite connection
 pub struct Database {
     conn: Connection,
+    options: SchemaOptions,
+    /// Memory-mapped copy of the chunk embeddings (none for in-memory databases)
+    vectors_path: Option<PathBuf>,
+    /// Lock file coordinating writers across processes (none for in-memory databases)
+    lock_path: Option<PathBuf>,
+    /// Indexing parameters found changed when opening
+    drift: IndexDrift,
 }
 
 impl Database {
-    /// Opens or creates the database at the given path
-    pub fn open(path: &Path) -> Result<Self> {
+    /// Opens or creates the database at the given path.
+    /// Optional indexes are created or dropped to match `config`.
+    pub fn open(path: &Path, config: &Config) -> Result<Self> {
+        Self::open_file(path, config, false)
+    }
+
+    /// Opens the database for searching: the connection refuses writes until
+    /// it takes the index lock (see `lock_index`)
+    pub fn open_read_only(path: &Path, config: &Config) -> Result<Self> {
+        Self::open_file(path, config, true)
+    }
+
+    fn open_file(path: &Path, config: &Config, read_only: bool) -> Result<Self> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create directory {:?}", parent))?;
@@ -23,24 +86,60 @@ impl Database {
         let conn = Connection::open(path)
             .with_context(|| format!("Failed to open database at {:?}", path))?;
 
-        // Enable WAL mode for better concurrent access
-        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
+        conn.execute_batch("PRAGMA synchronous=NORMAL;")?;
+        // Wait for another process's write transaction instead of failing with SQLITE_BUSY
+        conn.busy_timeout(Duration::from_millis(config.busy_timeout_ms))?;
+        // Room for every statement on the indexing write path (see `queries`)
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
 
-        // Create schema (sessions + FTS5 + embeddings)
-        schema::create_schema(&conn)?;
-        schema::create_vec_table(&conn)?;
+        // Setting up or upgrading the database writes to it, which is left to the
+        // holder of the index lock. An index that is already up to date is opened
+        // without writing, so opening doesn't wait for a process writing to it.
+        let options = SchemaOptions::from(config);
+        let lock_path = lock::lock_path(path);
+        let drift = if is_current(&conn, &options)? {
+            schema::check_index_params(&conn, &options)?
+        } else {
+            let timeout = Duration::from_millis(config.index_lock_timeout_ms);
+            let _lock = IndexLock::acquire(&lock_path, timeout)?.context(
+                "Another ccsearch process is updating the index; try again when it finishes",
+            )?;
+            set_up(&conn, Some(path), &options)?
+        };
+        if read_only {
+            conn.execute_batch("PRAGMA query_only = ON;")?;
+        }
 
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            options,
+            vectors_path: Some(vector_store::store_path(path)),
+            lock_path: Some(lock_path),
+            drift,
+        })
     }
 
     /// Opens an in-memory database (for testing)
     #[allow(dead_code)]
     pub fn open_in_memory() -> Result<Self> {
+        Self::open_in_memory_with(&Config::default())
+    }
+
+    /// Opens an in-memory database with the optional indexes enabled in `config` (for testing)
+    #[allow(dead_code)]
+    pub fn open_in_memory_with(config: &Config) -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        schema::create_schema(&conn)?;
-        schema::create_vec_table(&conn)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        let options = SchemaOptions::from(config);
+        let drift = set_up(&conn, None, &options)?;
 
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            options,
+            vectors_path: None,
+            lock_path: None,
+            drift,
+        })
     }
 
     /// Returns whether vector search is available (always true now — embeddings stored as blobs)
@@ -48,6 +147,46 @@ impl Database {
         true
     }
 
+    /// Chunking parameters or embedding model that had changed since the index
+    /// was built when it was opened; the first process to take the index lock
+    /// invalidates the affected parts and the next index run rebuilds them
+    pub fn index_drift(&self) -> &IndexDrift {
+        &self.drift
+    }
+
+    /// Old and new embedding model, while the stored vectors still come from
+    /// the old one (until a lock holder drops them)
+    pub fn stale_embedding_model(&self) -> Result<Option<(String, String)>> {
+        Ok(schema::check_index_params(&self.conn, &self.options)?.embedding_model)
+    }
+
+    /// Takes the index lock, making this the only process that indexes into the
+    /// database, waiting up to `timeout` for another to finish. Returns None if
+    /// it is still busy; searching needs no lock.
+    ///
+    /// The lock holder is the one to invalidate what changed indexing
+    /// parameters affect (see `schema::update_index_params`), which it does
+    /// on taking the lock.
+    pub fn lock_index(&self, timeout: Duration) -> Result<Option<IndexLock>> {
+        let lock = match self.lock_path {
+            Some(ref path) => IndexLock::acquire(path, timeout)?,
+            None => Some(IndexLock::unshared()),
+        };
+        if lock.is_some() {
+            // A read-only connection may write from now on
+            self.conn.execute_batch("PRAGMA query_only = OFF;")?;
+            schema::update_index_params(&self.conn, &self.options)?;
+        }
+        Ok(lock)
+    }
+
+    /// Starts a write transaction. The `Database` methods called while it is
+    /// open run inside it; dropping it without committing rolls them back.
+    pub fn write_transaction(&self) -> Result<Transaction<'_>> {
+        Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)
+            .context("Failed to start a write transaction")
+    }
+
     /// Gets a reference to the underlying connection
     #[allow(dead_code)]
     pub fn conn(&self) -> &Connection {
@@ -73,13 +212,35 @@ impl Database {
         queries::delete_session_chunks(&self.conn, session_id)
     }
 
-    pub fn insert_chunk(
+    pub fn insert_chunk(&self, session_id: &str, chunk_index: i32, chunk: &Chunk) -> Result<i64> {
+        queries::insert_chunk(
+            &self.conn,
+            session_id,
+            chunk_index,
+            chunk,
+            self.options.tokenizer.cjk_bigrams,
+        )
+    }
+
+    pub fn delete_session_messages(&self, session_id: &str) -> Result<()> {
+        queries::delete_session_messages(&self.conn, session_id)
+    }
+
+    pub fn insert_message(&self, session_id: &str, message: &ParsedMessage) -> Result<()> {
+        queries::insert_message(&self.conn, session_id, message)
+    }
+
+    pub fn get_messages_in_range(
         &self,
         session_id: &str,
-        chunk_index: i32,
-        text: &str,
-    ) -> Result<i64> {
-        queries::insert_chunk(&self.conn, session_id, chunk_index, text)
+        first: i64,
+        last: i64,
+    ) -> Result<Vec<queries::MessageRow>> {
+        queries::get_messages_in_range(&self.conn, session_id, first, last)
+    }
+
+    pub fn get_session_message_total(&self, session_id: &str) -> Result<i64> {
+        queries::get_session_message_total(&self.conn, session_id)
     }
 
     pub fn delete_session_chunk_embeddings(&self, session_id: &str) -> Result<()> {
@@ -92,14 +253,113 @@ impl Database {
         session_id: &str,
         embedding: &[f32],
     ) -> Result<()> {
-        queries::upsert_chunk_embedding(&self.conn, chunk_id, session_id, embedding)
+        queries::upsert_chunk_embedding(
+            &self.conn,
+            chunk_id,
+            session_id,
+            embedding,
+            self.options.quantization,
+        )
+    }
+
+    pub fn chunk_embedding_bytes(&self) -> Result<(u64, u64)> {
+        queries::chunk_embedding_bytes(&self.conn)
+    }
+
+    pub fn count_chunk_embeddings(&self) -> Result<usize> {
+        queries::count_chunk_embeddings(&self.conn)
+    }
+
+    pub fn chunk_embedding_dim(&self) -> Result<Option<usize>> {
+        queries::chunk_embedding_dim(&self.conn, self.options.quantization)
+    }
+
+    pub fn sample_chunk_embeddings(&self, limit: usize) -> Result<Vec<Vec<f32>>> {
+        queries::sample_chunk_embeddings(&self.conn, limit, self.options.quantization)
+    }
+
+    pub fn get_unassigned_chunk_embeddings(&self, limit: usize) -> Result<Vec<(i64, Vec<f32>)>> {
+        queries::get_unassigned_chunk_embeddings(&self.conn, limit, self.options.quantization)
+    }
+
+    pub fn set_chunk_lists(&self, assignments: &[(i64, i64)]) -> Result<()> {
+        queries::set_chunk_lists(&self.conn, assignments)
+    }
+
+    pub fn get_ann_centroids(&self) -> Result<Vec<Vec<f32>>> {
+        queries::get_ann_centroids(&self.conn)
+    }
+
+    pub fn replace_ann_centroids(&self, centroids: &[Vec<f32>]) -> Result<()> {
+        queries::replace_ann_centroids(&self.conn, centroids)
+    }
+
+    pub fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        queries::get_meta(&self.conn, key)
+    }
+
+    pub fn set_meta(&self, key: &str, value: &str) -> Result<()> {
+        queries::set_meta(&self.conn, key, value)
+    }
+
+    pub fn get_pending_chunks(&self, limit: usize) -> Result<Vec<queries::PendingChunk>> {
+        queries::get_pending_chunks(&self.conn, limit)
+    }
+
+    pub fn count_pending_chunks(&self) -> Result<usize> {
+        queries::count_pending_chunks(&self.conn)
+    }
+
+    pub fn delete_session_tool_calls(&self, session_id: &str) -> Result<()> {
+        queries::delete_session_tool_calls(&self.conn, session_id)
+    }
+
+    pub fn insert_tool_call(
+        &self,
+        session_id: &str,
+        call_index: i32,
+        call: &ToolCall,
+    ) -> Result<()> {
+        queries::insert_tool_call(&self.conn, session_id, call_index, call)
+    }
+
+    pub fn next_chunk_index(&self, session_id: &str) -> Result<i32> {
+        queries::next_chunk_index(&self.conn, session_id)
+    }
+
+    pub fn next_tool_call_index(&self, session_id: &str) -> Result<i32> {
+        queries::next_tool_call_index(&self.conn, session_id)
+    }
+
+    pub fn get_parse_checkpoint(&self, session_id: &str) -> Result<Option<ParseCheckpoint>> {
+        queries::get_parse_checkpoint(&self.conn, session_id)
+    }
+
+    pub fn set_parse_checkpoint(
+        &self,
+        session_id: &str,
+        checkpoint: &ParseCheckpoint,
+    ) -> Result<()> {
+        queries::set_parse_checkpoint(&self.conn, session_id, checkpoint)
+    }
+
+    pub fn list_indexed_sessions(&self) -> Result<Vec<(String, bool)>> {
+        queries::list_indexed_sessions(&self.conn)
+    }
+
+    pub fn set_session_orphaned(&self, session_id: &str, orphaned: bool) -> Result<()> {
+        queries::set_session_orphaned(&self.conn, session_id, orphaned)
+    }
+
+    pub fn delete_session(&self, session_id: &str) -> Result<()> {
+        queries::delete_session(&self.conn, session_id)
     }
 
     pub fn get_best_matching_chunk(
         &self,
         fts_query: &str,
         session_id: &str,
-    ) -> Result<Option<String>> {
+    ) -> Result<Option<queries::ChunkMatch>> {
         queries::get_best_matching_chunk(&self.conn, fts_query, session_id)
     }
 
@@ -107,16 +367,114 @@ impl Database {
         queries::get_session_mtime(&self.conn, session_id)
     }
 
-    pub fn fts_search(&self, query: &str, limit: usize) -> Result<Vec<queries::FtsResult>> {
-        queries::fts_search(&self.conn, query, limit)
+    pub fn has_trigram_index(&self) -> Result<bool> {
+        schema::table_exists(&self.conn, "chunks_trigram")
+    }
+
+    pub fn fuzzy_search(
+        &self,
+        query: &str,
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<Vec<queries::FtsResult>> {
+        queries::fuzzy_search(&self.conn, query, limit, filter)
+    }
+
+    pub fn fts_search_filtered(
+        &self,
+        query: &str,
+        limit: usize,
+        filter: &SearchFilter,
+    ) -> Result<Vec<queries::FtsResult>> {
+        queries::fts_search(&self.conn, query, limit, filter)
+    }
+
+    /// Rebuilds the memory-mapped vector store if chunk embeddings changed since
+    /// it was written. Returns true if it was rebuilt.
+    pub fn sync_vector_store(&self) -> Result<bool> {
+        let Some(ref path) = self.vectors_path else {
+            return Ok(false);
+        };
+        if self.fresh_vector_store().unwrap_or(None).is_some() {
+            return Ok(false);
+        }
+        vector_store::build(&self.conn, path, self.options.quantization)?;
+        Ok(true)
+    }
+
+    /// The vector store, if there is one and it matches the current chunk embeddings
+    fn fresh_vector_store(&self) -> Result<Option<VectorStore>> {
+        let Some(ref path) = self.vectors_path else {
+            return Ok(None);
+        };
+        let Some(store) = VectorStore::open(path)? else {
+            return Ok(None);
+        };
+        // The generation restarts with a rebuilt index.db, so a store left
+        // behind by the old one can carry a matching generation: its row count
+        // gives it away
+        let fresh = store.generation() == queries::get_vector_generation(&self.conn)?
+            && store.rows() == queries::count_chunk_embeddings(&self.conn)?
+            && store.quantization() == self.options.quantization;
+        Ok(fresh.then_some(store))
     }
 
+    /// Searches the memory-mapped vector store; None if it is missing or stale
+    fn vector_store_search(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        filter: &SearchFilter,
+        lists: Option<&[i64]>,
+    ) -> Result<Option<Vec<queries::VecResult>>> {
+        let Some(store) = self.fresh_vector_store()? else {
+            return Ok(None);
+        };
+        let allowed = queries::matching_session_ids(&self.conn, filter)?;
+        let scored = store.search(query_embedding, limit, &allowed, lists)?;
+        Ok(Some(
+            scored
+                .into_iter()
+                .map(|(session_id, sim)| queries::VecResult {
+                    session_id,
+                    distance: 1.0 - sim,
+                })
+                .collect(),
+        ))
+    }
+
+    /// Vector similarity search. Scans the memory-mapped vector store when it is
+    /// up to date, and chunk_embeddings otherwise (binary codes first under
+    /// binary quantization).
     pub fn vec_search(
         &self,
         query_embedding: &[f32],
         limit: usize,
+        filter: &SearchFilter,
+        lists: Option<&[i64]>,
     ) -> Result<Vec<queries::VecResult>> {
-        queries::vec_search(&self.conn, query_embedding, limit)
+        match self.vector_store_search(query_embedding, limit, filter, lists) {
+            Ok(Some(results)) => return Ok(results),
+            Ok(None) => {}
+            Err(e) => log::warn!("Vector store unavailable, scanning the database: {}", e),
+        }
+        match self.options.quantization {
+            VectorQuantization::Binary => {
+                queries::vec_search_binary(&self.conn, query_embedding, limit, filter, lists)
+            }
+            quantization => queries::vec_search(
+                &self.conn,
+                query_embedding,
+                limit,
+                filter,
+                lists,
+                quantization,
+            ),
+        }
+    }
+
+    pub fn filter_sessions(&self, filter: &SearchFilter, limit: usize) -> Result<Vec<String>> {
+        queries::filter_sessions(&self.conn, filter, limit)
     }
 
     pub fn get_session(&self, session_id: &str) -> Result<Option<queries::SessionRow>> {
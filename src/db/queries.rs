@@ -1,8 +1,16 @@
 use anyhow::{Context, Result};
+use rusqlite::types::ToSql;
 use rusqlite::{params, Connection};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::indexer::parser::ParsedSession;
+use crate::config::VectorQuantization;
+use crate::indexer::parser::{
+    Chunk, ChunkKind, ParseCheckpoint, ParsedMessage, ParsedSession, ToolCall,
+};
+use crate::indexer::tokenizer;
+use crate::search::filter::{is_glob, SearchFilter, SessionKind};
+use crate::search::fts_query;
+use crate::search::quantize::{self, QueryCode};
 
 /// Search result from BM25 (FTS5) query
 #[derive(Debug, Clone)]
@@ -20,6 +28,32 @@ pub struct VecResult {
     pub distance: f64,
 }
 
+/// The chunk that best matches a query within a session
+#[derive(Debug, Clone)]
+pub struct ChunkMatch {
+    pub text: String,
+    pub kind: ChunkKind,
+    pub first_ordinal: Option<i64>,
+    pub last_ordinal: Option<i64>,
+}
+
+/// A chunk stored without an embedding, waiting for backfill
+#[derive(Debug, Clone)]
+pub struct PendingChunk {
+    pub chunk_id: i64,
+    pub session_id: String,
+    pub text: String,
+}
+
+/// A stored user/assistant message
+#[derive(Debug, Clone)]
+pub struct MessageRow {
+    pub ordinal: i64,
+    pub role: String,
+    pub timestamp: Option<String>,
+    pub text: String,
+}
+
 /// Full session row from the database
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct SessionRow {
@@ -43,32 +77,30 @@ pub fn upsert_session(
     indexed_at: &str,
 ) -> Result<()> {
     // Delete first to trigger FTS cleanup, then insert
-    conn.execute(
-        "DELETE FROM sessions WHERE session_id = ?1",
-        params![session.session_id],
-    )?;
+    conn.prepare_cached("DELETE FROM sessions WHERE session_id = ?1")?
+        .execute(params![session.session_id])?;
 
-    conn.execute(
+    conn.prepare_cached(
         "INSERT INTO sessions (
             session_id, project_path, first_prompt, summary, slug,
             git_branch, message_count, created_at, modified_at,
             file_mtime, indexed_at, full_text
         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-        params![
-            session.session_id,
-            session.project_path,
-            session.first_prompt,
-            session.summary,
-            session.slug,
-            session.git_branch,
-            session.message_count as i64,
-            session.created_at,
-            session.modified_at,
-            file_mtime,
-            indexed_at,
-            session.full_text,
-        ],
-    )
+    )?
+    .execute(params![
+        session.session_id,
+        session.project_path,
+        session.first_prompt,
+        session.summary,
+        session.slug,
+        session.git_branch,
+        session.message_count as i64,
+        session.created_at,
+        session.modified_at,
+        file_mtime,
+        indexed_at,
+        session.full_text,
+    ])
     .context("Failed to insert session")?;
 
     Ok(())
@@ -77,10 +109,10 @@ pub fn upsert_session(
 /// Upserts a vector embedding for a session
 pub fn upsert_embedding(conn: &Connection, session_id: &str, embedding: &[f32]) -> Result<()> {
     let bytes = embedding_to_bytes(embedding);
-    conn.execute(
+    conn.prepare_cached(
         "INSERT OR REPLACE INTO session_embeddings (session_id, embedding) VALUES (?1, ?2)",
-        params![session_id, bytes],
-    )
+    )?
+    .execute(params![session_id, bytes])
     .context("Failed to insert embedding")?;
 
     Ok(())
@@ -88,10 +120,8 @@ pub fn upsert_embedding(conn: &Connection, session_id: &str, embedding: &[f32])
 
 /// Deletes all chunks for a session (triggers FTS cleanup via trigger)
 pub fn delete_session_chunks(conn: &Connection, session_id: &str) -> Result<()> {
-    conn.execute(
-        "DELETE FROM chunks WHERE session_id = ?1",
-        params![session_id],
-    )?;
+    conn.prepare_cached("DELETE FROM chunks WHERE session_id = ?1")?
+        .execute(params![session_id])?;
     Ok(())
 }
 
@@ -100,36 +130,453 @@ pub fn insert_chunk(
     conn: &Connection,
     session_id: &str,
     chunk_index: i32,
-    text: &str,
+    chunk: &Chunk,
+    cjk_bigrams: bool,
 ) -> Result<i64> {
-    conn.execute(
-        "INSERT INTO chunks (session_id, chunk_index, text) VALUES (?1, ?2, ?3)",
-        params![session_id, chunk_index, text],
+    conn.prepare_cached(
+        "INSERT INTO chunks (session_id, chunk_index, text, kind, first_ordinal, last_ordinal, code_tokens)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?
+    .insert(params![
+        session_id,
+        chunk_index,
+        chunk.text,
+        chunk.kind.as_str(),
+        chunk.first_message.map(|o| o as i64),
+        chunk.last_message.map(|o| o as i64),
+        tokenizer::index_tokens(&chunk.text, cjk_bigrams),
+    ])
+    .context("Failed to insert chunk")
+}
+
+/// Deletes all messages for a session
+pub fn delete_session_messages(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.prepare_cached("DELETE FROM messages WHERE session_id = ?1")?
+        .execute(params![session_id])?;
+    Ok(())
+}
+
+/// Inserts a single message
+pub fn insert_message(conn: &Connection, session_id: &str, message: &ParsedMessage) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO messages (session_id, uuid, parent_uuid, ordinal, role, timestamp, text)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?
+    .execute(params![
+        session_id,
+        message.uuid,
+        message.parent_uuid,
+        message.ordinal as i64,
+        message.role,
+        message.timestamp,
+        message.text,
+    ])?;
+    Ok(())
+}
+
+/// Gets the messages of a session with ordinals in `[first, last]`
+pub fn get_messages_in_range(
+    conn: &Connection,
+    session_id: &str,
+    first: i64,
+    last: i64,
+) -> Result<Vec<MessageRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT ordinal, role, timestamp, text
+         FROM messages
+         WHERE session_id = ?1 AND ordinal BETWEEN ?2 AND ?3
+         ORDER BY ordinal",
     )?;
-    Ok(conn.last_insert_rowid())
+
+    let rows = stmt.query_map(params![session_id, first, last], |row| {
+        Ok(MessageRow {
+            ordinal: row.get(0)?,
+            role: row.get(1)?,
+            timestamp: row.get(2)?,
+            text: row.get(3)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
 }
 
-/// Deletes all chunk embeddings for a session
-pub fn delete_session_chunk_embeddings(conn: &Connection, session_id: &str) -> Result<()> {
-    conn.execute(
-        "DELETE FROM chunk_embeddings WHERE session_id = ?1",
+/// Gets the total number of messages in a session (highest ordinal + 1)
+pub fn get_session_message_total(conn: &Connection, session_id: &str) -> Result<i64> {
+    let total: Option<i64> = conn.query_row(
+        "SELECT MAX(ordinal) + 1 FROM messages WHERE session_id = ?1",
         params![session_id],
+        |row| row.get(0),
     )?;
+    Ok(total.unwrap_or(0))
+}
+
+/// Deletes all chunk embeddings for a session
+pub fn delete_session_chunk_embeddings(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.prepare_cached("DELETE FROM chunk_embeddings WHERE session_id = ?1")?
+        .execute(params![session_id])?;
     Ok(())
 }
 
-/// Inserts a chunk embedding
+/// Inserts a chunk embedding in its stored form (see `stored_embedding`), plus
+/// its binary code for binary quantization
 pub fn upsert_chunk_embedding(
     conn: &Connection,
     chunk_id: i64,
     session_id: &str,
     embedding: &[f32],
+    quantization: VectorQuantization,
 ) -> Result<()> {
-    let bytes = embedding_to_bytes(embedding);
-    conn.execute(
+    let bytes = stored_embedding(embedding, quantization);
+    conn.prepare_cached(
         "INSERT OR REPLACE INTO chunk_embeddings (chunk_id, session_id, embedding) VALUES (?1, ?2, ?3)",
-        params![chunk_id, session_id, bytes],
+    )?
+    .execute(params![chunk_id, session_id, bytes])?;
+    if quantization == VectorQuantization::Binary {
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO chunk_codes (chunk_id, session_id, code) VALUES (?1, ?2, ?3)",
+        )?
+        .execute(params![
+            chunk_id,
+            session_id,
+            quantize::encode(embedding, quantization)
+        ])?;
+    }
+    conn.prepare_cached("UPDATE chunks SET embedding_pending = 0 WHERE chunk_id = ?1")?
+        .execute(params![chunk_id])?;
+    Ok(())
+}
+
+/// Bytes taken by chunk embeddings: the stored vectors (see `stored_embedding`)
+/// and the binary codes
+pub fn chunk_embedding_bytes(conn: &Connection) -> Result<(u64, u64)> {
+    let stored: i64 = conn.query_row(
+        "SELECT COALESCE(SUM(LENGTH(embedding)), 0) FROM chunk_embeddings",
+        [],
+        |row| row.get(0),
     )?;
+    let codes: i64 = if crate::db::schema::table_exists(conn, "chunk_codes")? {
+        conn.query_row(
+            "SELECT COALESCE(SUM(LENGTH(code)), 0) FROM chunk_codes",
+            [],
+            |row| row.get(0),
+        )?
+    } else {
+        0
+    };
+    Ok((stored as u64, codes as u64))
+}
+
+/// Counter bumped by triggers whenever chunk_embeddings changes; a vector store
+/// built at another generation is stale
+pub fn get_vector_generation(conn: &Connection) -> Result<u64> {
+    Ok(get_meta(conn, VECTOR_GENERATION_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0))
+}
+
+/// IDs of every session that passes a filter
+pub fn matching_session_ids(conn: &Connection, filter: &SearchFilter) -> Result<HashSet<String>> {
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 1);
+    let params_refs: Vec<&dyn ToSql> = filter_params.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT session_id FROM sessions WHERE 1=1{}",
+        filter_sql
+    ))?;
+    let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+
+    let mut ids = HashSet::new();
+    for row in rows {
+        ids.insert(row?);
+    }
+    Ok(ids)
+}
+
+/// Counts stored chunk embeddings
+pub fn count_chunk_embeddings(conn: &Connection) -> Result<usize> {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM chunk_embeddings", [], |row| {
+        row.get(0)
+    })?;
+    Ok(count as usize)
+}
+
+/// Dimension of the stored chunk embeddings (None if there are none)
+pub fn chunk_embedding_dim(
+    conn: &Connection,
+    quantization: VectorQuantization,
+) -> Result<Option<usize>> {
+    let bytes: Option<i64> = conn
+        .query_row(
+            "SELECT length(embedding) FROM chunk_embeddings LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(bytes.map(|b| match quantization {
+        VectorQuantization::None => b as usize / 4,
+        // int8 codes: a 4-byte scale, then one byte per dimension
+        VectorQuantization::Int8 | VectorQuantization::Binary => (b as usize).saturating_sub(4),
+    }))
+}
+
+/// Gets a random sample of up to `limit` chunk embeddings (for training the ANN index)
+pub fn sample_chunk_embeddings(
+    conn: &Connection,
+    limit: usize,
+    quantization: VectorQuantization,
+) -> Result<Vec<Vec<f32>>> {
+    let mut stmt =
+        conn.prepare("SELECT embedding FROM chunk_embeddings ORDER BY random() LIMIT ?1")?;
+    let rows = stmt.query_map(params![limit as i64], |row| row.get::<_, Vec<u8>>(0))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(decode_stored_embedding(&row?, quantization));
+    }
+    Ok(results)
+}
+
+/// Gets up to `limit` chunk embeddings not yet assigned to an ANN list
+pub fn get_unassigned_chunk_embeddings(
+    conn: &Connection,
+    limit: usize,
+    quantization: VectorQuantization,
+) -> Result<Vec<(i64, Vec<f32>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT chunk_id, embedding FROM chunk_embeddings
+         WHERE list_id IS NULL
+         LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![limit as i64], |row| {
+        let chunk_id: i64 = row.get(0)?;
+        let blob: Vec<u8> = row.get(1)?;
+        Ok((chunk_id, blob))
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        let (chunk_id, blob) = row?;
+        results.push((chunk_id, decode_stored_embedding(&blob, quantization)));
+    }
+    Ok(results)
+}
+
+/// Assigns chunk embeddings to ANN lists in one transaction
+pub fn set_chunk_lists(conn: &Connection, assignments: &[(i64, i64)]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt =
+            tx.prepare("UPDATE chunk_embeddings SET list_id = ?2 WHERE chunk_id = ?1")?;
+        for (chunk_id, list_id) in assignments {
+            stmt.execute(params![chunk_id, list_id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Gets the ANN centroids; a centroid's position is its list ID
+pub fn get_ann_centroids(conn: &Connection) -> Result<Vec<Vec<f32>>> {
+    let mut stmt = conn.prepare("SELECT centroid FROM ann_centroids ORDER BY list_id")?;
+    let rows = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(bytes_to_embedding(&row?));
+    }
+    Ok(results)
+}
+
+/// Replaces the ANN centroids and unassigns every chunk embedding, in one transaction.
+/// An empty `centroids` removes the index (vector search then scans everything).
+pub fn replace_ann_centroids(conn: &Connection, centroids: &[Vec<f32>]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM ann_centroids", [])?;
+    tx.execute(
+        "UPDATE chunk_embeddings SET list_id = NULL WHERE list_id IS NOT NULL",
+        [],
+    )?;
+    {
+        let mut stmt =
+            tx.prepare("INSERT INTO ann_centroids (list_id, centroid) VALUES (?1, ?2)")?;
+        for (list_id, centroid) in centroids.iter().enumerate() {
+            stmt.execute(params![list_id as i64, embedding_to_bytes(centroid)])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Gets up to `limit` chunks that still need an embedding, newest first
+pub fn get_pending_chunks(conn: &Connection, limit: usize) -> Result<Vec<PendingChunk>> {
+    let mut stmt = conn.prepare(
+        "SELECT chunk_id, session_id, text FROM chunks
+         WHERE embedding_pending = 1
+         ORDER BY chunk_id DESC
+         LIMIT ?1",
+    )?;
+    let rows = stmt.query_map(params![limit as i64], |row| {
+        Ok(PendingChunk {
+            chunk_id: row.get(0)?,
+            session_id: row.get(1)?,
+            text: row.get(2)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Counts chunks that still need an embedding
+pub fn count_pending_chunks(conn: &Connection) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM chunks WHERE embedding_pending = 1",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+/// Deletes all tool calls for a session (triggers FTS cleanup via trigger)
+pub fn delete_session_tool_calls(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.prepare_cached("DELETE FROM tool_calls WHERE session_id = ?1")?
+        .execute(params![session_id])?;
+    Ok(())
+}
+
+/// Inserts a single tool call
+pub fn insert_tool_call(
+    conn: &Connection,
+    session_id: &str,
+    call_index: i32,
+    call: &ToolCall,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO tool_calls (session_id, call_index, tool_name, file_path, command, pattern)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![
+        session_id,
+        call_index,
+        call.tool_name,
+        call.file_path,
+        call.command,
+        call.pattern,
+    ])?;
+    Ok(())
+}
+
+/// Gets the next free chunk_index for a session
+pub fn next_chunk_index(conn: &Connection, session_id: &str) -> Result<i32> {
+    let next = conn.query_row(
+        "SELECT COALESCE(MAX(chunk_index) + 1, 0) FROM chunks WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    Ok(next)
+}
+
+/// Gets the next free call_index for a session
+pub fn next_tool_call_index(conn: &Connection, session_id: &str) -> Result<i32> {
+    let next = conn.query_row(
+        "SELECT COALESCE(MAX(call_index) + 1, 0) FROM tool_calls WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+    Ok(next)
+}
+
+/// Gets how far a session's JSONL file has been parsed
+pub fn get_parse_checkpoint(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Option<ParseCheckpoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT byte_offset, line_count, message_count, prefix_hash
+         FROM session_files WHERE session_id = ?1",
+    )?;
+    let result = stmt
+        .query_row(params![session_id], |row| {
+            Ok(ParseCheckpoint {
+                byte_offset: row.get::<_, i64>(0)? as u64,
+                line_count: row.get::<_, i64>(1)? as usize,
+                message_count: row.get::<_, i64>(2)? as usize,
+                prefix_hash: row.get(3)?,
+            })
+        })
+        .optional()?;
+    Ok(result)
+}
+
+/// Records how far a session's JSONL file has been parsed
+pub fn set_parse_checkpoint(
+    conn: &Connection,
+    session_id: &str,
+    checkpoint: &ParseCheckpoint,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO session_files (session_id, byte_offset, line_count, message_count, prefix_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        session_id,
+        checkpoint.byte_offset as i64,
+        checkpoint.line_count as i64,
+        checkpoint.message_count as i64,
+        checkpoint.prefix_hash,
+    ])?;
+    Ok(())
+}
+
+/// Lists every indexed session ID with whether it is marked as orphaned
+pub fn list_indexed_sessions(conn: &Connection) -> Result<Vec<(String, bool)>> {
+    let mut stmt = conn.prepare("SELECT session_id, orphaned FROM sessions")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Marks (or unmarks) a session whose JSONL file no longer exists.
+/// Orphaned sessions are kept in the index but excluded from search and listing.
+pub fn set_session_orphaned(conn: &Connection, session_id: &str, orphaned: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE sessions SET orphaned = ?2 WHERE session_id = ?1",
+        params![session_id, orphaned],
+    )
+    .context("Failed to update orphaned flag")?;
+    Ok(())
+}
+
+/// Removes a session and everything derived from it (chunks, messages, tool calls,
+/// embeddings, parse checkpoint) in one transaction
+pub fn delete_session(conn: &Connection, session_id: &str) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for table in [
+        "chunk_embeddings",
+        "session_embeddings",
+        "chunks",
+        "messages",
+        "tool_calls",
+        "session_files",
+        "sessions",
+    ] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE session_id = ?1", table),
+            params![session_id],
+        )
+        .with_context(|| format!("Failed to delete session rows from {}", table))?;
+    }
+    tx.commit()?;
     Ok(())
 }
 
@@ -138,9 +585,9 @@ pub fn get_best_matching_chunk(
     conn: &Connection,
     fts_query: &str,
     session_id: &str,
-) -> Result<Option<String>> {
+) -> Result<Option<ChunkMatch>> {
     let mut stmt = conn.prepare(
-        "SELECT c.text
+        "SELECT c.text, c.kind, c.first_ordinal, c.last_ordinal
          FROM chunks_fts f
          JOIN chunks c ON c.chunk_id = f.rowid
          WHERE chunks_fts MATCH ?1 AND f.session_id = ?2
@@ -150,13 +597,39 @@ pub fn get_best_matching_chunk(
 
     let result = stmt
         .query_row(params![fts_query, session_id], |row| {
-            row.get::<_, String>(0)
+            Ok(ChunkMatch {
+                text: row.get(0)?,
+                kind: ChunkKind::from_db(&row.get::<_, String>(1)?),
+                first_ordinal: row.get(2)?,
+                last_ordinal: row.get(3)?,
+            })
         })
         .optional()?;
 
     Ok(result)
 }
 
+/// Reads a value from index_meta
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let value: Option<Option<String>> = conn
+        .query_row(
+            "SELECT value FROM index_meta WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value.flatten())
+}
+
+/// Writes a value to index_meta
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO index_meta (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
 /// Gets the stored file_mtime for a session (for staleness detection)
 pub fn get_session_mtime(conn: &Connection, session_id: &str) -> Result<Option<i64>> {
     let mut stmt = conn.prepare("SELECT file_mtime FROM sessions WHERE session_id = ?1")?;
@@ -166,9 +639,18 @@ pub fn get_session_mtime(conn: &Connection, session_id: &str) -> Result<Option<i
     Ok(result)
 }
 
+/// RRF constant for merging the chunk and tool call rankings in `fts_search`
+const TABLE_FUSION_K: f64 = 60.0;
+
 /// BM25 full-text search using FTS5.
 /// Uses chunk-based search if chunks are available, falls back to session-level.
-pub fn fts_search(conn: &Connection, query: &str, limit: usize) -> Result<Vec<FtsResult>> {
+/// Tool calls (file paths, commands, patterns) are searched alongside chunks.
+pub fn fts_search(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+    filter: &SearchFilter,
+) -> Result<Vec<FtsResult>> {
     let has_chunks: bool = conn
         .query_row(
             "SELECT EXISTS(SELECT 1 FROM chunks LIMIT 1)",
@@ -177,18 +659,39 @@ pub fn fts_search(conn: &Connection, query: &str, limit: usize) -> Result<Vec<Ft
         )
         .unwrap_or(false);
 
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 3);
+    let mut param_values: Vec<Box<dyn ToSql>> =
+        vec![Box::new(query.to_string()), Box::new(limit as i64)];
+    param_values.extend(filter_params);
+    let params_refs: Vec<&dyn ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+
     if has_chunks {
-        // Search chunks, group by session_id (best rank per session)
-        let mut stmt = conn.prepare(
-            "SELECT session_id, MIN(rank) as best_rank
-             FROM chunks_fts
-             WHERE chunks_fts MATCH ?1
+        // Rank sessions in chunks and in tool calls separately (best match per
+        // session), then fuse the two rankings by reciprocal rank: bm25 scores
+        // from different tables aren't on the same scale. The fused score is
+        // negated so that, like bm25, lower is better.
+        let ranked = |table: &str| {
+            format!(
+                "SELECT session_id, ROW_NUMBER() OVER (ORDER BY MIN(rank)) AS pos
+                 FROM {table} WHERE {table} MATCH ?1{filter}
+                 GROUP BY session_id",
+                table = table,
+                filter = filter_sql
+            )
+        };
+        let sql = format!(
+            "SELECT session_id, -SUM(1.0 / ({k} + pos)) AS fused
+             FROM ({chunks} UNION ALL {calls})
              GROUP BY session_id
-             ORDER BY best_rank
+             ORDER BY fused
              LIMIT ?2",
-        )?;
+            k = TABLE_FUSION_K,
+            chunks = ranked("chunks_fts"),
+            calls = ranked("tool_calls_fts"),
+        );
+        let mut stmt = conn.prepare(&sql)?;
 
-        let rows = stmt.query_map(params![query, limit as i64], |row| {
+        let rows = stmt.query_map(params_refs.as_slice(), |row| {
             Ok(FtsResult {
                 session_id: row.get(0)?,
                 rank: row.get(1)?,
@@ -206,15 +709,17 @@ pub fn fts_search(conn: &Connection, query: &str, limit: usize) -> Result<Vec<Ft
     }
 
     // Fall back to session-level search
-    let mut stmt = conn.prepare(
+    let sql = format!(
         "SELECT session_id, rank
          FROM sessions_fts
-         WHERE sessions_fts MATCH ?1
+         WHERE sessions_fts MATCH ?1{}
          ORDER BY rank
          LIMIT ?2",
-    )?;
+        filter_sql
+    );
+    let mut stmt = conn.prepare(&sql)?;
 
-    let rows = stmt.query_map(params![query, limit as i64], |row| {
+    let rows = stmt.query_map(params_refs.as_slice(), |row| {
         Ok(FtsResult {
             session_id: row.get(0)?,
             rank: row.get(1)?,
@@ -232,12 +737,71 @@ pub fn fts_search(conn: &Connection, query: &str, limit: usize) -> Result<Vec<Ft
     Ok(results)
 }
 
+/// Typo-tolerant search over the trigram index of chunks (best rank per session).
+/// Chunks are found through any trigram of each query word (see
+/// `fts_query::compile_trigrams`), then kept only if they share enough of every
+/// word's trigrams, so a word can't match through one common trigram.
+pub fn fuzzy_search(
+    conn: &Connection,
+    query: &str,
+    limit: usize,
+    filter: &SearchFilter,
+) -> Result<Vec<FtsResult>> {
+    let match_expr = fts_query::compile_trigrams(query);
+    if match_expr.is_empty() {
+        return Ok(Vec::new());
+    }
+    let words = fts_query::word_trigrams(query);
+
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 2);
+    let mut param_values: Vec<Box<dyn ToSql>> = vec![Box::new(match_expr)];
+    param_values.extend(filter_params);
+    let params_refs: Vec<&dyn ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+
+    let sql = format!(
+        "SELECT session_id, rank, text
+         FROM chunks_trigram
+         WHERE chunks_trigram MATCH ?1{}
+         ORDER BY rank",
+        filter_sql
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params_refs.as_slice())?;
+
+    // Rows come best first, so a session's first similar chunk is its best
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    while results.len() < limit {
+        let Some(row) = rows.next()? else {
+            break;
+        };
+        let session_id: String = row.get(0)?;
+        let text: String = row.get(2)?;
+        if seen.contains(&session_id) || !fts_query::shares_trigrams(&words, &text) {
+            continue;
+        }
+        seen.insert(session_id.clone());
+        results.push(FtsResult {
+            session_id,
+            rank: row.get(1)?,
+        });
+    }
+    Ok(results)
+}
+
+/// index_meta key of the chunk_embeddings change counter
+pub const VECTOR_GENERATION_KEY: &str = "vector_generation";
+
 /// Vector similarity search — loads embeddings and computes cosine similarity in Rust.
 /// Uses chunk embeddings if available, falls back to session embeddings.
+/// With `lists`, only chunks in those ANN lists (or not yet assigned to one) are scanned.
 pub fn vec_search(
     conn: &Connection,
     query_embedding: &[f32],
     limit: usize,
+    filter: &SearchFilter,
+    lists: Option<&[i64]>,
+    quantization: VectorQuantization,
 ) -> Result<Vec<VecResult>> {
     let has_chunk_embeddings: bool = conn
         .query_row(
@@ -247,11 +811,17 @@ pub fn vec_search(
         )
         .unwrap_or(false);
 
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 1);
+    let params_refs: Vec<&dyn ToSql> = filter_params.iter().map(|p| p.as_ref()).collect();
+
     if has_chunk_embeddings {
-        let mut stmt =
-            conn.prepare("SELECT session_id, embedding FROM chunk_embeddings")?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT session_id, embedding FROM chunk_embeddings WHERE 1=1{}{}",
+            ann_list_clause(lists),
+            filter_sql
+        ))?;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params_refs.as_slice(), |row| {
             let session_id: String = row.get(0)?;
             let blob: Vec<u8> = row.get(1)?;
             Ok((session_id, blob))
@@ -262,7 +832,7 @@ pub fn vec_search(
         for row in rows {
             match row {
                 Ok((session_id, blob)) => {
-                    let embedding = bytes_to_embedding(&blob);
+                    let embedding = decode_stored_embedding(&blob, quantization);
                     let sim = cosine_similarity(query_embedding, &embedding);
                     let entry = best_per_session.entry(session_id).or_insert(f64::MIN);
                     if sim > *entry {
@@ -287,9 +857,12 @@ pub fn vec_search(
     }
 
     // Fall back to session-level embeddings
-    let mut stmt = conn.prepare("SELECT session_id, embedding FROM session_embeddings")?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT session_id, embedding FROM session_embeddings WHERE 1=1{}",
+        filter_sql
+    ))?;
 
-    let rows = stmt.query_map([], |row| {
+    let rows = stmt.query_map(params_refs.as_slice(), |row| {
         let session_id: String = row.get(0)?;
         let blob: Vec<u8> = row.get(1)?;
         Ok((session_id, blob))
@@ -319,6 +892,136 @@ pub fn vec_search(
         .collect())
 }
 
+/// Two-pass vector search for binary quantization: scores every binary code in
+/// chunk_codes (keeping the best chunk per session), then rescores the chunks
+/// of the top `limit × rescore_factor` sessions with their stored int8 codes.
+/// Falls back to `vec_search` when there are no codes.
+pub fn vec_search_binary(
+    conn: &Connection,
+    query_embedding: &[f32],
+    limit: usize,
+    filter: &SearchFilter,
+    lists: Option<&[i64]>,
+) -> Result<Vec<VecResult>> {
+    let quantization = VectorQuantization::Binary;
+    let has_codes: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM chunk_codes LIMIT 1)",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+    if !has_codes {
+        return vec_search(conn, query_embedding, limit, filter, lists, quantization);
+    }
+
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 1);
+    let params_refs: Vec<&dyn ToSql> = filter_params.iter().map(|p| p.as_ref()).collect();
+    let list_sql = match lists {
+        Some(_) => format!(
+            " AND chunk_id IN (SELECT chunk_id FROM chunk_embeddings WHERE 1=1{})",
+            ann_list_clause(lists)
+        ),
+        None => String::new(),
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT session_id, code FROM chunk_codes WHERE 1=1{}{}",
+        list_sql, filter_sql
+    ))?;
+    let rows = stmt.query_map(params_refs.as_slice(), |row| {
+        let session_id: String = row.get(0)?;
+        let code: Vec<u8> = row.get(1)?;
+        Ok((session_id, code))
+    })?;
+
+    // First pass: approximate score of each session's best chunk
+    let query_code = QueryCode::new(query_embedding, quantization);
+    let mut best_per_session: HashMap<String, f32> = HashMap::new();
+    for row in rows {
+        match row {
+            Ok((session_id, code)) => {
+                let score = query_code.score(&code);
+                let entry = best_per_session.entry(session_id).or_insert(f32::MIN);
+                if score > *entry {
+                    *entry = score;
+                }
+            }
+            Err(e) => log::warn!("Vec code query row error: {}", e),
+        }
+    }
+
+    let mut candidates: Vec<(String, f32)> = best_per_session.into_iter().collect();
+    candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    candidates.truncate(limit * quantization.rescore_factor());
+
+    // Second pass: similarity with the int8 codes of the candidate sessions' chunks
+    let mut stmt = conn.prepare(&format!(
+        "SELECT embedding FROM chunk_embeddings WHERE session_id = ?1{}",
+        ann_list_clause(lists)
+    ))?;
+    let mut scored: Vec<(String, f64)> = Vec::with_capacity(candidates.len());
+    for (session_id, _) in candidates {
+        let mut best = f64::MIN;
+        let blobs = stmt.query_map(params![session_id], |row| row.get::<_, Vec<u8>>(0))?;
+        for blob in blobs {
+            best = best.max(cosine_similarity(
+                query_embedding,
+                &decode_stored_embedding(&blob?, quantization),
+            ));
+        }
+        scored.push((session_id, best));
+    }
+
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(limit);
+
+    Ok(scored
+        .into_iter()
+        .map(|(session_id, sim)| VecResult {
+            session_id,
+            distance: 1.0 - sim,
+        })
+        .collect())
+}
+
+/// SQL restricting chunk_embeddings rows to the given ANN lists, plus rows not yet
+/// assigned to a list (empty when `lists` is None)
+fn ann_list_clause(lists: Option<&[i64]>) -> String {
+    match lists {
+        Some(lists) => {
+            let ids: Vec<String> = lists.iter().map(|id| id.to_string()).collect();
+            format!(" AND (list_id IS NULL OR list_id IN ({}))", ids.join(","))
+        }
+        None => String::new(),
+    }
+}
+
+/// Lists sessions matching a filter, most recently active first.
+/// Used when a query has qualifiers but no free text to rank by.
+pub fn filter_sessions(
+    conn: &Connection,
+    filter: &SearchFilter,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let (filter_sql, filter_params) = session_filter_clause(filter, "session_id", 2);
+    let mut param_values: Vec<Box<dyn ToSql>> = vec![Box::new(limit as i64)];
+    param_values.extend(filter_params);
+    let params_refs: Vec<&dyn ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT session_id FROM sessions WHERE 1=1{} ORDER BY modified_at DESC LIMIT ?1",
+        filter_sql
+    ))?;
+    let rows = stmt.query_map(params_refs.as_slice(), |row| row.get(0))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
 /// Gets a full session row by ID
 pub fn get_session(conn: &Connection, session_id: &str) -> Result<Option<SessionRow>> {
     let mut stmt = conn.prepare(
@@ -348,36 +1051,29 @@ pub fn get_session(conn: &Connection, session_id: &str) -> Result<Option<Session
     Ok(result)
 }
 
-/// Lists sessions with optional filtering
+/// Lists sessions with optional filtering. Days and project match the same way
+/// as the search filters: by last activity, and as a glob or substring.
 pub fn list_sessions(
     conn: &Connection,
     days: Option<u32>,
     project: Option<&str>,
     limit: usize,
 ) -> Result<Vec<SessionRow>> {
-    let mut sql = String::from(
+    let mut filter = SearchFilter {
+        project: project.map(str::to_string),
+        ..Default::default()
+    };
+    if let Some(days) = days {
+        filter = filter.with_days(days);
+    }
+    let (clause, mut param_values) = session_filter_clause(&filter, "session_id", 1);
+    let sql = format!(
         "SELECT session_id, project_path, first_prompt, summary, slug,
                 git_branch, message_count, created_at, modified_at, full_text
-         FROM sessions WHERE 1=1",
+         FROM sessions WHERE 1 = 1{} ORDER BY modified_at DESC LIMIT ?{}",
+        clause,
+        param_values.len() + 1
     );
-
-    let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-    let mut param_idx = 1;
-
-    if let Some(days) = days {
-        let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
-        sql.push_str(&format!(" AND created_at >= ?{}", param_idx));
-        param_values.push(Box::new(cutoff.to_rfc3339()));
-        param_idx += 1;
-    }
-
-    if let Some(project) = project {
-        sql.push_str(&format!(" AND project_path LIKE ?{}", param_idx));
-        param_values.push(Box::new(format!("%{}%", project)));
-        param_idx += 1;
-    }
-
-    sql.push_str(&format!(" ORDER BY modified_at DESC LIMIT ?{}", param_idx));
     param_values.push(Box::new(limit as i64));
 
     let mut stmt = conn.prepare(&sql)?;
@@ -406,13 +1102,162 @@ pub fn list_sessions(
     Ok(results)
 }
 
+/// Builds an SQL predicate (starting with " AND") that restricts `column` to sessions
+/// matching the filter. Orphaned sessions are always excluded.
+/// Note that `LIKE` only folds ASCII case.
+/// Placeholders are numbered from `first_param`.
+fn session_filter_clause(
+    filter: &SearchFilter,
+    column: &str,
+    first_param: usize,
+) -> (String, Vec<Box<dyn ToSql>>) {
+    let mut param_values: Vec<Box<dyn ToSql>> = Vec::new();
+    let mut param_idx = first_param;
+
+    // Session metadata conditions share one subquery on the sessions table
+    let mut session_conds = String::from("orphaned = 0");
+
+    if let Some(ref after) = filter.after {
+        session_conds.push_str(&format!(
+            " AND julianday(modified_at) >= julianday(?{})",
+            param_idx
+        ));
+        param_values.push(Box::new(after.to_rfc3339()));
+        param_idx += 1;
+    }
+
+    if let Some(ref before) = filter.before {
+        session_conds.push_str(&format!(
+            " AND julianday(modified_at) < julianday(?{})",
+            param_idx
+        ));
+        param_values.push(Box::new(before.to_rfc3339()));
+        param_idx += 1;
+    }
+
+    for (pattern, field) in [
+        (&filter.project, "project_path"),
+        (&filter.branch, "git_branch"),
+    ] {
+        if let Some(pattern) = pattern {
+            if is_glob(pattern) {
+                session_conds.push_str(&format!(" AND {} GLOB ?{}", field, param_idx));
+                param_values.push(Box::new(pattern.clone()));
+            } else {
+                session_conds.push_str(&format!(" AND {} LIKE ?{}", field, param_idx));
+                param_values.push(Box::new(format!("%{}%", pattern)));
+            }
+            param_idx += 1;
+        }
+    }
+
+    if let Some(ref prefix) = filter.session_id {
+        session_conds.push_str(&format!(" AND session_id LIKE ?{}", param_idx));
+        param_values.push(Box::new(format!("{}%", prefix)));
+        param_idx += 1;
+    }
+
+    match filter.kind {
+        Some(SessionKind::Agent) => session_conds.push_str(" AND session_id LIKE 'agent-%'"),
+        Some(SessionKind::Main) => session_conds.push_str(" AND session_id NOT LIKE 'agent-%'"),
+        None => {}
+    }
+
+    for excluded in &filter.exclude_projects {
+        session_conds.push_str(&format!(" AND project_path NOT LIKE ?{}", param_idx));
+        param_values.push(Box::new(format!("%{}%", excluded)));
+        param_idx += 1;
+    }
+
+    let mut sql = format!(
+        " AND {} IN (SELECT session_id FROM sessions WHERE {})",
+        column, session_conds
+    );
+
+    if let Some(ref role) = filter.role {
+        let mut message_conds = format!("role = ?{}", param_idx);
+        param_values.push(Box::new(role.clone()));
+        param_idx += 1;
+        if let Some((cond, patterns)) =
+            fts_query::message_condition(&filter.role_text, "text", param_idx)
+        {
+            message_conds.push_str(&format!(" AND {}", cond));
+            param_idx += patterns.len();
+            param_values.extend(patterns.into_iter().map(|p| Box::new(p) as Box<dyn ToSql>));
+        }
+        sql.push_str(&format!(
+            " AND {} IN (SELECT session_id FROM messages WHERE {})",
+            column, message_conds
+        ));
+    }
+
+    for term in &filter.exclude_terms {
+        sql.push_str(&format!(
+            " AND {col} NOT IN (SELECT session_id FROM chunks_fts WHERE chunks_fts MATCH ?{idx}
+                 UNION SELECT session_id FROM sessions_fts WHERE sessions_fts MATCH ?{idx})",
+            col = column,
+            idx = param_idx
+        ));
+        // Match the term as a literal FTS5 phrase
+        param_values.push(Box::new(format!("\"{}\"", term.replace('"', "\"\""))));
+        param_idx += 1;
+    }
+
+    if let Some(ref tool) = filter.tool {
+        sql.push_str(&format!(
+            " AND {} IN (SELECT session_id FROM tool_calls WHERE tool_name = ?{} COLLATE NOCASE)",
+            column, param_idx
+        ));
+        param_values.push(Box::new(tool.clone()));
+        param_idx += 1;
+    }
+
+    if let Some(ref file) = filter.file {
+        sql.push_str(&format!(
+            " AND {} IN (SELECT session_id FROM tool_calls WHERE file_path LIKE ?{})",
+            column, param_idx
+        ));
+        param_values.push(Box::new(format!("%{}%", file)));
+        param_idx += 1;
+    }
+
+    if let Some(ref command) = filter.command {
+        sql.push_str(&format!(
+            " AND {} IN (SELECT session_id FROM tool_calls WHERE command LIKE ?{})",
+            column, param_idx
+        ));
+        param_values.push(Box::new(format!("%{}%", command)));
+    }
+
+    (sql, param_values)
+}
+
 /// Converts f32 slice to little-endian bytes for storage
 fn embedding_to_bytes(embedding: &[f32]) -> Vec<u8> {
     embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
 }
 
+/// A chunk embedding as kept in chunk_embeddings: little-endian f32s, or its int8
+/// code when quantization is on (binary codes are kept next to it in chunk_codes)
+pub fn stored_embedding(embedding: &[f32], quantization: VectorQuantization) -> Vec<u8> {
+    match quantization {
+        VectorQuantization::None => embedding_to_bytes(embedding),
+        VectorQuantization::Int8 | VectorQuantization::Binary => {
+            quantize::encode(embedding, VectorQuantization::Int8)
+        }
+    }
+}
+
+/// Reads back a chunk embedding written by `stored_embedding`
+pub fn decode_stored_embedding(bytes: &[u8], quantization: VectorQuantization) -> Vec<f32> {
+    match quantization {
+        VectorQuantization::None => bytes_to_embedding(bytes),
+        VectorQuantization::Int8 | VectorQuantization::Binary => quantize::decode_int8(bytes),
+    }
+}
+
 /// Converts little-endian bytes back to f32 slice
-fn bytes_to_embedding(bytes: &[u8]) -> Vec<f32> {
+pub fn bytes_to_embedding(bytes: &[u8]) -> Vec<f32> {
     bytes
         .chunks_exact(4)
         .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
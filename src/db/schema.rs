@@ -1,26 +1,204 @@
 use anyhow::Result;
-use rusqlite::Connection;
+use rusqlite::{params, Connection, Transaction, TransactionBehavior};
 
-/// Creates all tables and triggers for the ccsearch database
-pub fn create_schema(conn: &Connection) -> Result<()> {
-    conn.execute_batch(
-        "
-        -- Session metadata
-        CREATE TABLE IF NOT EXISTS sessions (
-            session_id TEXT PRIMARY KEY,
-            project_path TEXT NOT NULL,
-            first_prompt TEXT,
-            summary TEXT,
-            slug TEXT,
-            git_branch TEXT,
-            message_count INTEGER,
-            created_at TEXT NOT NULL,
-            modified_at TEXT NOT NULL,
-            file_mtime INTEGER NOT NULL,
-            indexed_at TEXT NOT NULL,
-            full_text TEXT NOT NULL DEFAULT ''
-        );
+use super::queries;
+use crate::config::{Config, VectorQuantization};
+use crate::indexer::{pipeline, tokenizer};
+use crate::model::ModelSpec;
+use crate::search::quantize;
+
+/// Optional parts of the schema, chosen in config
+#[derive(Debug, Clone, Default)]
+pub struct SchemaOptions {
+    /// Keep a trigram FTS5 index over chunks (typo-tolerant matching)
+    pub trigram_index: bool,
+    /// Tokenizer of the keyword indexes (chunks_fts, sessions_fts)
+    pub tokenizer: FtsTokenizer,
+    /// Encoding of the stored chunk embeddings (and binary codes in chunk_codes)
+    pub quantization: VectorQuantization,
+    /// Parameters chunks are cut with (see `pipeline::chunking_fingerprint`); None skips the check
+    pub chunking: Option<String>,
+    /// Model chunk embeddings come from (see `ModelSpec::id`); None skips the check
+    pub embedding_model: Option<String>,
+}
+
+impl From<&Config> for SchemaOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            trigram_index: config.trigram_index,
+            tokenizer: FtsTokenizer {
+                stemming: config.stemming,
+                remove_diacritics: config.remove_diacritics.min(2),
+                cjk_bigrams: config.cjk_bigrams,
+            },
+            quantization: config.vector_quantization,
+            chunking: Some(pipeline::chunking_fingerprint(config)),
+            // An invalid model can't embed anything, so there is nothing to compare
+            embedding_model: ModelSpec::from_config(config).ok().map(|spec| spec.id()),
+        }
+    }
+}
+
+/// How the keyword indexes split text into tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct FtsTokenizer {
+    /// Porter stemming on top of unicode61
+    pub stemming: bool,
+    /// unicode61 `remove_diacritics` option (0, 1 or 2)
+    pub remove_diacritics: u8,
+    /// Index CJK runs as bigrams (in chunks.code_tokens)
+    pub cjk_bigrams: bool,
+}
+
+impl Default for FtsTokenizer {
+    fn default() -> Self {
+        Self {
+            stemming: false,
+            remove_diacritics: 1,
+            cjk_bigrams: false,
+        }
+    }
+}
+
+impl FtsTokenizer {
+    /// The FTS5 `tokenize` argument
+    fn spec(&self) -> String {
+        let base = format!("unicode61 remove_diacritics {}", self.remove_diacritics);
+        if self.stemming {
+            format!("porter {}", base)
+        } else {
+            base
+        }
+    }
+
+    /// Identifies the tokenization an index was built with (stored in index_meta)
+    fn fingerprint(&self) -> String {
+        if self.cjk_bigrams {
+            format!("{} +cjk_bigrams", self.spec())
+        } else {
+            self.spec()
+        }
+    }
+}
+
+/// index_meta key holding the tokenizer fingerprint of the keyword indexes
+const FTS_TOKENIZER_KEY: &str = "fts_tokenizer";
+
+/// index_meta key holding the encoding of chunk_embeddings (see `VectorQuantization`)
+const QUANTIZATION_KEY: &str = "vector_quantization";
+
+/// index_meta key holding the chunking parameters of the stored chunks
+const CHUNKING_KEY: &str = "chunking_params";
+
+/// index_meta key holding the model the stored embeddings come from
+const EMBEDDING_MODEL_KEY: &str = "embedding_model";
 
+/// Indexing parameters that changed since the index was built. The parts built
+/// with the old ones have been invalidated and are rebuilt by the next index run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexDrift {
+    /// Old and new chunking parameters: every session is re-chunked
+    pub chunking: Option<(String, String)>,
+    /// Old and new embedding model: every chunk is re-embedded
+    pub embedding_model: Option<(String, String)>,
+}
+
+/// Triggers keeping the keyword indexes in sync with their tables
+const FTS_TRIGGERS: &[&str] = &[
+    "sessions_ai",
+    "sessions_ad",
+    "sessions_au",
+    "chunks_ai",
+    "chunks_ad",
+    "chunks_au",
+    "tool_calls_ai",
+    "tool_calls_ad",
+    "tool_calls_au",
+];
+
+/// Triggers bumping the vector store generation
+const VECTOR_GENERATION_TRIGGERS: &[&str] = &[
+    "chunk_embeddings_gen_ai",
+    "chunk_embeddings_gen_ad",
+    "chunk_embeddings_gen_au",
+];
+
+/// Triggers keeping the trigram index in sync with chunks
+const TRIGRAM_TRIGGERS: &[&str] = &[
+    "chunks_trigram_ai",
+    "chunks_trigram_ad",
+    "chunks_trigram_au",
+];
+
+/// True if the keyword indexes, triggers and optional parts already match
+/// `options`, so `create_schema` and `create_vec_table` have nothing to do.
+/// Only reads, so it doesn't wait for a process writing to the database.
+/// The tables must be at the current schema version (see `migrations::is_current`).
+pub fn is_current(conn: &Connection, options: &SchemaOptions) -> Result<bool> {
+    let tokenizer = queries::get_meta(conn, FTS_TOKENIZER_KEY)?;
+    if tokenizer.as_deref() != Some(options.tokenizer.fingerprint().as_str())
+        || !column_exists(conn, "chunks_fts", "code_tokens")?
+        || !table_exists(conn, "sessions_fts")?
+        || !table_exists(conn, "tool_calls_fts")?
+        || table_exists(conn, "chunks_trigram")? != options.trigram_index
+    {
+        return Ok(false);
+    }
+
+    let mut triggers = [FTS_TRIGGERS, VECTOR_GENERATION_TRIGGERS].concat();
+    if options.trigram_index {
+        triggers.extend_from_slice(TRIGRAM_TRIGGERS);
+    }
+    if options.quantization == VectorQuantization::Binary {
+        triggers.push("chunk_embeddings_codes_ad");
+    }
+    for trigger in triggers {
+        if !trigger_exists(conn, trigger)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(stored_quantization(conn)? == options.quantization
+        && table_exists(conn, "chunk_codes")?
+            == (options.quantization == VectorQuantization::Binary))
+}
+
+/// Creates the keyword indexes, triggers and optional indexes over the tables
+/// of the current schema version (see `migrations`)
+pub fn create_schema(conn: &Connection, options: &SchemaOptions) -> Result<()> {
+    // Keyword indexes built with a different tokenizer are rebuilt. Databases from
+    // before the setting existed used the default tokenizer.
+    let tokenizer_changed = queries::get_meta(conn, FTS_TOKENIZER_KEY)?
+        .unwrap_or_else(|| FtsTokenizer::default().fingerprint())
+        != options.tokenizer.fingerprint();
+
+    // chunks_fts also gained a code_tokens column; older databases recreate it
+    let rebuild_chunks_fts = table_exists(conn, "chunks_fts")?
+        && (tokenizer_changed || !column_exists(conn, "chunks_fts", "code_tokens")?);
+    if rebuild_chunks_fts {
+        conn.execute_batch(
+            "
+            DROP TRIGGER IF EXISTS chunks_ai;
+            DROP TRIGGER IF EXISTS chunks_ad;
+            DROP TRIGGER IF EXISTS chunks_au;
+            DROP TABLE chunks_fts;
+            ",
+        )?;
+    }
+    let rebuild_sessions_fts = tokenizer_changed && table_exists(conn, "sessions_fts")?;
+    if rebuild_sessions_fts {
+        conn.execute_batch(
+            "
+            DROP TRIGGER IF EXISTS sessions_ai;
+            DROP TRIGGER IF EXISTS sessions_ad;
+            DROP TRIGGER IF EXISTS sessions_au;
+            DROP TABLE sessions_fts;
+            ",
+        )?;
+    }
+
+    // Keyword indexes over the tables created by `migrations`
+    let tables = "
         -- FTS5 virtual table for BM25 keyword search
         CREATE VIRTUAL TABLE IF NOT EXISTS sessions_fts USING fts5(
             session_id UNINDEXED,
@@ -28,22 +206,8 @@ pub fn create_schema(conn: &Connection) -> Result<()> {
             summary,
             full_text,
             content='sessions',
-            content_rowid='rowid'
-        );
-
-        -- Index metadata for staleness tracking
-        CREATE TABLE IF NOT EXISTS index_meta (
-            key TEXT PRIMARY KEY,
-            value TEXT
-        );
-
-        -- Conversation chunks for fine-grained search
-        CREATE TABLE IF NOT EXISTS chunks (
-            chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
-            session_id TEXT NOT NULL,
-            chunk_index INTEGER NOT NULL,
-            text TEXT NOT NULL DEFAULT '',
-            UNIQUE(session_id, chunk_index)
+            content_rowid='rowid',
+            tokenize='{tokenizer}'
         );
 
         -- FTS5 on chunks for BM25 keyword search
@@ -51,15 +215,43 @@ pub fn create_schema(conn: &Connection) -> Result<()> {
         --   FTS5[0] session_id  -> chunks.session_id
         --   FTS5[1] chunk_index -> chunks.chunk_index
         --   FTS5[2] text        -> chunks.text
+        --   FTS5[3] code_tokens -> chunks.code_tokens (camelCase parts of identifiers)
         CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
             session_id UNINDEXED,
             chunk_index UNINDEXED,
             text,
+            code_tokens,
             content='chunks',
-            content_rowid='chunk_id'
+            content_rowid='chunk_id',
+            tokenize='{tokenizer}'
         );
-        ",
-    )?;
+
+        -- FTS5 on tool calls so file paths and shell commands are keyword-searchable
+        CREATE VIRTUAL TABLE IF NOT EXISTS tool_calls_fts USING fts5(
+            session_id UNINDEXED,
+            tool_name,
+            file_path,
+            command,
+            pattern,
+            content='tool_calls',
+            content_rowid='call_id'
+        );
+        "
+    .replace("{tokenizer}", &options.tokenizer.spec());
+    conn.execute_batch(&tables)?;
+
+    // Chunks stored before chunks_fts had code_tokens (or under another tokenizer)
+    // get their identifier parts recomputed
+    if rebuild_chunks_fts {
+        backfill_code_tokens(conn, options.tokenizer.cjk_bigrams)?;
+        log::info!("Rebuilding chunk keyword index");
+        conn.execute_batch("INSERT INTO chunks_fts(chunks_fts) VALUES ('rebuild')")?;
+    }
+    if rebuild_sessions_fts {
+        log::info!("Rebuilding session keyword index");
+        conn.execute_batch("INSERT INTO sessions_fts(sessions_fts) VALUES ('rebuild')")?;
+    }
+    queries::set_meta(conn, FTS_TOKENIZER_KEY, &options.tokenizer.fingerprint())?;
 
     // Create triggers (use IF NOT EXISTS workaround: drop and recreate)
     conn.execute_batch(
@@ -86,44 +278,421 @@ pub fn create_schema(conn: &Connection) -> Result<()> {
 
         DROP TRIGGER IF EXISTS chunks_ai;
         CREATE TRIGGER chunks_ai AFTER INSERT ON chunks BEGIN
-            INSERT INTO chunks_fts(rowid, session_id, chunk_index, text)
-            VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text);
+            INSERT INTO chunks_fts(rowid, session_id, chunk_index, text, code_tokens)
+            VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text, new.code_tokens);
         END;
 
         DROP TRIGGER IF EXISTS chunks_ad;
         CREATE TRIGGER chunks_ad AFTER DELETE ON chunks BEGIN
-            INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text)
-            VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text);
+            INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text, code_tokens)
+            VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text, old.code_tokens);
         END;
 
         DROP TRIGGER IF EXISTS chunks_au;
         CREATE TRIGGER chunks_au AFTER UPDATE ON chunks BEGIN
-            INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text)
+            INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text, code_tokens)
+            VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text, old.code_tokens);
+            INSERT INTO chunks_fts(rowid, session_id, chunk_index, text, code_tokens)
+            VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text, new.code_tokens);
+        END;
+
+        DROP TRIGGER IF EXISTS tool_calls_ai;
+        CREATE TRIGGER tool_calls_ai AFTER INSERT ON tool_calls BEGIN
+            INSERT INTO tool_calls_fts(rowid, session_id, tool_name, file_path, command, pattern)
+            VALUES (new.call_id, new.session_id, new.tool_name, new.file_path, new.command, new.pattern);
+        END;
+
+        DROP TRIGGER IF EXISTS tool_calls_ad;
+        CREATE TRIGGER tool_calls_ad AFTER DELETE ON tool_calls BEGIN
+            INSERT INTO tool_calls_fts(tool_calls_fts, rowid, session_id, tool_name, file_path, command, pattern)
+            VALUES ('delete', old.call_id, old.session_id, old.tool_name, old.file_path, old.command, old.pattern);
+        END;
+
+        DROP TRIGGER IF EXISTS tool_calls_au;
+        CREATE TRIGGER tool_calls_au AFTER UPDATE ON tool_calls BEGIN
+            INSERT INTO tool_calls_fts(tool_calls_fts, rowid, session_id, tool_name, file_path, command, pattern)
+            VALUES ('delete', old.call_id, old.session_id, old.tool_name, old.file_path, old.command, old.pattern);
+            INSERT INTO tool_calls_fts(rowid, session_id, tool_name, file_path, command, pattern)
+            VALUES (new.call_id, new.session_id, new.tool_name, new.file_path, new.command, new.pattern);
+        END;
+        ",
+    )?;
+
+    if options.trigram_index {
+        create_trigram_index(conn)?;
+    } else {
+        drop_trigram_index(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Creates the trigram index over chunks, filling it from existing chunks when new
+fn create_trigram_index(conn: &Connection) -> Result<()> {
+    let existed = table_exists(conn, "chunks_trigram")?;
+    conn.execute_batch(
+        "
+        -- Trigram FTS5 on chunks for typo-tolerant matching (same columns as chunks_fts)
+        CREATE VIRTUAL TABLE IF NOT EXISTS chunks_trigram USING fts5(
+            session_id UNINDEXED,
+            chunk_index UNINDEXED,
+            text,
+            content='chunks',
+            content_rowid='chunk_id',
+            tokenize='trigram'
+        );
+
+        DROP TRIGGER IF EXISTS chunks_trigram_ai;
+        CREATE TRIGGER chunks_trigram_ai AFTER INSERT ON chunks BEGIN
+            INSERT INTO chunks_trigram(rowid, session_id, chunk_index, text)
+            VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text);
+        END;
+
+        DROP TRIGGER IF EXISTS chunks_trigram_ad;
+        CREATE TRIGGER chunks_trigram_ad AFTER DELETE ON chunks BEGIN
+            INSERT INTO chunks_trigram(chunks_trigram, rowid, session_id, chunk_index, text)
+            VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text);
+        END;
+
+        DROP TRIGGER IF EXISTS chunks_trigram_au;
+        CREATE TRIGGER chunks_trigram_au AFTER UPDATE ON chunks BEGIN
+            INSERT INTO chunks_trigram(chunks_trigram, rowid, session_id, chunk_index, text)
             VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text);
-            INSERT INTO chunks_fts(rowid, session_id, chunk_index, text)
+            INSERT INTO chunks_trigram(rowid, session_id, chunk_index, text)
             VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text);
         END;
         ",
     )?;
 
+    if !existed {
+        // Index chunks stored before the trigram index was enabled
+        conn.execute_batch("INSERT INTO chunks_trigram(chunks_trigram) VALUES ('rebuild')")?;
+    }
     Ok(())
 }
 
-/// Creates the vector embedding tables (plain tables with blob storage)
-pub fn create_vec_table(conn: &Connection) -> Result<()> {
+/// Removes the trigram index and its triggers when it has been turned off
+fn drop_trigram_index(conn: &Connection) -> Result<()> {
     conn.execute_batch(
         "
-        CREATE TABLE IF NOT EXISTS session_embeddings (
-            session_id TEXT PRIMARY KEY,
-            embedding BLOB NOT NULL
-        );
+        DROP TRIGGER IF EXISTS chunks_trigram_ai;
+        DROP TRIGGER IF EXISTS chunks_trigram_ad;
+        DROP TRIGGER IF EXISTS chunks_trigram_au;
+        DROP TABLE IF EXISTS chunks_trigram;
+        ",
+    )?;
+    Ok(())
+}
+
+/// Sets up what is derived from the embedding tables: the generation counter
+/// triggers of the memory-mapped vector store, the encoding of the stored
+/// embeddings and the binary codes
+pub fn create_vec_table(conn: &Connection, options: &SchemaOptions) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- Any change to chunk_embeddings makes the memory-mapped vector store stale
+        CREATE TRIGGER IF NOT EXISTS chunk_embeddings_gen_ai AFTER INSERT ON chunk_embeddings BEGIN
+            UPDATE index_meta SET value = CAST(value AS INTEGER) + 1 WHERE key = 'vector_generation';
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunk_embeddings_gen_ad AFTER DELETE ON chunk_embeddings BEGIN
+            UPDATE index_meta SET value = CAST(value AS INTEGER) + 1 WHERE key = 'vector_generation';
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS chunk_embeddings_gen_au AFTER UPDATE ON chunk_embeddings BEGIN
+            UPDATE index_meta SET value = CAST(value AS INTEGER) + 1 WHERE key = 'vector_generation';
+        END;
+        ",
+    )?;
+
+    let stored = stored_quantization(conn)?;
+    let mode = options.quantization;
+    if stored == mode {
+        return Ok(());
+    }
+
+    match (stored, mode) {
+        (VectorQuantization::None, _) => encode_int8_embeddings(conn)?,
+        (_, VectorQuantization::None) => discard_int8_embeddings(conn)?,
+        // int8 and binary store the same int8 embeddings
+        _ => {}
+    }
+    if mode == VectorQuantization::Binary {
+        create_chunk_codes(conn)?;
+    } else {
+        drop_chunk_codes(conn)?;
+    }
+    queries::set_meta(conn, QUANTIZATION_KEY, mode.as_str())?;
 
-        CREATE TABLE IF NOT EXISTS chunk_embeddings (
+    if stored == VectorQuantization::None {
+        // Give the space of the full-precision vectors back to the file system
+        conn.execute_batch("VACUUM")?;
+    }
+    Ok(())
+}
+
+/// Encoding of the stored chunk embeddings. Databases from before the setting
+/// existed hold full-precision vectors.
+fn stored_quantization(conn: &Connection) -> Result<VectorQuantization> {
+    Ok(
+        match queries::get_meta(conn, QUANTIZATION_KEY)?.as_deref() {
+            Some("int8") => VectorQuantization::Int8,
+            Some("binary") => VectorQuantization::Binary,
+            _ => VectorQuantization::None,
+        },
+    )
+}
+
+/// Replaces every full-precision chunk embedding with its int8 code
+fn encode_int8_embeddings(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut select = tx.prepare("SELECT chunk_id, embedding FROM chunk_embeddings")?;
+        let mut update =
+            tx.prepare("UPDATE chunk_embeddings SET embedding = ?2 WHERE chunk_id = ?1")?;
+        let rows = select.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+        for row in rows {
+            let (chunk_id, blob) = row?;
+            let code = queries::stored_embedding(
+                &queries::bytes_to_embedding(&blob),
+                VectorQuantization::Int8,
+            );
+            update.execute(params![chunk_id, code])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Deletes the int8 chunk embeddings when quantization has been turned off:
+/// full precision can't be recovered from them, so the next index run
+/// re-embeds every chunk
+fn discard_int8_embeddings(conn: &Connection) -> Result<()> {
+    log::info!("Vector quantization turned off, re-embedding chunks at full precision");
+    conn.execute_batch(
+        "
+        BEGIN;
+        DELETE FROM chunk_embeddings;
+        DELETE FROM ann_centroids;
+        UPDATE chunks SET embedding_pending = 1;
+        COMMIT;
+        ",
+    )?;
+    Ok(())
+}
+
+/// Creates the binary codes of chunk embeddings, encoding every stored one
+fn create_chunk_codes(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        -- Binary codes scanned by the first pass of vector search; kept in a
+        -- separate table so the scan doesn't read the int8 embeddings
+        DROP TABLE IF EXISTS chunk_codes;
+        CREATE TABLE chunk_codes (
             chunk_id INTEGER PRIMARY KEY,
             session_id TEXT NOT NULL,
-            embedding BLOB NOT NULL
+            code BLOB NOT NULL
         );
+
+        DROP TRIGGER IF EXISTS chunk_embeddings_codes_ad;
+        CREATE TRIGGER chunk_embeddings_codes_ad AFTER DELETE ON chunk_embeddings BEGIN
+            DELETE FROM chunk_codes WHERE chunk_id = old.chunk_id;
+        END;
         ",
     )?;
+    {
+        let mut select =
+            tx.prepare("SELECT chunk_id, session_id, embedding FROM chunk_embeddings")?;
+        let mut insert =
+            tx.prepare("INSERT INTO chunk_codes (chunk_id, session_id, code) VALUES (?1, ?2, ?3)")?;
+        let rows = select.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (chunk_id, session_id, blob) = row?;
+            let embedding = quantize::decode_int8(&blob);
+            let code = quantize::encode(&embedding, VectorQuantization::Binary);
+            insert.execute(params![chunk_id, session_id, code])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Removes the binary codes when binary quantization has been turned off
+fn drop_chunk_codes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        DROP TRIGGER IF EXISTS chunk_embeddings_codes_ad;
+        DROP TABLE IF EXISTS chunk_codes;
+        ",
+    )?;
+    Ok(())
+}
+
+/// Recomputes the extra index tokens of every chunk (after an upgrade or a tokenizer change)
+fn backfill_code_tokens(conn: &Connection, cjk_bigrams: bool) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut select = tx.prepare("SELECT chunk_id, text FROM chunks")?;
+        let mut update = tx.prepare("UPDATE chunks SET code_tokens = ?1 WHERE chunk_id = ?2")?;
+        let rows = select.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (chunk_id, text) = row?;
+            let tokens = tokenizer::index_tokens(&text, cjk_bigrams);
+            update.execute(params![tokens, chunk_id])?;
+        }
+    }
+    tx.commit()?;
     Ok(())
 }
+
+/// True if a table (or virtual table) has a column with this name
+pub fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+/// Adds a column to an existing table if an older database doesn't have it yet.
+/// Returns true if the column was added.
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> Result<bool> {
+    let exists = column_exists(conn, table, column)?;
+
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, decl
+        ))?;
+    }
+    Ok(!exists)
+}
+
+/// True if a table with this name exists
+pub fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// True if a trigger with this name exists
+fn trigger_exists(conn: &Connection, trigger: &str) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ?1)",
+        [trigger],
+        |row| row.get(0),
+    )?;
+    Ok(exists)
+}
+
+/// Compares the chunking parameters and embedding model in `options` with the
+/// ones recorded in index_meta, without changing anything
+pub fn check_index_params(conn: &Connection, options: &SchemaOptions) -> Result<IndexDrift> {
+    let mut drift = IndexDrift::default();
+
+    if let Some(ref chunking) = options.chunking {
+        // Unknown for databases from before it was recorded: assume the current one
+        if let Some(stored) = queries::get_meta(conn, CHUNKING_KEY)? {
+            if stored != *chunking {
+                drift.chunking = Some((stored, chunking.clone()));
+            }
+        }
+    }
+
+    if let Some(ref model) = options.embedding_model {
+        // Databases from before it was recorded could only use the default model
+        let stored = match queries::get_meta(conn, EMBEDDING_MODEL_KEY)? {
+            Some(stored) => stored,
+            None => ModelSpec::from_config(&Config::default())?.id(),
+        };
+        if stored != *model {
+            drift.embedding_model = Some((stored, model.clone()));
+        }
+    }
+
+    Ok(drift)
+}
+
+/// Invalidates what changed indexing parameters (see `check_index_params`) no
+/// longer match and records the current ones. Only the process holding the
+/// index lock runs this. Must run after `create_vec_table`.
+///
+/// New chunking parameters reset every session's parse checkpoint, so the next
+/// index run re-chunks (and re-embeds) it from scratch. A new embedding model
+/// deletes all vectors and marks every chunk as pending, so the next index run
+/// re-embeds the existing chunks without re-parsing anything.
+pub fn update_index_params(conn: &Connection, options: &SchemaOptions) -> Result<IndexDrift> {
+    let drift = check_index_params(conn, options)?;
+    let mut unrecorded = Vec::new();
+    for (key, value) in [
+        (CHUNKING_KEY, &options.chunking),
+        (EMBEDDING_MODEL_KEY, &options.embedding_model),
+    ] {
+        if let Some(value) = value {
+            if queries::get_meta(conn, key)?.as_deref() != Some(value.as_str()) {
+                unrecorded.push((key, value));
+            }
+        }
+    }
+    if unrecorded.is_empty() {
+        return Ok(drift);
+    }
+
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+
+    if let Some((ref stored, ref chunking)) = drift.chunking {
+        log::info!(
+            "Chunking changed ({} → {}), re-chunking sessions",
+            stored,
+            chunking
+        );
+        tx.execute_batch(
+            "
+            UPDATE sessions SET file_mtime = 0;
+            DELETE FROM session_files;
+            ",
+        )?;
+    }
+
+    if let Some((ref stored, ref model)) = drift.embedding_model {
+        log::info!(
+            "Embedding model changed ({} → {}), re-embedding chunks",
+            stored,
+            model
+        );
+        tx.execute_batch(
+            "
+            DELETE FROM chunk_embeddings;
+            DELETE FROM session_embeddings;
+            DELETE FROM ann_centroids;
+            UPDATE chunks SET embedding_pending = 1;
+            ",
+        )?;
+    }
+
+    for (key, value) in unrecorded {
+        queries::set_meta(&tx, key, value)?;
+    }
+    tx.commit()?;
+    Ok(drift)
+}
@@ -0,0 +1,513 @@
+use anyhow::{bail, Context, Result};
+use memmap2::Mmap;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use zerocopy::FromBytes;
+
+use super::queries;
+use crate::config::VectorQuantization;
+use crate::search::quantize::{self, QueryCode};
+
+/// File signature and format version
+const MAGIC: &[u8; 8] = b"CCSVEC02";
+
+/// magic, generation (u64), dim, rows, lists, sessions, encoding (u32 each), padding
+const HEADER_LEN: usize = 40;
+
+/// list_id (i64), first row (u32), row count (u32)
+const LIST_ENTRY_LEN: usize = 16;
+
+/// List ID of embeddings not assigned to an ANN list (all of them without an ANN index)
+const UNASSIGNED: i64 = -1;
+
+/// Path of the vector store that belongs to a database file (`index.vectors` next to `index.db`)
+pub fn store_path(db_path: &Path) -> PathBuf {
+    db_path.with_extension("vectors")
+}
+
+/// A read-only, memory-mapped copy of every chunk embedding.
+///
+/// Layout (little-endian): header, list directory, then one contiguous matrix
+/// with a row per embedding, grouped by ANN list, the session ordinal of each
+/// row, and the session IDs. Rows are unit-length f32 vectors, or their int8
+/// codes under int8 and binary quantization; binary adds a matrix of sign bits
+/// after the rows. Sections start at 4-byte boundaries. Scoring a query is a
+/// straight scan over a matrix, with no per-row SQLite work.
+///
+/// The store is a derived snapshot: it records the `vector_generation` it was
+/// built from and is only used while that still matches the database.
+pub struct VectorStore {
+    mmap: Mmap,
+    generation: u64,
+    quantization: VectorQuantization,
+    dim: usize,
+    rows: usize,
+    /// (list_id, first row, row count), in file order
+    lists: Vec<(i64, usize, usize)>,
+    matrix_offset: usize,
+    bits_offset: usize,
+    row_sessions_offset: usize,
+    sessions: Vec<String>,
+}
+
+impl VectorStore {
+    /// Maps the store at `path`; None if it doesn't exist
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        if cfg!(target_endian = "big") {
+            return Ok(None);
+        }
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {:?}", path)),
+        };
+        // SAFETY: the store is only ever replaced by renaming a new file over it,
+        // never modified in place, so the mapped pages don't change under us
+        let mmap =
+            unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {:?}", path))?;
+        Self::parse(mmap).map(Some)
+    }
+
+    fn parse(mmap: Mmap) -> Result<Self> {
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            bail!("not a vector store");
+        }
+        let generation = u64::from_le_bytes(mmap[8..16].try_into()?);
+        let dim = read_u32(&mmap, 16)? as usize;
+        let rows = read_u32(&mmap, 20)? as usize;
+        let list_count = read_u32(&mmap, 24)? as usize;
+        let session_count = read_u32(&mmap, 28)? as usize;
+        let quantization = match read_u32(&mmap, 32)? {
+            0 => VectorQuantization::None,
+            1 => VectorQuantization::Int8,
+            2 => VectorQuantization::Binary,
+            other => bail!("unknown vector encoding {}", other),
+        };
+
+        let mut lists = Vec::with_capacity(list_count);
+        for i in 0..list_count {
+            let at = HEADER_LEN + i * LIST_ENTRY_LEN;
+            let list_id = i64::from_le_bytes(slice(&mmap, at, 8)?.try_into()?);
+            let start = read_u32(&mmap, at + 8)? as usize;
+            let count = read_u32(&mmap, at + 12)? as usize;
+            if start + count > rows {
+                bail!("list {} out of range", list_id);
+            }
+            lists.push((list_id, start, count));
+        }
+
+        let matrix_offset = HEADER_LEN + list_count * LIST_ENTRY_LEN;
+        let bits_offset = aligned(matrix_offset + rows * row_len(dim, quantization));
+        let row_sessions_offset = aligned(bits_offset + rows * bits_len(dim, quantization));
+        let mut at = row_sessions_offset + rows * 4;
+        let mut sessions = Vec::with_capacity(session_count);
+        for _ in 0..session_count {
+            let len = read_u32(&mmap, at)? as usize;
+            let name = std::str::from_utf8(slice(&mmap, at + 4, len)?)?;
+            sessions.push(name.to_string());
+            at += 4 + len;
+        }
+
+        let store = Self {
+            mmap,
+            generation,
+            quantization,
+            dim,
+            rows,
+            lists,
+            matrix_offset,
+            bits_offset,
+            row_sessions_offset,
+            sessions,
+        };
+        if store
+            .row_sessions()?
+            .iter()
+            .any(|&s| s as usize >= session_count)
+        {
+            bail!("row session out of range");
+        }
+        Ok(store)
+    }
+
+    /// The `vector_generation` this store was built from
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Number of vectors in the store
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Encoding of the rows
+    pub fn quantization(&self) -> VectorQuantization {
+        self.quantization
+    }
+
+    /// Best cosine similarity per session, highest first, for the sessions in
+    /// `allowed`. With `lists`, only rows in those ANN lists (and unassigned rows)
+    /// are scored.
+    pub fn search(
+        &self,
+        query: &[f32],
+        limit: usize,
+        allowed: &HashSet<String>,
+        lists: Option<&[i64]>,
+    ) -> Result<Vec<(String, f64)>> {
+        if query.len() != self.dim {
+            bail!(
+                "query has {} dimensions, vector store has {}",
+                query.len(),
+                self.dim
+            );
+        }
+        let query = normalized(query);
+        let allowed: Vec<bool> = self.sessions.iter().map(|s| allowed.contains(s)).collect();
+
+        let best = match self.quantization {
+            VectorQuantization::None => {
+                let matrix = self.matrix()?;
+                self.best_per_session(&allowed, lists, |row| {
+                    dot(&query, &matrix[row * self.dim..(row + 1) * self.dim])
+                })?
+            }
+            VectorQuantization::Int8 => self.int8_scores(&query, &allowed, lists)?,
+            VectorQuantization::Binary => {
+                // First pass over the sign bits; the best sessions are rescored
+                // with their int8 rows
+                let code = QueryCode::new(&query, VectorQuantization::Binary);
+                let bits = self.bits()?;
+                let len = bits_len(self.dim, self.quantization);
+                let first = self.best_per_session(&allowed, lists, |row| {
+                    code.score(&bits[row * len..(row + 1) * len])
+                })?;
+                let mut candidates: Vec<usize> =
+                    (0..first.len()).filter(|&s| first[s] > f32::MIN).collect();
+                candidates.sort_by(|a, b| {
+                    first[*b]
+                        .partial_cmp(&first[*a])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                candidates.truncate(limit * self.quantization.rescore_factor());
+                let mut rescored = vec![false; self.sessions.len()];
+                for s in candidates {
+                    rescored[s] = true;
+                }
+                self.int8_scores(&query, &rescored, lists)?
+            }
+        };
+
+        let mut scored: Vec<(String, f64)> = best
+            .iter()
+            .enumerate()
+            .filter(|(_, sim)| **sim > f32::MIN)
+            .map(|(i, sim)| (self.sessions[i].clone(), *sim as f64))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    /// Best score per session over the rows of `allowed` sessions in the probed lists
+    fn best_per_session(
+        &self,
+        allowed: &[bool],
+        lists: Option<&[i64]>,
+        score: impl Fn(usize) -> f32,
+    ) -> Result<Vec<f32>> {
+        let row_sessions = self.row_sessions()?;
+        let mut best = vec![f32::MIN; self.sessions.len()];
+        for &(list_id, start, count) in &self.lists {
+            let probed = match lists {
+                Some(lists) => list_id == UNASSIGNED || lists.contains(&list_id),
+                None => true,
+            };
+            if !probed {
+                continue;
+            }
+            for (row, &session) in row_sessions.iter().enumerate().skip(start).take(count) {
+                let session = session as usize;
+                if !allowed[session] {
+                    continue;
+                }
+                let sim = score(row);
+                if sim > best[session] {
+                    best[session] = sim;
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Scores the int8 rows with an integer dot product
+    fn int8_scores(
+        &self,
+        query: &[f32],
+        allowed: &[bool],
+        lists: Option<&[i64]>,
+    ) -> Result<Vec<f32>> {
+        let code = QueryCode::new(query, VectorQuantization::Int8);
+        let len = row_len(self.dim, self.quantization);
+        let rows = slice(&self.mmap, self.matrix_offset, self.rows * len)?;
+        self.best_per_session(allowed, lists, |row| {
+            code.score(&rows[row * len..(row + 1) * len])
+        })
+    }
+
+    fn matrix(&self) -> Result<&[f32]> {
+        let bytes = slice(&self.mmap, self.matrix_offset, self.rows * self.dim * 4)?;
+        <[f32]>::ref_from_bytes(bytes).map_err(|_| anyhow::anyhow!("misaligned vector matrix"))
+    }
+
+    fn bits(&self) -> Result<&[u8]> {
+        slice(
+            &self.mmap,
+            self.bits_offset,
+            self.rows * bits_len(self.dim, self.quantization),
+        )
+    }
+
+    fn row_sessions(&self) -> Result<&[u32]> {
+        let bytes = slice(&self.mmap, self.row_sessions_offset, self.rows * 4)?;
+        <[u32]>::ref_from_bytes(bytes).map_err(|_| anyhow::anyhow!("misaligned session column"))
+    }
+}
+
+/// Writes a fresh store for every chunk embedding in the database, replacing
+/// `path` atomically (searches holding the old mapping keep reading it).
+/// Removes the store instead when there are no chunk embeddings.
+pub fn build(conn: &Connection, path: &Path, quantization: VectorQuantization) -> Result<()> {
+    // One read transaction, so the generation matches the rows written
+    let tx = conn.unchecked_transaction()?;
+    let generation = queries::get_vector_generation(&tx)?;
+
+    let mut lists: Vec<(i64, usize, usize)> = Vec::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT COALESCE(list_id, -1) AS list, COUNT(*) FROM chunk_embeddings
+             GROUP BY list ORDER BY list",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+        let mut start = 0;
+        for row in rows {
+            let (list_id, count) = row?;
+            lists.push((list_id, start, count));
+            start += count;
+        }
+    }
+    let rows: usize = lists.iter().map(|(_, _, count)| count).sum();
+    if rows == 0 {
+        drop(tx);
+        return remove(path);
+    }
+
+    let tmp_path = path.with_extension("vectors.tmp");
+    let mut out = BufWriter::new(
+        File::create(&tmp_path).with_context(|| format!("Failed to create {:?}", tmp_path))?,
+    );
+
+    let mut dim: Option<usize> = None;
+    let mut session_ids: HashMap<String, u32> = HashMap::new();
+    let mut sessions: Vec<String> = Vec::new();
+    let mut row_sessions: Vec<u32> = Vec::with_capacity(rows);
+    // Sign bits of every row under binary quantization, written after the rows
+    let mut bits: Vec<u8> = Vec::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT session_id, embedding FROM chunk_embeddings
+             ORDER BY COALESCE(list_id, -1), chunk_id",
+        )?;
+        let mut query_rows = stmt.query([])?;
+        while let Some(row) = query_rows.next()? {
+            let session_id: String = row.get(0)?;
+            let embedding =
+                queries::decode_stored_embedding(&row.get::<_, Vec<u8>>(1)?, quantization);
+
+            let dim = *dim.get_or_insert(embedding.len());
+            if embedding.len() != dim {
+                drop(out);
+                let _ = std::fs::remove_file(&tmp_path);
+                bail!(
+                    "chunk embeddings have mixed dimensions ({} and {})",
+                    dim,
+                    embedding.len()
+                );
+            }
+            if row_sessions.is_empty() {
+                write_header(&mut out, generation, quantization, dim, rows, &lists)?;
+            }
+
+            let embedding = normalized(&embedding);
+            match quantization {
+                VectorQuantization::None => {
+                    for x in &embedding {
+                        out.write_all(&x.to_le_bytes())?;
+                    }
+                }
+                VectorQuantization::Int8 | VectorQuantization::Binary => {
+                    out.write_all(&quantize::encode(&embedding, VectorQuantization::Int8))?;
+                }
+            }
+            if quantization == VectorQuantization::Binary {
+                bits.extend(quantize::encode(&embedding, VectorQuantization::Binary));
+            }
+            let next = sessions.len() as u32;
+            let ordinal = *session_ids.entry(session_id.clone()).or_insert_with(|| {
+                sessions.push(session_id);
+                next
+            });
+            row_sessions.push(ordinal);
+        }
+    }
+    drop(tx);
+
+    let dim = dim.unwrap_or(0);
+    let matrix_end = HEADER_LEN + lists.len() * LIST_ENTRY_LEN + rows * row_len(dim, quantization);
+    write_padding(&mut out, matrix_end)?;
+    out.write_all(&bits)?;
+    write_padding(&mut out, bits.len())?;
+    for ordinal in &row_sessions {
+        out.write_all(&ordinal.to_le_bytes())?;
+    }
+    for session_id in &sessions {
+        out.write_all(&(session_id.len() as u32).to_le_bytes())?;
+        out.write_all(session_id.as_bytes())?;
+    }
+
+    // The session count is only known now; patch it into the header
+    let mut file = out.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(28))?;
+    file.write_all(&(sessions.len() as u32).to_le_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace vector store {:?}", path))?;
+    Ok(())
+}
+
+/// Deletes the store, if any
+pub fn remove(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove {:?}", path)),
+    }
+}
+
+/// Writes the header and list directory; the session count is patched in later
+fn write_header(
+    out: &mut impl Write,
+    generation: u64,
+    quantization: VectorQuantization,
+    dim: usize,
+    rows: usize,
+    lists: &[(i64, usize, usize)],
+) -> Result<()> {
+    let encoding = match quantization {
+        VectorQuantization::None => 0,
+        VectorQuantization::Int8 => 1,
+        VectorQuantization::Binary => 2,
+    };
+    out.write_all(MAGIC)?;
+    out.write_all(&generation.to_le_bytes())?;
+    for n in [dim, rows, lists.len(), 0, encoding, 0] {
+        out.write_all(&(n as u32).to_le_bytes())?;
+    }
+    for (list_id, start, count) in lists {
+        out.write_all(&list_id.to_le_bytes())?;
+        out.write_all(&(*start as u32).to_le_bytes())?;
+        out.write_all(&(*count as u32).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Pads a section that ends at `end` to the next 4-byte boundary
+fn write_padding(out: &mut impl Write, end: usize) -> Result<()> {
+    out.write_all(&[0; 3][..aligned(end) - end])?;
+    Ok(())
+}
+
+fn aligned(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+/// Bytes per row of the main matrix: an f32 vector or an int8 code
+fn row_len(dim: usize, quantization: VectorQuantization) -> usize {
+    match quantization {
+        VectorQuantization::None => dim * 4,
+        VectorQuantization::Int8 | VectorQuantization::Binary => 4 + dim,
+    }
+}
+
+/// Bytes per row of the sign-bit matrix (binary quantization only)
+fn bits_len(dim: usize, quantization: VectorQuantization) -> usize {
+    match quantization {
+        VectorQuantization::Binary => dim.div_ceil(8),
+        VectorQuantization::None | VectorQuantization::Int8 => 0,
+    }
+}
+
+fn slice(bytes: &[u8], at: usize, len: usize) -> Result<&[u8]> {
+    bytes.get(at..at + len).context("vector store is truncated")
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(slice(bytes, at, 4)?.try_into()?))
+}
+
+/// Dot product over eight lanes at a time, which the compiler turns into SIMD
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut lanes = [0.0f32; 8];
+    let (a_chunks, b_chunks) = (a.chunks_exact(8), b.chunks_exact(8));
+    let tail: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+            *lane += x * y;
+        }
+    }
+    lanes.iter().sum::<f32>() + tail
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter().map(|x| x / norm).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_matches_naive() {
+        let a: Vec<f32> = (0..19).map(|i| i as f32 * 0.5).collect();
+        let b: Vec<f32> = (0..19).map(|i| 1.0 - i as f32 * 0.1).collect();
+        let naive: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        assert!((dot(&a, &b) - naive).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_open_rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.vectors");
+        assert!(VectorStore::open(&path).unwrap().is_none());
+
+        std::fs::write(&path, b"not a vector store at all, just some bytes").unwrap();
+        assert!(VectorStore::open(&path).is_err());
+    }
+}
@@ -1,158 +1,290 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use ort::session::SessionInputValue;
 use ort::value::Tensor;
 use std::path::Path;
 
+use crate::config::EmbeddingPooling;
+use crate::model::ModelSpec;
+
+/// Maximum tokens per sequence, special tokens included
+const MAX_TOKENS: usize = 512;
+
+/// Tokens shared by consecutive segments of a text longer than `max_tokens`
+const SEGMENT_OVERLAP: usize = 50;
+
 /// Wrapper around ONNX Runtime for generating text embeddings
 pub struct Embedder {
     session: ort::session::Session,
     tokenizer: tokenizers::Tokenizer,
-    max_tokens: usize,
+    dim: usize,
+    pooling: EmbeddingPooling,
+    query_prefix: String,
+    document_prefix: String,
+    /// Whether the model takes a `token_type_ids` input (BERT-style models do, others not)
+    token_type_ids: bool,
+    batch_size: usize,
 }
 
-/// Embedding dimension for all-MiniLM-L6-v2
-pub const EMBEDDING_DIM: usize = 384;
-
 impl Embedder {
-    /// Creates a new embedder from model files in the given directory
-    pub fn new(model_dir: &Path) -> Result<Self> {
-        let model_path = model_dir.join("model.onnx");
+    /// Creates a new embedder from the model files in the given directory.
+    /// The embedding dimension comes from the model's output shape (or `hidden_size`
+    /// in config.json), and special tokens from the tokenizer's post-processor.
+    /// `threads` is the ONNX intra-op thread count (0 lets ONNX Runtime decide);
+    /// `batch_size` is the maximum number of token sequences per inference call.
+    pub fn new(
+        model_dir: &Path,
+        spec: &ModelSpec,
+        threads: usize,
+        batch_size: usize,
+    ) -> Result<Self> {
+        let model_path = model_dir.join(spec.onnx_file()?);
         let tokenizer_path = model_dir.join("tokenizer.json");
 
         // Initialize ONNX Runtime session
-        let session = ort::session::Session::builder()
-            .context("Failed to create ONNX session builder")?
-            .with_intra_threads(1)
-            .context("Failed to set thread count")?
+        let mut builder =
+            ort::session::Session::builder().context("Failed to create ONNX session builder")?;
+        if threads > 0 {
+            builder = builder
+                .with_intra_threads(threads)
+                .context("Failed to set thread count")?;
+        }
+        let session = builder
             .commit_from_file(&model_path)
             .with_context(|| format!("Failed to load ONNX model from {:?}", model_path))?;
 
-        // Load tokenizer
-        let tokenizer = tokenizers::Tokenizer::from_file(&tokenizer_path)
+        let dim = match output_dim(&session) {
+            Some(dim) => dim,
+            None => config_hidden_size(&model_dir.join("config.json")).with_context(|| {
+                format!(
+                    "Cannot tell the embedding dimension of {:?}: its output shape is dynamic and config.json has no hidden_size",
+                    model_path
+                )
+            })?,
+        };
+        let token_type_ids = session
+            .inputs()
+            .iter()
+            .any(|input| input.name() == "token_type_ids");
+
+        // Load tokenizer; over-long texts come back as overlapping windows, each with
+        // the model's own special tokens
+        let mut tokenizer = tokenizers::Tokenizer::from_file(&tokenizer_path)
             .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+        tokenizer.with_padding(None);
+        tokenizer
+            .with_truncation(Some(tokenizers::TruncationParams {
+                max_length: MAX_TOKENS,
+                stride: SEGMENT_OVERLAP,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!("Failed to configure tokenizer: {}", e))?;
+
+        log::debug!(
+            "Loaded embedding model {} ({} dimensions, {:?} pooling)",
+            spec.name,
+            dim,
+            spec.pooling
+        );
 
         Ok(Self {
             session,
             tokenizer,
-            max_tokens: 512,
+            dim,
+            pooling: spec.pooling,
+            query_prefix: spec.query_prefix.clone(),
+            document_prefix: spec.document_prefix.clone(),
+            token_type_ids,
+            batch_size: batch_size.max(1),
         })
     }
 
-    /// Generates an embedding for the given text.
+    /// Generates an embedding for the given indexed text.
     /// For long texts, chunks into overlapping segments and mean-pools.
     pub fn embed(&mut self, text: &str) -> Result<Vec<f32>> {
-        let text = text.trim();
-        if text.is_empty() {
-            return Ok(vec![0.0; EMBEDDING_DIM]);
+        let mut embeddings = self.embed_batch(&[text])?;
+        Ok(embeddings.pop().unwrap_or_else(|| vec![0.0; self.dim]))
+    }
+
+    /// Generates an embedding for a search query, with the model's query prefix
+    pub fn embed_query(&mut self, query: &str) -> Result<Vec<f32>> {
+        let prefix = self.query_prefix.clone();
+        let mut embeddings = self.embed_prefixed(&[query], &prefix)?;
+        Ok(embeddings.pop().unwrap_or_else(|| vec![0.0; self.dim]))
+    }
+
+    /// Generates embeddings for many indexed texts, returned in input order.
+    /// Token sequences are grouped into `[batch, seq_len]` tensors padded to the
+    /// longest sequence in each batch; long texts are split into overlapping
+    /// segments whose embeddings are mean-pooled as in [`Embedder::embed`].
+    pub fn embed_batch(&mut self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let prefix = self.document_prefix.clone();
+        self.embed_prefixed(texts, &prefix)
+    }
+
+    fn embed_prefixed(&mut self, texts: &[&str], prefix: &str) -> Result<Vec<Vec<f32>>> {
+        // Token sequences to run, each tagged with the text it belongs to
+        let mut sequences: Vec<(usize, Vec<u32>)> = Vec::new();
+        for (i, text) in texts.iter().enumerate() {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let text = format!("{}{}", prefix, text);
+            for ids in token_sequences(&self.tokenizer, &text)? {
+                sequences.push((i, ids));
+            }
         }
 
-        let encoding = self
-            .tokenizer
-            .encode(text, true)
-            .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
+        // Sort by length so each batch carries as little padding as possible
+        sequences.sort_by_key(|(_, ids)| ids.len());
 
-        let token_count = encoding.get_ids().len();
+        let mut sums = vec![vec![0.0f32; self.dim]; texts.len()];
+        let mut counts = vec![0usize; texts.len()];
 
-        if token_count <= self.max_tokens {
-            // Single-pass embedding
-            self.embed_tokens(encoding.get_ids(), encoding.get_attention_mask())
-        } else {
-            // Chunked embedding with mean pooling
-            self.embed_chunked(text)
+        for batch in sequences.chunks(self.batch_size) {
+            let ids: Vec<&[u32]> = batch.iter().map(|(_, ids)| ids.as_slice()).collect();
+            let embeddings = self.embed_tokens_batch(&ids)?;
+            for ((owner, _), emb) in batch.iter().zip(embeddings) {
+                for (sum, val) in sums[*owner].iter_mut().zip(&emb) {
+                    *sum += val;
+                }
+                counts[*owner] += 1;
+            }
         }
+
+        // Mean pool across segments of the same text
+        Ok(sums
+            .into_iter()
+            .zip(counts)
+            .map(|(sum, n)| {
+                if n == 0 {
+                    return sum;
+                }
+                let mean: Vec<f32> = sum.iter().map(|x| x / n as f32).collect();
+                l2_normalize(&mean)
+            })
+            .collect())
     }
 
-    /// Embeds a single token sequence using ort v2 Tensor API
-    fn embed_tokens(&mut self, input_ids: &[u32], attention_mask: &[u32]) -> Result<Vec<f32>> {
-        let seq_len = input_ids.len();
+    /// Embeds a batch of token sequences in one inference call using the ort v2 Tensor API.
+    /// Sequences are right-padded to the longest one; padding is masked out of pooling.
+    fn embed_tokens_batch(&mut self, sequences: &[&[u32]]) -> Result<Vec<Vec<f32>>> {
+        let batch = sequences.len();
+        let seq_len = sequences.iter().map(|ids| ids.len()).max().unwrap_or(0);
+        if batch == 0 || seq_len == 0 {
+            return Ok(Vec::new());
+        }
 
-        let input_ids_i64: Vec<i64> = input_ids.iter().map(|&x| x as i64).collect();
-        let attention_mask_i64: Vec<i64> = attention_mask.iter().map(|&x| x as i64).collect();
-        let token_type_ids: Vec<i64> = vec![0i64; seq_len];
+        let mut input_ids_i64: Vec<i64> = vec![0; batch * seq_len];
+        let mut attention_mask_i64: Vec<i64> = vec![0; batch * seq_len];
+        for (row, ids) in sequences.iter().enumerate() {
+            for (col, &id) in ids.iter().enumerate() {
+                input_ids_i64[row * seq_len + col] = id as i64;
+                attention_mask_i64[row * seq_len + col] = 1;
+            }
+        }
+        let mask_f32: Vec<f32> = attention_mask_i64.iter().map(|&x| x as f32).collect();
 
-        let shape = vec![1i64, seq_len as i64];
+        let shape = vec![batch as i64, seq_len as i64];
 
         let input_ids_tensor = Tensor::from_array((shape.clone(), input_ids_i64))
             .context("Failed to create input_ids tensor")?;
         let attention_mask_tensor = Tensor::from_array((shape.clone(), attention_mask_i64))
             .context("Failed to create attention_mask tensor")?;
-        let token_type_ids_tensor = Tensor::from_array((shape, token_type_ids))
-            .context("Failed to create token_type_ids tensor")?;
-
-        let outputs = self
-            .session
-            .run(ort::inputs! {
-                "input_ids" => input_ids_tensor,
-                "attention_mask" => attention_mask_tensor,
-                "token_type_ids" => token_type_ids_tensor,
-            })
-            .context("ONNX inference failed")?;
+        let mut inputs: Vec<(&str, SessionInputValue)> = vec![
+            ("input_ids", input_ids_tensor.into()),
+            ("attention_mask", attention_mask_tensor.into()),
+        ];
+        if self.token_type_ids {
+            let token_type_ids_tensor = Tensor::from_array((shape, vec![0i64; batch * seq_len]))
+                .context("Failed to create token_type_ids tensor")?;
+            inputs.push(("token_type_ids", token_type_ids_tensor.into()));
+        }
 
-        // Get the output tensor (last_hidden_state: [1, seq_len, 384])
-        // try_extract_tensor returns (&Shape, &[f32])
-        let (_shape, data) = outputs[0]
+        let outputs = self.session.run(inputs).context("ONNX inference failed")?;
+
+        // Token vectors ([batch, seq_len, dim]), or sentence vectors ([batch, dim]) from
+        // models exported with pooling built in
+        let (out_shape, data) = outputs[0]
             .try_extract_tensor::<f32>()
             .context("Failed to extract output tensor")?;
-
-        // Mean pooling over the sequence dimension with attention mask
-        let mask_f32: Vec<f32> = attention_mask.iter().map(|&x| x as f32).collect();
-        let embedding = mean_pool_flat(data, &mask_f32, seq_len, EMBEDDING_DIM);
-
-        // L2 normalize
-        Ok(l2_normalize(&embedding))
+        let dim = self.dim;
+        let pooled = match **out_shape {
+            [b, d] if b as usize == batch && d as usize == dim => {
+                data.chunks_exact(dim).map(|row| row.to_vec()).collect()
+            }
+            [b, s, d] if b as usize == batch && s as usize == seq_len && d as usize == dim => {
+                match self.pooling {
+                    EmbeddingPooling::Mean => mean_pool_batch(data, &mask_f32, batch, seq_len, dim),
+                    EmbeddingPooling::Cls => cls_pool_batch(data, batch, seq_len, dim),
+                }
+            }
+            _ => bail!(
+                "Unexpected model output shape {:?} (expected [{}, {}, {}])",
+                &**out_shape,
+                batch,
+                seq_len,
+                dim
+            ),
+        };
+
+        Ok(pooled.iter().map(|emb| l2_normalize(emb)).collect())
     }
+}
 
-    /// Chunks long text and mean-pools the chunk embeddings
-    fn embed_chunked(&mut self, text: &str) -> Result<Vec<f32>> {
-        let chunk_size = self.max_tokens - 2; // Reserve for [CLS] and [SEP]
-        let overlap = 50; // Token overlap between chunks
-
-        let encoding = self
-            .tokenizer
-            .encode(text, false)
-            .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
-
-        let all_ids = encoding.get_ids();
-        let mut embeddings: Vec<Vec<f32>> = Vec::new();
-        let mut start = 0;
-
-        while start < all_ids.len() {
-            let end = (start + chunk_size).min(all_ids.len());
-            let chunk_ids = &all_ids[start..end];
-
-            // Add [CLS] and [SEP] tokens
-            let mut padded_ids = vec![101u32]; // [CLS]
-            padded_ids.extend_from_slice(chunk_ids);
-            padded_ids.push(102); // [SEP]
-
-            let attention_mask: Vec<u32> = vec![1; padded_ids.len()];
-
-            let emb = self.embed_tokens(&padded_ids, &attention_mask)?;
-            embeddings.push(emb);
+/// Tokenizes text into one or more sequences of at most the tokenizer's truncation
+/// length, consecutive ones sharing its stride. Each sequence carries the special
+/// tokens the tokenizer's post-processor adds ([CLS] … [SEP], <s> … </s>, ...).
+fn token_sequences(tokenizer: &tokenizers::Tokenizer, text: &str) -> Result<Vec<Vec<u32>>> {
+    let encoding = tokenizer
+        .encode(text, true)
+        .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
+
+    Ok(std::iter::once(&encoding)
+        .chain(encoding.get_overflowing())
+        .map(|e| e.get_ids().to_vec())
+        .collect())
+}
 
-            if end >= all_ids.len() {
-                break;
-            }
-            start = end - overlap;
-        }
+/// Embedding dimension declared by the model's first output (its last axis), if fixed
+fn output_dim(session: &ort::session::Session) -> Option<usize> {
+    let shape = session.outputs().first()?.dtype().tensor_shape()?;
+    match shape.last() {
+        Some(&d) if d > 0 => Some(d as usize),
+        _ => None,
+    }
+}
 
-        if embeddings.is_empty() {
-            return Ok(vec![0.0; EMBEDDING_DIM]);
-        }
+/// `hidden_size` from a Hugging Face config.json
+fn config_hidden_size(path: &Path) -> Option<usize> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let config: serde_json::Value = serde_json::from_str(&content).ok()?;
+    config.get("hidden_size")?.as_u64().map(|d| d as usize)
+}
 
-        // Mean pool across chunks
-        let mut result = vec![0.0f32; EMBEDDING_DIM];
-        for emb in &embeddings {
-            for (i, val) in emb.iter().enumerate() {
-                result[i] += val;
-            }
-        }
-        let n = embeddings.len() as f32;
-        for val in &mut result {
-            *val /= n;
-        }
+/// First-token pooling on a flat f32 slice with shape [batch, seq_len, embedding_dim]
+fn cls_pool_batch(data: &[f32], batch: usize, seq_len: usize, dim: usize) -> Vec<Vec<f32>> {
+    (0..batch)
+        .map(|row| data[row * seq_len * dim..row * seq_len * dim + dim].to_vec())
+        .collect()
+}
 
-        Ok(l2_normalize(&result))
-    }
+/// Mean pooling on a flat f32 slice with shape [batch, seq_len, embedding_dim],
+/// using a flat [batch, seq_len] attention mask
+fn mean_pool_batch(
+    data: &[f32],
+    mask: &[f32],
+    batch: usize,
+    seq_len: usize,
+    dim: usize,
+) -> Vec<Vec<f32>> {
+    (0..batch)
+        .map(|row| {
+            let data = &data[row * seq_len * dim..(row + 1) * seq_len * dim];
+            let mask = &mask[row * seq_len..(row + 1) * seq_len];
+            mean_pool_flat(data, mask, seq_len, dim)
+        })
+        .collect()
 }
 
 /// Mean pooling on a flat f32 slice with shape [1, seq_len, embedding_dim]
@@ -220,4 +352,81 @@ mod tests {
         let result = mean_pool_flat(&data, &mask, 2, 2);
         assert_eq!(result, vec![1.0, 2.0]);
     }
+
+    #[test]
+    fn test_cls_pool_batch() {
+        // 2 rows, 2 tokens, dim=2
+        let data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let result = cls_pool_batch(&data, 2, 2, 2);
+        assert_eq!(result, vec![vec![1.0, 2.0], vec![5.0, 6.0]]);
+    }
+
+    /// Word-level tokenizer over "w0".."w99" that wraps sequences in <s> … </s>
+    fn test_tokenizer(max_length: usize, stride: usize) -> tokenizers::Tokenizer {
+        use tokenizers::models::wordlevel::WordLevel;
+        use tokenizers::pre_tokenizers::whitespace::Whitespace;
+        use tokenizers::processors::template::TemplateProcessing;
+
+        let mut vocab: std::collections::HashMap<String, u32> =
+            (0..100).map(|i| (format!("w{}", i), i + 10)).collect();
+        vocab.insert("<unk>".to_string(), 0);
+        vocab.insert("<s>".to_string(), 1);
+        vocab.insert("</s>".to_string(), 2);
+        let model = WordLevel::builder()
+            .vocab(vocab.into_iter().collect())
+            .unk_token("<unk>".to_string())
+            .build()
+            .unwrap();
+        let template = TemplateProcessing::builder()
+            .try_single("<s> $A </s>")
+            .unwrap()
+            .special_tokens(vec![("<s>", 1), ("</s>", 2)])
+            .build()
+            .unwrap();
+
+        let mut tokenizer = tokenizers::Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Some(Whitespace {}));
+        tokenizer.with_post_processor(Some(template));
+        tokenizer
+            .with_truncation(Some(tokenizers::TruncationParams {
+                max_length,
+                stride,
+                ..Default::default()
+            }))
+            .unwrap();
+        tokenizer
+    }
+
+    #[test]
+    fn test_token_sequences_use_tokenizer_special_tokens() {
+        let tokenizer = test_tokenizer(10, 2);
+
+        let short = token_sequences(&tokenizer, "w1 w2 w3").unwrap();
+        assert_eq!(short, vec![vec![1, 11, 12, 13, 2]]);
+
+        // 20 words in windows of 8 (10 minus the two special tokens), sharing 2 words
+        let text: Vec<String> = (0..20).map(|i| format!("w{}", i)).collect();
+        let sequences = token_sequences(&tokenizer, &text.join(" ")).unwrap();
+        assert!(sequences.len() > 1);
+        for ids in &sequences {
+            assert!(ids.len() <= 10);
+            assert_eq!(ids[0], 1);
+            assert_eq!(*ids.last().unwrap(), 2);
+        }
+        assert_eq!(&sequences[0][1..9], &[10, 11, 12, 13, 14, 15, 16, 17]);
+        assert_eq!(&sequences[1][1..3], &[16, 17]);
+
+        // Every word lands in some window
+        let covered: std::collections::HashSet<u32> = sequences.iter().flatten().copied().collect();
+        assert!((10..30).all(|id| covered.contains(&id)));
+    }
+
+    #[test]
+    fn test_mean_pool_batch() {
+        // 2 rows, 2 tokens, dim=2; second row has one padding token
+        let data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 100.0, 100.0];
+        let mask = vec![1.0, 1.0, 1.0, 0.0];
+        let result = mean_pool_batch(&data, &mask, 2, 2, 2);
+        assert_eq!(result, vec![vec![2.0, 3.0], vec![5.0, 6.0]]);
+    }
 }
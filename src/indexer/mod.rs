@@ -1,17 +1,21 @@
 pub mod embedder;
 pub mod parser;
+pub mod pipeline;
 #[allow(dead_code)]
 pub mod tokenizer;
 
 use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use rusqlite::Transaction;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
 use crate::claude;
-use crate::config::Config;
+use crate::config::{Config, OrphanPolicy, VectorQuantization};
 use crate::db::Database;
-use parser::{ParsedSession, SessionIndexEntry};
+use crate::search::ann;
+use parser::SessionIndexEntry;
+use pipeline::{AppendFrom, PreparedSession, SessionJob};
 
 /// Orchestrates the full indexing pipeline
 pub struct Indexer<'a> {
@@ -36,6 +40,11 @@ impl<'a> Indexer<'a> {
         }
     }
 
+    /// Gives back the embedder so it can be reused (e.g. for the search itself)
+    pub fn into_embedder(self) -> Option<embedder::Embedder> {
+        self.embedder
+    }
+
     /// Runs a full index of all sessions.
     /// First indexes sessions from sessions-index.json files (rich metadata),
     /// then discovers any .jsonl session files not covered by the index.
@@ -48,34 +57,28 @@ impl<'a> Indexer<'a> {
 
         let total_phases = if indices.is_empty() { 1 } else { 2 };
         if !indices.is_empty() {
-            eprintln!("→ Phase 1/{}: Indexing from session indices...", total_phases);
-
-            let pb = ProgressBar::new(indices.len() as u64);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({msg})")
-                    .expect("Invalid progress bar template")
-                    .progress_chars("#>-"),
+            eprintln!(
+                "→ Phase 1/{}: Indexing from session indices...",
+                total_phases
             );
 
+            let mut jobs = Vec::new();
             for index_path in &indices {
-                pb.set_message(claude::encoded_project_name(index_path).unwrap_or_default());
-
-                match self.index_project(index_path, force, days_filter, &mut indexed_ids) {
-                    Ok(project_stats) => {
-                        stats.sessions_indexed += project_stats.sessions_indexed;
-                        stats.sessions_skipped += project_stats.sessions_skipped;
-                        stats.sessions_errored += project_stats.sessions_errored;
-                    }
-                    Err(e) => {
-                        log::warn!("Error indexing {:?}: {}", index_path, e);
-                        stats.sessions_errored += 1;
-                    }
+                if let Err(e) = self.plan_project(
+                    index_path,
+                    force,
+                    days_filter,
+                    &mut indexed_ids,
+                    &mut stats,
+                    &mut jobs,
+                ) {
+                    log::warn!("Error indexing {:?}: {}", index_path, e);
+                    stats.sessions_errored += 1;
                 }
-
-                pb.inc(1);
             }
 
+            let pb = progress_bar(jobs.len());
+            self.run_jobs(jobs, Some(&pb), &mut stats);
             pb.finish_and_clear();
         }
 
@@ -85,159 +88,206 @@ impl<'a> Indexer<'a> {
             total_phases, total_phases
         );
 
-        let all_files = claude::discover_all_session_files()?;
-        let unlisted: Vec<_> = all_files
-            .iter()
-            .filter(|(sid, _)| !indexed_ids.contains(*sid))
-            .collect();
-
-        if !unlisted.is_empty() {
-            let pb = ProgressBar::new(unlisted.len() as u64);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({msg})")
-                    .expect("Invalid progress bar template")
-                    .progress_chars("#>-"),
-            );
-
-            let cutoff = days_filter
-                .map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
-
-            for (session_id, (jsonl_path, encoded_name)) in &unlisted {
-                pb.set_message(encoded_name.clone());
-
-                // Staleness check
-                if !force {
-                    let current_mtime = parser::file_mtime(jsonl_path).unwrap_or(0);
-                    if let Ok(Some(stored_mtime)) = self.db.get_session_mtime(session_id) {
-                        if stored_mtime >= current_mtime {
-                            stats.sessions_skipped += 1;
-                            pb.inc(1);
-                            continue;
-                        }
-                    }
-                }
-
-                // Date filter based on file mtime
-                if let Some(ref cutoff_time) = cutoff {
-                    if let Ok(mtime) = parser::file_mtime(jsonl_path) {
-                        let file_time = chrono::DateTime::from_timestamp(mtime, 0);
-                        if let Some(ft) = file_time {
-                            if ft < *cutoff_time {
-                                stats.sessions_skipped += 1;
-                                pb.inc(1);
-                                continue;
-                            }
-                        }
-                    }
-                }
-
-                let decoded_path = claude::decode_project_path(encoded_name);
-                // Create a minimal entry for sessions not in the index
-                let entry = SessionIndexEntry {
-                    session_id: session_id.to_string(),
-                    full_path: Some(jsonl_path.to_string_lossy().to_string()),
-                    first_prompt: None,
-                    summary: None,
-                    slug: None,
-                    project_path: Some(decoded_path.clone()),
-                    message_count: None,
-                    created: None,
-                    modified: None,
-                    created_at: None,
-                    last_activity_at: None,
-                    file_mtime: None,
-                    git_branch: None,
-                };
-
-                match self.index_session(&entry, jsonl_path, &decoded_path) {
-                    Ok(_) => {
-                        stats.sessions_indexed += 1;
-                        if self.verbose {
-                            log::info!("Indexed unlisted session: {}", session_id);
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Error indexing session {}: {}", session_id, e);
-                        stats.sessions_errored += 1;
-                    }
-                }
-
-                pb.inc(1);
-            }
+        let mut jobs = Vec::new();
+        self.plan_unlisted(force, days_filter, &indexed_ids, &mut stats, &mut jobs)?;
 
+        if !jobs.is_empty() {
+            let pb = progress_bar(jobs.len());
+            self.run_jobs(jobs, Some(&pb), &mut stats);
             pb.finish_and_clear();
         }
 
+        // Drop (or mark) sessions whose JSONL file was deleted or moved
+        if let Err(e) = self.reconcile(&mut stats) {
+            log::warn!("Error pruning deleted sessions: {}", e);
+        }
+
+        // Embed chunks stored without vectors (e.g. by JIT indexing before a search)
+        self.backfill_with_progress(&mut stats)?;
+        self.update_vector_indexes(&mut stats)?;
+
         eprintln!(
             "\nDone: {} sessions indexed, {} skipped, {} errors",
             stats.sessions_indexed, stats.sessions_skipped, stats.sessions_errored
         );
+        if stats.sessions_removed > 0 || stats.sessions_orphaned > 0 {
+            eprintln!(
+                "Pruned deleted sessions: {} removed, {} marked orphaned",
+                stats.sessions_removed, stats.sessions_orphaned
+            );
+        }
+        if stats.ann_lists > 0 {
+            eprintln!("Vector index: {} lists", stats.ann_lists);
+        }
+        if self.config.vector_quantization != VectorQuantization::None {
+            let (stored, codes) = self.db.chunk_embedding_bytes()?;
+            let dim = self.db.chunk_embedding_dim()?.unwrap_or(0) as u64;
+            let full = self.db.count_chunk_embeddings()? as u64 * dim * 4;
+            if stored > 0 {
+                eprintln!(
+                    "Quantized vectors ({}): {} stored instead of {} at full precision ({:.1}× less)",
+                    self.config.vector_quantization.as_str(),
+                    format_bytes(stored + codes),
+                    format_bytes(full),
+                    full as f64 / (stored + codes) as f64
+                );
+            }
+        }
+        if stats.chunks_missing_vectors > 0 {
+            eprintln!(
+                "{} chunks are missing vectors (embedding model unavailable); \
+                 semantic search can't see them yet",
+                stats.chunks_missing_vectors
+            );
+        }
 
         Ok(stats)
     }
 
     /// Performs a quick JIT index check — only indexes new/changed sessions
     pub fn jit_index(&mut self) -> Result<()> {
+        let mut stats = IndexStats::default();
         let mut indexed_ids = HashSet::new();
+        let mut jobs = Vec::new();
 
         // Check sessions-index.json files
         let indices = claude::discover_session_indices()?;
         for index_path in &indices {
-            if let Err(e) = self.index_project(index_path, false, None, &mut indexed_ids) {
+            if let Err(e) = self.plan_project(
+                index_path,
+                false,
+                None,
+                &mut indexed_ids,
+                &mut stats,
+                &mut jobs,
+            ) {
                 log::warn!("JIT index error for {:?}: {}", index_path, e);
             }
         }
 
         // Also check for unlisted .jsonl files
-        let all_files = claude::discover_all_session_files()?;
-        for (session_id, (jsonl_path, encoded_name)) in &all_files {
-            if indexed_ids.contains(session_id.as_str()) {
-                continue;
+        self.plan_unlisted(false, None, &indexed_ids, &mut stats, &mut jobs)?;
+
+        self.run_jobs(jobs, None, &mut stats);
+
+        if self.config.prune_on_jit {
+            if let Err(e) = self.reconcile(&mut stats) {
+                log::warn!("JIT prune error: {}", e);
             }
-            // Staleness check
-            let current_mtime = parser::file_mtime(jsonl_path).unwrap_or(0);
-            if let Ok(Some(stored_mtime)) = self.db.get_session_mtime(session_id) {
-                if stored_mtime >= current_mtime {
-                    continue;
-                }
+        }
+
+        Ok(())
+    }
+
+    /// Embeds every pending chunk with a progress bar, then records how many
+    /// chunks are still missing vectors in `stats`
+    pub fn backfill_with_progress(&mut self, stats: &mut IndexStats) -> Result<()> {
+        let pending = self.db.count_pending_chunks()?;
+        if pending > 0 && self.embedder.is_some() {
+            eprintln!("→ Embedding {} chunks missing vectors...", pending);
+            let pb = progress_bar(pending);
+            stats.chunks_embedded += self.backfill_embeddings(Some(&pb))?;
+            pb.finish_and_clear();
+        }
+        stats.chunks_missing_vectors = self.db.count_pending_chunks()?;
+        Ok(())
+    }
+
+    /// Trains or extends the ANN index over chunk embeddings (or drops it if the
+    /// corpus is too small to need one, see `search::ann::maintain`), then
+    /// rewrites the memory-mapped vector store if the embeddings changed
+    pub fn update_vector_indexes(&self, stats: &mut IndexStats) -> Result<()> {
+        stats.ann_lists = ann::maintain(self.db, self.config)?;
+        if self.db.sync_vector_store()? && self.verbose {
+            log::info!("Vector store rewritten");
+        }
+        Ok(())
+    }
+
+    /// Embeds chunks that were stored without a vector (e.g. by JIT indexing,
+    /// which runs without the embedding model). Returns the number embedded;
+    /// does nothing if no embedder is loaded.
+    pub fn backfill_embeddings(&mut self, pb: Option<&ProgressBar>) -> Result<usize> {
+        let Some(ref mut embedder) = self.embedder else {
+            return Ok(0);
+        };
+
+        let batch_size = self.config.embedding_batch_size.max(1) * 4;
+        let mut embedded = 0;
+        loop {
+            let pending = self.db.get_pending_chunks(batch_size)?;
+            if pending.is_empty() {
+                break;
             }
 
-            let decoded_path = claude::decode_project_path(encoded_name);
-            let entry = SessionIndexEntry {
-                session_id: session_id.to_string(),
-                full_path: Some(jsonl_path.to_string_lossy().to_string()),
-                first_prompt: None,
-                summary: None,
-                slug: None,
-                project_path: Some(decoded_path.clone()),
-                message_count: None,
-                created: None,
-                modified: None,
-                created_at: None,
-                last_activity_at: None,
-                file_mtime: None,
-                git_branch: None,
-            };
+            let texts: Vec<&str> = pending.iter().map(|c| c.text.as_str()).collect();
+            let embeddings = embedder.embed_batch(&texts)?;
+            let tx = self.db.write_transaction()?;
+            for (chunk, embedding) in pending.iter().zip(&embeddings) {
+                self.db
+                    .upsert_chunk_embedding(chunk.chunk_id, &chunk.session_id, embedding)?;
+            }
+            tx.commit()?;
+
+            embedded += pending.len();
+            if let Some(pb) = pb {
+                pb.inc(pending.len() as u64);
+            }
+        }
+
+        Ok(embedded)
+    }
+
+    /// Finds indexed sessions whose JSONL file no longer exists and removes them
+    /// or marks them as orphaned, depending on `orphan_policy`. Orphaned sessions
+    /// whose file has reappeared are unmarked.
+    pub fn reconcile(&self, stats: &mut IndexStats) -> Result<()> {
+        let live_ids = live_session_ids()?;
+        if live_ids.is_empty() {
+            // Most likely ~/.claude/projects is unavailable rather than empty;
+            // don't wipe the index over it
+            log::warn!("No session files found, skipping prune");
+            return Ok(());
+        }
+
+        for (session_id, orphaned) in self.db.list_indexed_sessions()? {
+            if live_ids.contains(&session_id) {
+                if orphaned {
+                    self.db.set_session_orphaned(&session_id, false)?;
+                }
+                continue;
+            }
 
-            if let Err(e) = self.index_session(&entry, jsonl_path, &decoded_path) {
-                log::warn!("JIT index error for session {}: {}", session_id, e);
+            match self.config.orphan_policy {
+                OrphanPolicy::Remove => {
+                    self.db.delete_session(&session_id)?;
+                    stats.sessions_removed += 1;
+                }
+                OrphanPolicy::Mark => {
+                    if !orphaned {
+                        self.db.set_session_orphaned(&session_id, true)?;
+                        stats.sessions_orphaned += 1;
+                    }
+                }
+            }
+            if self.verbose {
+                log::info!("Pruned deleted session: {}", session_id);
             }
         }
 
         Ok(())
     }
 
-    /// Indexes sessions from a single project's sessions-index.json
-    fn index_project(
-        &mut self,
+    /// Queues stale sessions from a single project's sessions-index.json
+    fn plan_project(
+        &self,
         index_path: &Path,
         force: bool,
         days_filter: Option<u32>,
         indexed_ids: &mut HashSet<String>,
-    ) -> Result<IndexStats> {
-        let mut stats = IndexStats::default();
-
+        stats: &mut IndexStats,
+        jobs: &mut Vec<SessionJob>,
+    ) -> Result<()> {
         let project_dir = claude::project_dir_from_index(index_path)
             .context("Could not determine project directory")?;
 
@@ -250,14 +300,14 @@ impl<'a> Indexer<'a> {
             Ok(e) => e,
             Err(e) => {
                 log::warn!("Failed to parse {:?}: {}", index_path, e);
-                return Ok(stats);
+                return Ok(());
             }
         };
 
         let cutoff =
             days_filter.map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
 
-        for entry in &entries {
+        for entry in entries {
             // Track that we've seen this session (even if we skip it)
             indexed_ids.insert(entry.session_id.clone());
 
@@ -286,150 +336,363 @@ impl<'a> Indexer<'a> {
             }
 
             // Staleness check
-            if !force {
-                let current_mtime = parser::file_mtime(&jsonl_path).unwrap_or(0);
-                if let Ok(Some(stored_mtime)) = self.db.get_session_mtime(&entry.session_id) {
-                    if stored_mtime >= current_mtime {
-                        stats.sessions_skipped += 1;
-                        continue;
-                    }
+            if !force && self.is_up_to_date(&entry.session_id, &jsonl_path) {
+                stats.sessions_skipped += 1;
+                continue;
+            }
+
+            match self.plan_session(entry, jsonl_path, decoded_path.clone(), force) {
+                Ok(job) => jobs.push(job),
+                Err(e) => {
+                    log::warn!("Error indexing {:?}: {}", index_path, e);
+                    stats.sessions_errored += 1;
                 }
             }
+        }
 
-            // Parse and index
-            match self.index_session(entry, &jsonl_path, &decoded_path) {
-                Ok(_) => {
-                    stats.sessions_indexed += 1;
-                    if self.verbose {
-                        log::info!("Indexed session: {}", &entry.session_id);
+        Ok(())
+    }
+
+    /// Queues stale .jsonl files that are not listed in any sessions-index.json
+    fn plan_unlisted(
+        &self,
+        force: bool,
+        days_filter: Option<u32>,
+        indexed_ids: &HashSet<String>,
+        stats: &mut IndexStats,
+        jobs: &mut Vec<SessionJob>,
+    ) -> Result<()> {
+        let all_files = claude::discover_all_session_files()?;
+
+        let cutoff =
+            days_filter.map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+
+        for (session_id, (jsonl_path, encoded_name)) in all_files {
+            if indexed_ids.contains(&session_id) {
+                continue;
+            }
+
+            // Staleness check
+            if !force && self.is_up_to_date(&session_id, &jsonl_path) {
+                stats.sessions_skipped += 1;
+                continue;
+            }
+
+            // Date filter based on file mtime
+            if let Some(ref cutoff_time) = cutoff {
+                if let Ok(mtime) = parser::file_mtime(&jsonl_path) {
+                    let file_time = chrono::DateTime::from_timestamp(mtime, 0);
+                    if let Some(ft) = file_time {
+                        if ft < *cutoff_time {
+                            stats.sessions_skipped += 1;
+                            continue;
+                        }
                     }
                 }
+            }
+
+            let decoded_path = claude::decode_project_path(&encoded_name);
+            // Create a minimal entry for sessions not in the index
+            let entry = SessionIndexEntry {
+                session_id: session_id.clone(),
+                full_path: Some(jsonl_path.to_string_lossy().to_string()),
+                first_prompt: None,
+                summary: None,
+                slug: None,
+                project_path: Some(decoded_path.clone()),
+                message_count: None,
+                created: None,
+                modified: None,
+                created_at: None,
+                last_activity_at: None,
+                file_mtime: None,
+                git_branch: None,
+            };
+
+            match self.plan_session(entry, jsonl_path, decoded_path, force) {
+                Ok(job) => jobs.push(job),
                 Err(e) => {
-                    log::warn!("Error indexing session {}: {}", &entry.session_id, e);
+                    log::warn!("Error indexing session {}: {}", session_id, e);
                     stats.sessions_errored += 1;
                 }
             }
         }
 
-        Ok(stats)
+        Ok(())
+    }
+
+    /// True if the stored session is at least as new as its JSONL file
+    fn is_up_to_date(&self, session_id: &str, jsonl_path: &Path) -> bool {
+        let current_mtime = parser::file_mtime(jsonl_path).unwrap_or(0);
+        matches!(
+            self.db.get_session_mtime(session_id),
+            Ok(Some(stored_mtime)) if stored_mtime >= current_mtime
+        )
     }
 
-    /// Indexes a single session
-    fn index_session(
+    /// Builds the job for a single session.
+    /// If the JSONL file has only been appended to since the last run, the job
+    /// indexes just the new lines; otherwise (or with `force`) it rebuilds the session.
+    fn plan_session(
+        &self,
+        entry: SessionIndexEntry,
+        jsonl_path: PathBuf,
+        decoded_path: String,
+        force: bool,
+    ) -> Result<SessionJob> {
+        let mut append = None;
+        if !force {
+            if let Some(checkpoint) = self.db.get_parse_checkpoint(&entry.session_id)? {
+                if parser::checkpoint_matches(&jsonl_path, &checkpoint)? {
+                    if let Some(existing) = self.db.get_session(&entry.session_id)? {
+                        append = Some(AppendFrom {
+                            checkpoint,
+                            existing,
+                        });
+                    }
+                } else {
+                    log::debug!(
+                        "{} was rewritten, reindexing from scratch",
+                        &entry.session_id
+                    );
+                }
+            }
+        }
+
+        Ok(SessionJob {
+            entry,
+            jsonl_path,
+            decoded_path,
+            append,
+        })
+    }
+
+    /// Runs jobs through the parse → embed → write pipeline, updating stats
+    /// and the progress bar as each session is written
+    fn run_jobs(
         &mut self,
-        entry: &SessionIndexEntry,
-        jsonl_path: &Path,
-        decoded_path: &str,
-    ) -> Result<()> {
-        let parsed = parser::parse_conversation_jsonl(jsonl_path)?;
-
-        let mtime = parser::file_mtime(jsonl_path)?;
-        let now = chrono::Utc::now().to_rfc3339();
-
-        // For sessions without index metadata, derive timestamps from file mtime
-        let mtime_rfc3339 = chrono::DateTime::from_timestamp(mtime, 0)
-            .map(|dt| dt.to_rfc3339())
-            .unwrap_or_else(|| now.clone());
-
-        // Prefer: index metadata > JSONL timestamps > file mtime
-        let created_at = entry
-            .created
-            .clone()
-            .or_else(|| entry.created_at.clone())
-            .or(parsed.first_timestamp)
-            .unwrap_or_else(|| mtime_rfc3339.clone());
-
-        let modified_at = entry
-            .modified
-            .clone()
-            .or_else(|| entry.last_activity_at.clone())
-            .or(parsed.last_timestamp)
-            .unwrap_or_else(|| mtime_rfc3339);
-
-        // Truncate full_text for the sessions table (metadata/preview)
-        let truncated_full_text: String = parsed
-            .full_text
-            .chars()
-            .take(self.config.max_text_chars)
-            .collect();
-
-        let session = ParsedSession {
-            session_id: entry.session_id.clone(),
-            project_path: entry
-                .project_path
-                .clone()
-                .unwrap_or_else(|| decoded_path.to_string()),
-            first_prompt: parsed
-                .first_prompt
-                .or_else(|| entry.first_prompt.clone())
-                .or_else(|| entry.summary.clone()),
-            summary: entry.summary.clone(),
-            slug: entry.slug.clone(),
-            git_branch: entry.git_branch.clone(),
-            message_count: entry.message_count.unwrap_or(parsed.message_count),
-            created_at,
-            modified_at,
-            full_text: truncated_full_text,
-        };
+        jobs: Vec<SessionJob>,
+        pb: Option<&ProgressBar>,
+        stats: &mut IndexStats,
+    ) {
+        if jobs.is_empty() {
+            return;
+        }
 
-        // Store session metadata in DB
-        self.db.upsert_session(&session, mtime, &now)?;
+        let db = self.db;
+        let verbose = self.verbose;
+        let workers = pipeline::worker_count(self.config.index_threads);
+        let mut batch = WriteBatch::new(db, self.config.write_batch_sessions);
+
+        pipeline::run(
+            jobs,
+            self.config,
+            self.embedder.as_mut(),
+            workers,
+            |session_id, result| {
+                match result.and_then(|prepared| batch.write(prepared)) {
+                    Ok(project_path) => {
+                        stats.sessions_indexed += 1;
+                        if verbose {
+                            log::info!("Indexed session: {}", session_id);
+                        }
+                        if let Some(pb) = pb {
+                            pb.set_message(project_path);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Error indexing session {}: {}", session_id, e);
+                        stats.sessions_errored += 1;
+                    }
+                }
+                if batch.is_full() {
+                    batch.commit(stats);
+                }
 
-        // Chunk the full conversation text and store chunks
-        let chunks = parser::chunk_text(
-            &parsed.full_text,
-            self.config.chunk_size,
-            self.config.chunk_overlap,
+                if let Some(pb) = pb {
+                    pb.inc(1);
+                }
+            },
         );
+        batch.commit(stats);
+    }
+}
 
-        // Clear old chunks and embeddings for this session
-        self.db.delete_session_chunks(&session.session_id)?;
-        self.db
-            .delete_session_chunk_embeddings(&session.session_id)?;
+/// Sessions written since the last commit, all in one transaction. Each
+/// session is written under a savepoint so a failure discards only that
+/// session.
+struct WriteBatch<'a> {
+    db: &'a Database,
+    tx: Option<Transaction<'a>>,
+    written: usize,
+    limit: usize,
+}
 
-        // Insert chunks and generate per-chunk embeddings
-        for (i, chunk_text) in chunks.iter().enumerate() {
-            let chunk_id =
-                self.db
-                    .insert_chunk(&session.session_id, i as i32, chunk_text)?;
-
-            if let Some(ref mut embedder) = self.embedder {
-                let embedding = embedder.embed(chunk_text)?;
-                self.db.upsert_chunk_embedding(
-                    chunk_id,
-                    &session.session_id,
-                    &embedding,
-                )?;
-            }
+impl<'a> WriteBatch<'a> {
+    fn new(db: &'a Database, limit: usize) -> Self {
+        Self {
+            db,
+            tx: None,
+            written: 0,
+            limit: limit.max(1),
         }
+    }
 
-        // Also keep session-level embedding for backward compatibility
-        if let Some(ref mut embedder) = self.embedder {
-            let text_for_embedding = build_embedding_text(&session);
-            let embedding = embedder.embed(&text_for_embedding)?;
-            self.db.upsert_embedding(&session.session_id, &embedding)?;
-        }
+    /// Writes a session into the open transaction (starting one if needed),
+    /// returning its project path
+    fn write(&mut self, prepared: PreparedSession) -> Result<String> {
+        let tx = match self.tx {
+            Some(ref mut tx) => tx,
+            None => self.tx.insert(self.db.write_transaction()?),
+        };
+        let savepoint = tx.savepoint()?;
+        let project_path = write_session(self.db, prepared)?;
+        savepoint.commit()?;
+        self.written += 1;
+        Ok(project_path)
+    }
 
-        Ok(())
+    fn is_full(&self) -> bool {
+        self.written >= self.limit
+    }
+
+    /// Commits the sessions written so far. If the commit fails they are
+    /// rolled back and counted as errors instead.
+    fn commit(&mut self, stats: &mut IndexStats) {
+        let Some(tx) = self.tx.take() else {
+            return;
+        };
+        let written = std::mem::take(&mut self.written);
+        if let Err(e) = tx.commit() {
+            log::warn!("Error committing {} indexed sessions: {}", written, e);
+            stats.sessions_indexed -= written;
+            stats.sessions_errored += written;
+        }
     }
 }
 
-/// Builds the text to embed at session level, prioritizing summary and first prompt
-fn build_embedding_text(session: &ParsedSession) -> String {
-    let mut parts = Vec::new();
+/// Writes a prepared session to the database, returning its project path.
+/// A full reindex replaces the session's chunks, embeddings, messages and tool
+/// calls; an append adds the new ones after the existing rows.
+fn write_session(db: &Database, prepared: PreparedSession) -> Result<String> {
+    let PreparedSession {
+        session,
+        parsed,
+        chunks,
+        mtime,
+        append,
+        chunk_embeddings,
+        session_embedding,
+    } = prepared;
+    let session_id = session.session_id.as_str();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    // Store session metadata in DB
+    db.upsert_session(&session, mtime, &now)?;
+
+    let (chunk_base, call_base) = if append {
+        (
+            db.next_chunk_index(session_id)?,
+            db.next_tool_call_index(session_id)?,
+        )
+    } else {
+        // Clear old chunks, embeddings, messages and tool calls for this session
+        db.delete_session_chunks(session_id)?;
+        db.delete_session_chunk_embeddings(session_id)?;
+        db.delete_session_messages(session_id)?;
+        db.delete_session_tool_calls(session_id)?;
+        (0, 0)
+    };
+
+    // Insert chunks with their per-chunk embeddings
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_id = db.insert_chunk(session_id, chunk_base + i as i32, chunk)?;
+
+        if let Some(ref embeddings) = chunk_embeddings {
+            db.upsert_chunk_embedding(chunk_id, session_id, &embeddings[i])?;
+        }
+    }
+
+    for message in &parsed.messages {
+        db.insert_message(session_id, message)?;
+    }
 
-    if let Some(ref summary) = session.summary {
-        parts.push(summary.clone());
+    // Tool calls (file paths, commands, patterns)
+    for (i, call) in parsed.tool_calls.iter().enumerate() {
+        db.insert_tool_call(session_id, call_base + i as i32, call)?;
     }
-    if let Some(ref first_prompt) = session.first_prompt {
-        parts.push(first_prompt.clone());
+
+    if let Some(ref embedding) = session_embedding {
+        db.upsert_embedding(session_id, embedding)?;
     }
-    if !session.full_text.is_empty() {
-        let truncated: String = session.full_text.chars().take(2000).collect();
-        parts.push(truncated);
+
+    db.set_parse_checkpoint(session_id, &parsed.checkpoint)?;
+
+    if append {
+        log::debug!(
+            "Appended {} messages to {}",
+            parsed.messages.len(),
+            session_id
+        );
     }
 
-    parts.join(" ")
+    Ok(session.project_path)
+}
+
+/// IDs of all sessions that still have a JSONL file on disk, from both the
+/// sessions-index.json files and a scan of the projects directory
+fn live_session_ids() -> Result<HashSet<String>> {
+    let mut ids: HashSet<String> = claude::discover_all_session_files()?.into_keys().collect();
+
+    for index_path in claude::discover_session_indices()? {
+        let Some(project_dir) = claude::project_dir_from_index(&index_path) else {
+            continue;
+        };
+        let Ok(entries) = parser::parse_session_index(&index_path) else {
+            continue;
+        };
+        for entry in entries {
+            let jsonl_path = match entry.full_path {
+                Some(ref fp) => PathBuf::from(fp),
+                None => project_dir.join(format!("{}.jsonl", &entry.session_id)),
+            };
+            if jsonl_path.exists() {
+                ids.insert(entry.session_id);
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Progress bar used by both indexing phases
+fn progress_bar(len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({msg})",
+            )
+            .expect("Invalid progress bar template")
+            .progress_chars("#>-"),
+    );
+    pb
+}
+
+/// Human-readable size, e.g. "1.5 MB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
 
 #[derive(Debug, Default)]
@@ -437,4 +700,136 @@ pub struct IndexStats {
     pub sessions_indexed: usize,
     pub sessions_skipped: usize,
     pub sessions_errored: usize,
+    /// Sessions deleted from the index because their JSONL file is gone
+    pub sessions_removed: usize,
+    /// Sessions newly marked as orphaned because their JSONL file is gone
+    pub sessions_orphaned: usize,
+    /// Chunks that were stored without a vector and embedded by backfill
+    pub chunks_embedded: usize,
+    /// Chunks still without a vector after indexing
+    pub chunks_missing_vectors: usize,
+    /// Lists in the ANN index after indexing (0 = no index)
+    pub ann_lists: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{Chunk, ChunkKind, ParsedSession};
+
+    fn prepared(session_id: &str) -> PreparedSession {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("sample-session.jsonl");
+        let parsed = parser::parse_conversation_jsonl(&fixture).unwrap();
+        let chunks = vec![Chunk {
+            kind: ChunkKind::Conversation,
+            text: parsed.full_text.clone(),
+            first_message: None,
+            last_message: None,
+        }];
+        PreparedSession {
+            session: ParsedSession {
+                session_id: session_id.to_string(),
+                project_path: "/tmp/project".to_string(),
+                first_prompt: parsed.first_prompt.clone(),
+                summary: None,
+                slug: None,
+                git_branch: None,
+                message_count: parsed.message_count,
+                created_at: "2025-01-01T00:00:00Z".to_string(),
+                modified_at: "2025-01-01T00:00:00Z".to_string(),
+                full_text: parsed.full_text.clone(),
+            },
+            parsed,
+            chunks,
+            mtime: 1,
+            append: false,
+            chunk_embeddings: None,
+            session_embedding: None,
+        }
+    }
+
+    fn count_rows(db: &Database, table: &str, session_id: &str) -> i64 {
+        db.conn()
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {} WHERE session_id = ?1", table),
+                [session_id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rewritten_prefix_rebuilds_session() {
+        const LINE: &str =
+            "{\"type\":\"human\",\"message\":{\"role\":\"user\",\"content\":\"one more thing\"}}\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("sample-session.jsonl");
+        // Enough later lines that the first ones are well before the last 4 KB
+        let original = std::fs::read_to_string(fixture).unwrap() + &LINE.repeat(100);
+        std::fs::write(&path, &original).unwrap();
+
+        let db = Database::open_in_memory().unwrap();
+        let mut session = prepared("rewritten");
+        session.parsed = parser::parse_conversation_jsonl(&path).unwrap();
+        let mut batch = WriteBatch::new(&db, 1);
+        batch.write(session).unwrap();
+        batch.commit(&mut IndexStats::default());
+
+        let config = Config::default();
+        let indexer = Indexer::new(&db, None, &config, false);
+        let plan = || {
+            let entry = serde_json::from_str(r#"{"sessionId": "rewritten"}"#).unwrap();
+            indexer
+                .plan_session(entry, path.clone(), "/tmp/project".to_string(), false)
+                .unwrap()
+        };
+
+        // Appended to: only the new lines are parsed
+        std::fs::write(&path, original.clone() + LINE).unwrap();
+        assert!(plan().append.is_some());
+
+        // An early line edited in place: full rebuild
+        std::fs::write(&path, original.replacen("401", "403", 1) + LINE).unwrap();
+        assert!(plan().append.is_none());
+    }
+
+    #[test]
+    fn test_write_batch_rolls_back_only_the_failed_session() {
+        let db = Database::open_in_memory().unwrap();
+        // Fails the write after the session's chunks and messages are stored
+        db.conn()
+            .execute_batch(
+                "CREATE TRIGGER fail_tool_calls BEFORE INSERT ON tool_calls
+                 WHEN NEW.session_id = 'broken'
+                 BEGIN SELECT RAISE(ABORT, 'write failed'); END;",
+            )
+            .unwrap();
+
+        let mut stats = IndexStats::default();
+        let mut batch = WriteBatch::new(&db, 2);
+        batch.write(prepared("first")).unwrap();
+        assert!(batch.write(prepared("broken")).is_err());
+        assert!(!batch.is_full());
+        batch.write(prepared("second")).unwrap();
+        assert!(batch.is_full());
+        batch.commit(&mut stats);
+        assert_eq!(stats.sessions_errored, 0);
+        assert!(db.conn().is_autocommit());
+
+        for session_id in ["first", "second"] {
+            assert_eq!(count_rows(&db, "sessions", session_id), 1);
+            assert!(count_rows(&db, "chunks", session_id) > 0);
+            assert_eq!(count_rows(&db, "tool_calls", session_id), 2);
+        }
+        for table in ["sessions", "chunks", "messages", "session_files"] {
+            assert_eq!(count_rows(&db, table, "broken"), 0, "{}", table);
+        }
+    }
 }
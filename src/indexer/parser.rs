@@ -1,8 +1,9 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Top-level structure of sessions-index.json
 #[derive(Debug, Deserialize)]
@@ -63,9 +64,19 @@ pub struct ConversationMessage {
     #[serde(default)]
     pub role: Option<String>,
 
+    #[serde(default)]
+    pub uuid: Option<String>,
+
+    #[serde(rename = "parentUuid", default)]
+    pub parent_uuid: Option<String>,
+
     #[serde(default)]
     pub message: Option<MessageContent>,
 
+    /// Top-level content (used by standalone `tool_result` lines)
+    #[serde(default)]
+    pub content: Option<serde_json::Value>,
+
     #[serde(default)]
     pub timestamp: Option<String>,
 }
@@ -103,6 +114,88 @@ pub fn parse_session_index(path: &Path) -> Result<Vec<SessionIndexEntry>> {
     Ok(index.entries)
 }
 
+/// A tool invocation extracted from a `tool_use` content block
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCall {
+    pub tool_name: String,
+    /// File the tool operated on (Read/Edit/Write/NotebookEdit, or the path of a Grep/Glob)
+    pub file_path: Option<String>,
+    /// Shell command (Bash)
+    pub command: Option<String>,
+    /// Search pattern, query or URL (Grep/Glob/WebSearch/WebFetch)
+    pub pattern: Option<String>,
+}
+
+/// What a chunk was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkKind {
+    /// User and assistant messages
+    Conversation,
+    /// Output returned by tools (compiler errors, test failures, stack traces)
+    ToolOutput,
+}
+
+impl ChunkKind {
+    /// Value stored in the `chunks.kind` column
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Conversation => "conversation",
+            Self::ToolOutput => "tool_output",
+        }
+    }
+
+    /// Parses a `chunks.kind` column value, defaulting to conversation
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "tool_output" => Self::ToolOutput,
+            _ => Self::Conversation,
+        }
+    }
+}
+
+/// A chunk of indexed text, with the range of messages it covers
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub kind: ChunkKind,
+    pub text: String,
+    /// Ordinal of the first message in the chunk (None for tool output)
+    pub first_message: Option<usize>,
+    /// Ordinal of the last message in the chunk (None for tool output)
+    pub last_message: Option<usize>,
+}
+
+/// A single user or assistant turn that contributed text to the conversation
+#[derive(Debug, Clone)]
+pub struct ParsedMessage {
+    pub uuid: Option<String>,
+    pub parent_uuid: Option<String>,
+    /// 0-based position among all text-bearing messages in the session
+    pub ordinal: usize,
+    /// "user" or "assistant"
+    pub role: String,
+    pub timestamp: Option<String>,
+    pub text: String,
+    /// Char offset of this message's line in `ParsedConversation::full_text`
+    pub char_offset: usize,
+}
+
+/// How far a JSONL file has been parsed, so appended lines can be parsed on their own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseCheckpoint {
+    /// Byte offset just past the last complete line that was parsed
+    pub byte_offset: u64,
+    /// Number of lines parsed so far
+    pub line_count: usize,
+    /// Number of text-bearing messages seen so far (next message ordinal)
+    pub message_count: usize,
+    /// Hash of every byte before `byte_offset`, used to detect a rewritten prefix
+    pub prefix_hash: String,
+}
+
+/// FNV-1a offset basis: the hash of no bytes
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
 /// Result of parsing a JSONL conversation file
 pub struct ParsedConversation {
     pub full_text: String,
@@ -110,33 +203,84 @@ pub struct ParsedConversation {
     pub message_count: usize,
     pub first_timestamp: Option<String>,
     pub last_timestamp: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+    /// Text output of each tool_result block, in order (not truncated)
+    pub tool_results: Vec<String>,
+    /// Messages that make up `full_text`, in order
+    pub messages: Vec<ParsedMessage>,
+    /// Where the next incremental parse should resume
+    pub checkpoint: ParseCheckpoint,
 }
 
 /// Parses a JSONL conversation file and extracts all text content (no truncation).
 pub fn parse_conversation_jsonl(path: &Path) -> Result<ParsedConversation> {
-    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
-    let reader = BufReader::new(file);
+    parse_conversation_jsonl_from(path, None)
+}
+
+/// Parses a JSONL conversation file starting at `checkpoint` (or at the start if None).
+/// Only complete lines are consumed, so a line that is still being written is picked up
+/// by the next parse. Message ordinals continue from `checkpoint.message_count`, and
+/// `message_count` counts only the messages parsed in this call.
+pub fn parse_conversation_jsonl_from(
+    path: &Path,
+    checkpoint: Option<&ParseCheckpoint>,
+) -> Result<ParsedConversation> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let start = checkpoint.cloned().unwrap_or_default();
+    file.seek(SeekFrom::Start(start.byte_offset))
+        .with_context(|| format!("Failed to seek in {:?}", path))?;
+    let mut reader = BufReader::new(file);
+
+    let mut byte_offset = start.byte_offset;
+    let mut line_count = start.line_count;
+    // Continued over each consumed line, so it always covers [0, byte_offset)
+    let mut prefix_hash = if start.prefix_hash.is_empty() {
+        FNV_OFFSET_BASIS
+    } else {
+        u64::from_str_radix(&start.prefix_hash, 16)
+            .with_context(|| format!("Invalid prefix hash {:?}", start.prefix_hash))?
+    };
+    let ordinal_base = start.message_count;
 
     let mut full_text = String::new();
     let mut first_prompt: Option<String> = None;
     let mut message_count: usize = 0;
     let mut first_timestamp: Option<String> = None;
     let mut last_timestamp: Option<String> = None;
-
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) => l,
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut tool_results: Vec<String> = Vec::new();
+    let mut messages: Vec<ParsedMessage> = Vec::new();
+    let mut full_text_chars: usize = 0;
+
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
             Err(e) => {
                 log::warn!("Error reading line from {:?}: {}", path, e);
-                continue;
+                break;
             }
         };
 
+        let line = String::from_utf8_lossy(&buf);
+        let parsed_line = serde_json::from_str::<ConversationMessage>(&line);
+
+        // A trailing line without a newline may still be being written:
+        // leave it for the next parse unless it is already valid JSON
+        if !buf.ends_with(b"\n") && parsed_line.is_err() {
+            break;
+        }
+        byte_offset += read as u64;
+        line_count += 1;
+        prefix_hash = fnv1a(prefix_hash, &buf);
+
         if line.trim().is_empty() {
             continue;
         }
 
-        let msg: ConversationMessage = match serde_json::from_str(&line) {
+        let msg: ConversationMessage = match parsed_line {
             Ok(m) => m,
             Err(_) => continue,
         };
@@ -149,6 +293,20 @@ pub fn parse_conversation_jsonl(path: &Path) -> Result<ParsedConversation> {
             last_timestamp = Some(ts.clone());
         }
 
+        if let Some(content) = msg.message.as_ref().and_then(|m| m.content.as_ref()) {
+            tool_calls.extend(extract_tool_calls_from_content(content));
+            tool_results.extend(extract_tool_results_from_content(content));
+        }
+
+        // Standalone tool result lines: {"type": "tool_result", "content": ...}
+        if msg.msg_type.as_deref() == Some("tool_result") {
+            if let Some(text) = msg.content.as_ref().and_then(extract_text_from_content) {
+                if !text.trim().is_empty() {
+                    tool_results.push(text);
+                }
+            }
+        }
+
         if let Some(text) = extract_message_text(&msg) {
             if text.trim().is_empty() {
                 continue;
@@ -169,10 +327,22 @@ pub fn parse_conversation_jsonl(path: &Path) -> Result<ParsedConversation> {
             }
 
             // Add to full text with role prefix for context
-            let prefix = if is_user { "User: " } else { "Assistant: " };
+            let prefix = role_prefix(is_user);
+            let char_offset = full_text_chars;
             full_text.push_str(prefix);
             full_text.push_str(&text);
             full_text.push('\n');
+            full_text_chars += prefix.len() + text.chars().count() + 1;
+
+            messages.push(ParsedMessage {
+                uuid: msg.uuid.clone(),
+                parent_uuid: msg.parent_uuid.clone(),
+                ordinal: ordinal_base + message_count - 1,
+                role: if is_user { "user" } else { "assistant" }.to_string(),
+                timestamp: msg.timestamp.clone(),
+                text,
+                char_offset,
+            });
         }
     }
 
@@ -182,38 +352,286 @@ pub fn parse_conversation_jsonl(path: &Path) -> Result<ParsedConversation> {
         message_count,
         first_timestamp,
         last_timestamp,
+        tool_calls,
+        tool_results,
+        messages,
+        checkpoint: ParseCheckpoint {
+            byte_offset,
+            line_count,
+            message_count: ordinal_base + message_count,
+            prefix_hash: format!("{:016x}", prefix_hash),
+        },
     })
 }
 
+/// Returns true if the file still starts with the content parsed up to `checkpoint`,
+/// i.e. it has only been appended to since
+pub fn checkpoint_matches(path: &Path, checkpoint: &ParseCheckpoint) -> Result<bool> {
+    let len = std::fs::metadata(path)
+        .with_context(|| format!("Failed to stat {:?}", path))?
+        .len();
+    if len < checkpoint.byte_offset {
+        return Ok(false);
+    }
+    Ok(format!("{:016x}", prefix_hash(path, checkpoint.byte_offset)?) == checkpoint.prefix_hash)
+}
+
+/// FNV-1a hash of the first `len` bytes of the file
+fn prefix_hash(path: &Path, len: u64) -> Result<u64> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut reader = BufReader::new(file.take(len));
+    let mut hash = FNV_OFFSET_BASIS;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        hash = fnv1a(hash, buf);
+        let consumed = buf.len();
+        reader.consume(consumed);
+    }
+    Ok(hash)
+}
+
+/// Continues an FNV-1a hash over `bytes`
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 /// Splits text into overlapping chunks for fine-grained search indexing.
-/// Tries to break at newline boundaries when possible.
 pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
-    if text.is_empty() {
+    let chars: Vec<char> = text.chars().collect();
+    chunk_spans(chars.len(), chunk_size, overlap)
+        .into_iter()
+        .map(|(start, end)| chars[start..end].iter().collect())
+        .collect()
+}
+
+/// Chunks a parsed conversation's full text, tagging each chunk with the messages it spans
+pub fn chunk_conversation(
+    parsed: &ParsedConversation,
+    chunk_size: usize,
+    overlap: usize,
+) -> Vec<Chunk> {
+    let chars: Vec<char> = parsed.full_text.chars().collect();
+    chunk_spans(chars.len(), chunk_size, overlap)
+        .into_iter()
+        .map(|(start, end)| {
+            let range = message_range(&parsed.messages, start, end);
+            Chunk {
+                kind: ChunkKind::Conversation,
+                text: chars[start..end].iter().collect(),
+                first_message: range.map(|(first, _)| first),
+                last_message: range.map(|(_, last)| last),
+            }
+        })
+        .collect()
+}
+
+/// Chunks a conversation along message boundaries.
+/// Packs whole user→assistant exchanges into chunks of up to `chunk_size` chars;
+/// an exchange that doesn't fit on its own is packed message by message, and
+/// messages longer than a chunk are split at paragraph or sentence boundaries.
+pub fn chunk_turns(messages: &[ParsedMessage], chunk_size: usize) -> Vec<Chunk> {
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut builder = ChunkBuilder::default();
+
+    for exchange in split_exchanges(messages) {
+        let lines: Vec<String> = exchange.iter().map(format_message).collect();
+        let exchange_len: usize = lines.iter().map(|l| l.chars().count()).sum();
+
+        if builder.len + exchange_len > chunk_size {
+            builder.flush(&mut chunks);
+        }
+
+        if exchange_len <= chunk_size {
+            for (message, line) in exchange.iter().zip(&lines) {
+                builder.push(message.ordinal, line, chunk_size, &mut chunks);
+            }
+            continue;
+        }
+
+        for (message, line) in exchange.iter().zip(&lines) {
+            if line.chars().count() <= chunk_size {
+                builder.push(message.ordinal, line, chunk_size, &mut chunks);
+                continue;
+            }
+
+            let prefix = role_prefix(message.role == "user");
+            let budget = chunk_size.saturating_sub(prefix.len() + 1).max(1);
+            for piece in split_at_boundaries(&message.text, budget) {
+                let piece_line = format!("{}{}\n", prefix, piece);
+                builder.push(message.ordinal, &piece_line, chunk_size, &mut chunks);
+            }
+        }
+    }
+
+    builder.flush(&mut chunks);
+    chunks
+}
+
+/// Accumulates message lines into a chunk under construction
+#[derive(Default)]
+struct ChunkBuilder {
+    text: String,
+    len: usize,
+    first: Option<usize>,
+    last: Option<usize>,
+}
+
+impl ChunkBuilder {
+    /// Appends a line, flushing the current chunk first if the line would overflow it
+    fn push(&mut self, ordinal: usize, line: &str, chunk_size: usize, chunks: &mut Vec<Chunk>) {
+        let line_len = line.chars().count();
+        if self.len > 0 && self.len + line_len > chunk_size {
+            self.flush(chunks);
+        }
+        self.text.push_str(line);
+        self.len += line_len;
+        self.first.get_or_insert(ordinal);
+        self.last = Some(ordinal);
+    }
+
+    fn flush(&mut self, chunks: &mut Vec<Chunk>) {
+        if self.len == 0 {
+            return;
+        }
+        let builder = std::mem::take(self);
+        chunks.push(Chunk {
+            kind: ChunkKind::Conversation,
+            text: builder.text,
+            first_message: builder.first,
+            last_message: builder.last,
+        });
+    }
+}
+
+/// Groups messages into exchanges: a user message followed by the assistant's replies
+fn split_exchanges(messages: &[ParsedMessage]) -> Vec<&[ParsedMessage]> {
+    let mut exchanges = Vec::new();
+    let mut start = 0;
+    for (i, message) in messages.iter().enumerate() {
+        if i > start && message.role == "user" {
+            exchanges.push(&messages[start..i]);
+            start = i;
+        }
+    }
+    if start < messages.len() {
+        exchanges.push(&messages[start..]);
+    }
+    exchanges
+}
+
+/// Splits text into pieces of at most `max_chars` chars, preferring paragraph breaks,
+/// then sentence boundaries, then word boundaries
+fn split_at_boundaries(text: &str, max_chars: usize) -> Vec<String> {
+    let mut atoms: Vec<String> = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        if paragraph.chars().count() <= max_chars {
+            atoms.push(paragraph.to_string());
+            continue;
+        }
+        for sentence in paragraph.split_sentence_bounds() {
+            if sentence.chars().count() <= max_chars {
+                atoms.push(sentence.to_string());
+                continue;
+            }
+            for word in sentence.split_word_bounds() {
+                let chars: Vec<char> = word.chars().collect();
+                atoms.extend(
+                    chars
+                        .chunks(max_chars)
+                        .map(|c| c.iter().collect::<String>()),
+                );
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    for atom in atoms {
+        let atom_len = atom.chars().count();
+        if current_len > 0 && current_len + atom_len > max_chars {
+            pieces.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        current.push_str(&atom);
+        current_len += atom_len;
+    }
+    pieces.push(current);
+
+    pieces
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Formats a message the way it appears in `full_text`
+fn format_message(message: &ParsedMessage) -> String {
+    format!("{}{}\n", role_prefix(message.role == "user"), message.text)
+}
+
+/// Role prefix used in indexed text
+fn role_prefix(is_user: bool) -> &'static str {
+    if is_user {
+        "User: "
+    } else {
+        "Assistant: "
+    }
+}
+
+/// Computes the `[start, end)` char ranges that `chunk_text` cuts a text of `len` chars into
+pub fn chunk_spans(len: usize, chunk_size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    if len == 0 {
         return vec![];
     }
 
-    let chars: Vec<char> = text.chars().collect();
-    if chars.len() <= chunk_size {
-        return vec![text.to_string()];
+    if len <= chunk_size {
+        return vec![(0, len)];
     }
 
-    let mut chunks = Vec::new();
+    let mut spans = Vec::new();
     let step = chunk_size.saturating_sub(overlap).max(1);
     let mut start = 0;
 
-    while start < chars.len() {
-        let end = (start + chunk_size).min(chars.len());
-        let chunk: String = chars[start..end].iter().collect();
-        chunks.push(chunk);
+    while start < len {
+        let end = (start + chunk_size).min(len);
+        spans.push((start, end));
 
-        if end >= chars.len() {
+        if end >= len {
             break;
         }
 
         start += step;
     }
 
-    chunks
+    spans
+}
+
+/// Returns the ordinals of the first and last message overlapping the `[start, end)` char range.
+/// A message spans from its `char_offset` to the next message's; `messages` is
+/// sorted by `char_offset`, so both ends are found by binary search.
+pub fn message_range(
+    messages: &[ParsedMessage],
+    start: usize,
+    end: usize,
+) -> Option<(usize, usize)> {
+    // The last message starting at or before `start` contains it
+    let first = messages
+        .partition_point(|m| m.char_offset <= start)
+        .saturating_sub(1);
+    // The last message starting before `end`
+    let last = messages
+        .partition_point(|m| m.char_offset < end)
+        .checked_sub(1)?;
+    (first <= last).then(|| (messages[first].ordinal, messages[last].ordinal))
 }
 
 /// Extracts text content from a conversation message
@@ -252,6 +670,66 @@ fn extract_text_from_content(content: &serde_json::Value) -> Option<String> {
     }
 }
 
+/// Extracts tool invocations from message content (`{"type": "tool_use", "name": ..., "input": {...}}`)
+fn extract_tool_calls_from_content(content: &serde_json::Value) -> Vec<ToolCall> {
+    let Some(arr) = content.as_array() else {
+        return Vec::new();
+    };
+
+    arr.iter()
+        .filter_map(|item| item.as_object())
+        .filter(|obj| obj.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        .filter_map(|obj| {
+            let tool_name = obj.get("name").and_then(|n| n.as_str())?.to_string();
+            let input = obj.get("input");
+            let field = |keys: &[&str]| -> Option<String> {
+                let input = input?;
+                keys.iter()
+                    .find_map(|k| input.get(*k).and_then(|v| v.as_str()))
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+            };
+
+            Some(ToolCall {
+                tool_name,
+                file_path: field(&["file_path", "notebook_path", "path"]),
+                command: field(&["command"]),
+                pattern: field(&["pattern", "query", "url"]),
+            })
+        })
+        .collect()
+}
+
+/// Extracts the text of `{"type": "tool_result", "content": ...}` blocks from message content
+fn extract_tool_results_from_content(content: &serde_json::Value) -> Vec<String> {
+    let Some(arr) = content.as_array() else {
+        return Vec::new();
+    };
+
+    arr.iter()
+        .filter_map(|item| item.as_object())
+        .filter(|obj| obj.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+        .filter_map(|obj| obj.get("content").and_then(extract_text_from_content))
+        .filter(|text| !text.trim().is_empty())
+        .collect()
+}
+
+/// Builds the text indexed as tool-output chunks.
+/// Each result is capped at `max_chars_per_result` characters.
+pub fn build_tool_output_text(tool_results: &[String], max_chars_per_result: usize) -> String {
+    let mut text = String::new();
+    for result in tool_results {
+        let trimmed = result.trim();
+        if trimmed.len() < 5 {
+            continue;
+        }
+        text.push_str("Tool output: ");
+        text.extend(trimmed.chars().take(max_chars_per_result));
+        text.push('\n');
+    }
+    text
+}
+
 /// Checks if a message is from the user
 fn is_user_message(msg: &ConversationMessage) -> bool {
     if let Some(ref role) = msg.role {
@@ -328,6 +806,156 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_tool_calls_from_content() {
+        let content = serde_json::json!([
+            {"type": "text", "text": "Let me look"},
+            {"type": "tool_use", "name": "Edit", "input": {"file_path": "src/auth/token.rs", "old_string": "a", "new_string": "b"}},
+            {"type": "tool_use", "name": "Bash", "input": {"command": "cargo flamegraph --bin api"}},
+            {"type": "tool_use", "name": "Grep", "input": {"pattern": "refresh_token", "path": "src"}}
+        ]);
+        let calls = extract_tool_calls_from_content(&content);
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].tool_name, "Edit");
+        assert_eq!(calls[0].file_path.as_deref(), Some("src/auth/token.rs"));
+        assert_eq!(
+            calls[1].command.as_deref(),
+            Some("cargo flamegraph --bin api")
+        );
+        assert_eq!(calls[2].pattern.as_deref(), Some("refresh_token"));
+        assert_eq!(calls[2].file_path.as_deref(), Some("src"));
+
+        assert!(extract_tool_calls_from_content(&serde_json::json!("plain text")).is_empty());
+    }
+
+    #[test]
+    fn test_extract_tool_results_from_content() {
+        let content = serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "t1", "content": "error[E0308]: mismatched types"},
+            {"type": "tool_result", "tool_use_id": "t2", "content": [{"type": "text", "text": "test auth::refresh ... FAILED"}]},
+            {"type": "text", "text": "not a tool result"}
+        ]);
+        let results = extract_tool_results_from_content(&content);
+        assert_eq!(
+            results,
+            vec![
+                "error[E0308]: mismatched types".to_string(),
+                "test auth::refresh ... FAILED".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_tool_output_text_caps_results() {
+        let results = vec!["x".repeat(100), "ok".to_string()];
+        let text = build_tool_output_text(&results, 10);
+        assert_eq!(text, format!("Tool output: {}\n", "x".repeat(10)));
+    }
+
+    fn turn(ordinal: usize, role: &str, text: &str) -> ParsedMessage {
+        ParsedMessage {
+            uuid: None,
+            parent_uuid: None,
+            ordinal,
+            role: role.to_string(),
+            timestamp: None,
+            text: text.to_string(),
+            char_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_chunk_turns_packs_whole_exchanges() {
+        let messages = vec![
+            turn(0, "user", "How do I rotate refresh tokens?"),
+            turn(
+                1,
+                "assistant",
+                "Store a token family id and revoke on reuse.",
+            ),
+            turn(2, "user", "Now add dark mode to settings."),
+            turn(3, "assistant", "Added a toggle persisted in localStorage."),
+        ];
+
+        // Both exchanges fit in one chunk
+        let chunks = chunk_turns(&messages, 1000);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].first_message, Some(0));
+        assert_eq!(chunks[0].last_message, Some(3));
+
+        // Only one exchange fits per chunk: never mix the two
+        let chunks = chunk_turns(&messages, 100);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            (chunks[0].first_message, chunks[0].last_message),
+            (Some(0), Some(1))
+        );
+        assert_eq!(
+            (chunks[1].first_message, chunks[1].last_message),
+            (Some(2), Some(3))
+        );
+        assert!(chunks[1].text.starts_with("User: Now add dark mode"));
+    }
+
+    #[test]
+    fn test_chunk_turns_splits_oversized_message_at_sentences() {
+        let long = "The first sentence is here. The second sentence follows it. A third one ends the paragraph.";
+        let messages = vec![turn(0, "user", "Explain."), turn(1, "assistant", long)];
+
+        let chunks = chunk_turns(&messages, 50);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.text.chars().count() <= 50, "{:?}", chunk.text);
+        }
+        // Pieces of the long message start at sentence boundaries, not mid-word
+        for chunk in chunks.iter().filter(|c| c.first_message == Some(1)) {
+            assert!(
+                chunk.text.starts_with("Assistant: The") || chunk.text.starts_with("Assistant: A")
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_at_boundaries_prefers_paragraphs() {
+        let text = "First paragraph.\n\nSecond paragraph.";
+        assert_eq!(
+            split_at_boundaries(text, 20),
+            vec![
+                "First paragraph.".to_string(),
+                "Second paragraph.".to_string()
+            ]
+        );
+
+        // A single unbreakable word is hard-split
+        let pieces = split_at_boundaries(&"x".repeat(25), 10);
+        assert_eq!(pieces.len(), 3);
+    }
+
+    fn message(ordinal: usize, char_offset: usize) -> ParsedMessage {
+        ParsedMessage {
+            uuid: None,
+            parent_uuid: None,
+            ordinal,
+            role: "user".to_string(),
+            timestamp: None,
+            text: String::new(),
+            char_offset,
+        }
+    }
+
+    #[test]
+    fn test_message_range() {
+        let messages = vec![message(0, 0), message(2, 100), message(3, 250)];
+        assert_eq!(message_range(&messages, 0, 50), Some((0, 0)));
+        assert_eq!(message_range(&messages, 50, 150), Some((0, 2)));
+        assert_eq!(message_range(&messages, 120, 1000), Some((2, 3)));
+        // Boundaries: a message starts where the previous one ends
+        assert_eq!(message_range(&messages, 0, 100), Some((0, 0)));
+        assert_eq!(message_range(&messages, 100, 250), Some((2, 2)));
+        assert_eq!(message_range(&messages[1..], 0, 100), None);
+        assert_eq!(message_range(&[], 0, 10), None);
+    }
+
     #[test]
     fn test_is_tool_noise() {
         assert!(is_tool_noise("{\"tool_use\": true}"));
@@ -0,0 +1,380 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use super::embedder::Embedder;
+use super::parser::SessionIndexEntry;
+use super::parser::{self, Chunk, ChunkKind, ParseCheckpoint, ParsedConversation, ParsedSession};
+use crate::config::{ChunkStrategy, Config};
+use crate::db::queries::SessionRow;
+
+/// A session scheduled for indexing, with everything the workers need read from
+/// the database up front (workers never touch SQLite)
+pub struct SessionJob {
+    pub entry: SessionIndexEntry,
+    pub jsonl_path: PathBuf,
+    pub decoded_path: String,
+    /// Set when only lines appended since the last run need indexing
+    pub append: Option<AppendFrom>,
+}
+
+/// Where an append-only reindex picks up
+pub struct AppendFrom {
+    pub checkpoint: ParseCheckpoint,
+    pub existing: SessionRow,
+}
+
+/// A parsed, chunked and (optionally) embedded session, ready to be written
+pub struct PreparedSession {
+    pub session: ParsedSession,
+    pub parsed: ParsedConversation,
+    pub chunks: Vec<Chunk>,
+    pub mtime: i64,
+    /// True if `parsed` only holds lines appended since the last run
+    pub append: bool,
+    /// One embedding per chunk, when an embedder is loaded
+    pub chunk_embeddings: Option<Vec<Vec<f32>>>,
+    /// Session-level embedding (full reindexes only)
+    pub session_embedding: Option<Vec<f32>>,
+}
+
+/// Runs jobs through the indexing pipeline:
+/// a pool of `workers` threads parses and chunks sessions, a single stage embeds
+/// them, and `write` is called on the current thread for each result in
+/// completion order — so all SQLite writes happen on one thread.
+pub fn run<F>(
+    jobs: Vec<SessionJob>,
+    config: &Config,
+    embedder: Option<&mut Embedder>,
+    workers: usize,
+    mut write: F,
+) where
+    F: FnMut(String, Result<PreparedSession>),
+{
+    let workers = workers.max(1);
+    let queue = Mutex::new(jobs.into_iter());
+    let (parsed_tx, parsed_rx) =
+        mpsc::sync_channel::<(String, Result<PreparedSession>)>(workers * 2);
+    let (embedded_tx, embedded_rx) = mpsc::sync_channel(workers * 2);
+
+    thread::scope(|scope| {
+        // Stage 1: parse and chunk
+        for _ in 0..workers {
+            let tx = parsed_tx.clone();
+            let queue = &queue;
+            scope.spawn(move || loop {
+                let job = queue.lock().expect("job queue poisoned").next();
+                let Some(job) = job else { break };
+                let session_id = job.entry.session_id.clone();
+                let result = prepare_session(config, job);
+                if tx.send((session_id, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(parsed_tx);
+
+        // Stage 2: embed
+        scope.spawn(move || {
+            let mut embedder = embedder;
+            for (session_id, result) in parsed_rx {
+                let result = match embedder.as_deref_mut() {
+                    Some(embedder) => result.and_then(|mut prepared| {
+                        embed_session(embedder, &mut prepared)?;
+                        Ok(prepared)
+                    }),
+                    None => result,
+                };
+                if embedded_tx.send((session_id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Stage 3: write, on the calling thread
+        for (session_id, result) in embedded_rx {
+            write(session_id, result);
+        }
+    });
+}
+
+/// Number of parse/chunk workers for a configured value (0 = one per core)
+pub fn worker_count(configured: usize) -> usize {
+    if configured > 0 {
+        return configured;
+    }
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Parses a session file (or its appended tail) and chunks it
+fn prepare_session(config: &Config, job: SessionJob) -> Result<PreparedSession> {
+    let SessionJob {
+        entry,
+        jsonl_path,
+        decoded_path,
+        append,
+    } = job;
+
+    let mtime = parser::file_mtime(&jsonl_path)?;
+
+    let (session, parsed, append) = match append {
+        Some(AppendFrom {
+            checkpoint,
+            existing,
+        }) => {
+            let parsed = parser::parse_conversation_jsonl_from(&jsonl_path, Some(&checkpoint))?;
+            let session = merge_appended(config, &entry, existing, &parsed);
+            (session, parsed, true)
+        }
+        None => {
+            let parsed = parser::parse_conversation_jsonl(&jsonl_path)?;
+            let session = build_session(config, &entry, &decoded_path, mtime, &parsed);
+            (session, parsed, false)
+        }
+    };
+
+    let chunks = build_chunks(config, &parsed);
+
+    Ok(PreparedSession {
+        session,
+        parsed,
+        chunks,
+        mtime,
+        append,
+        chunk_embeddings: None,
+        session_embedding: None,
+    })
+}
+
+/// Builds session metadata for a full reindex
+fn build_session(
+    config: &Config,
+    entry: &SessionIndexEntry,
+    decoded_path: &str,
+    mtime: i64,
+    parsed: &ParsedConversation,
+) -> ParsedSession {
+    // For sessions without index metadata, derive timestamps from file mtime
+    let mtime_rfc3339 = chrono::DateTime::from_timestamp(mtime, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    // Prefer: index metadata > JSONL timestamps > file mtime
+    let created_at = entry
+        .created
+        .clone()
+        .or_else(|| entry.created_at.clone())
+        .or_else(|| parsed.first_timestamp.clone())
+        .unwrap_or_else(|| mtime_rfc3339.clone());
+
+    let modified_at = entry
+        .modified
+        .clone()
+        .or_else(|| entry.last_activity_at.clone())
+        .or_else(|| parsed.last_timestamp.clone())
+        .unwrap_or(mtime_rfc3339);
+
+    // Truncate full_text for the sessions table (metadata/preview)
+    let truncated_full_text: String = parsed
+        .full_text
+        .chars()
+        .take(config.max_text_chars)
+        .collect();
+
+    ParsedSession {
+        session_id: entry.session_id.clone(),
+        project_path: entry
+            .project_path
+            .clone()
+            .unwrap_or_else(|| decoded_path.to_string()),
+        first_prompt: parsed
+            .first_prompt
+            .clone()
+            .or_else(|| entry.first_prompt.clone())
+            .or_else(|| entry.summary.clone()),
+        summary: entry.summary.clone(),
+        slug: entry.slug.clone(),
+        git_branch: entry.git_branch.clone(),
+        message_count: entry.message_count.unwrap_or(parsed.message_count),
+        created_at,
+        modified_at,
+        full_text: truncated_full_text,
+    }
+}
+
+/// Merges newly appended lines into the stored session metadata
+fn merge_appended(
+    config: &Config,
+    entry: &SessionIndexEntry,
+    existing: SessionRow,
+    parsed: &ParsedConversation,
+) -> ParsedSession {
+    let mut full_text = existing.full_text;
+    let room = config
+        .max_text_chars
+        .saturating_sub(full_text.chars().count());
+    full_text.extend(parsed.full_text.chars().take(room));
+
+    ParsedSession {
+        session_id: entry.session_id.clone(),
+        project_path: entry.project_path.clone().unwrap_or(existing.project_path),
+        first_prompt: existing
+            .first_prompt
+            .or_else(|| parsed.first_prompt.clone())
+            .or_else(|| entry.first_prompt.clone()),
+        summary: entry.summary.clone().or(existing.summary),
+        slug: entry.slug.clone().or(existing.slug),
+        git_branch: entry.git_branch.clone().or(existing.git_branch),
+        message_count: entry
+            .message_count
+            .unwrap_or(existing.message_count.unwrap_or(0) as usize + parsed.message_count),
+        created_at: existing.created_at,
+        modified_at: entry
+            .modified
+            .clone()
+            .or_else(|| entry.last_activity_at.clone())
+            .or_else(|| parsed.last_timestamp.clone())
+            .unwrap_or(existing.modified_at),
+        full_text,
+    }
+}
+
+/// Identifies the settings that shape a session's stored text and chunks
+/// (stored in index_meta; when it changes every session is re-chunked)
+pub fn chunking_fingerprint(config: &Config) -> String {
+    let mut fingerprint = format!(
+        "{} chunk_size={} chunk_overlap={} max_text_chars={}",
+        config.chunk_strategy.as_str(),
+        config.chunk_size,
+        config.chunk_overlap,
+        config.max_text_chars
+    );
+    if config.index_tool_results {
+        fingerprint.push_str(&format!(" tool_results={}", config.max_tool_result_chars));
+    }
+    fingerprint
+}
+
+/// Cuts a parsed conversation into chunks using the configured strategy
+fn build_chunks(config: &Config, parsed: &ParsedConversation) -> Vec<Chunk> {
+    let mut chunks = match config.chunk_strategy {
+        ChunkStrategy::Fixed => {
+            parser::chunk_conversation(parsed, config.chunk_size, config.chunk_overlap)
+        }
+        ChunkStrategy::Turns => parser::chunk_turns(&parsed.messages, config.chunk_size),
+    };
+
+    // Optionally add tool output as its own chunk type
+    if config.index_tool_results {
+        let tool_output =
+            parser::build_tool_output_text(&parsed.tool_results, config.max_tool_result_chars);
+        chunks.extend(
+            parser::chunk_text(&tool_output, config.chunk_size, config.chunk_overlap)
+                .into_iter()
+                .map(|text| Chunk {
+                    kind: ChunkKind::ToolOutput,
+                    text,
+                    first_message: None,
+                    last_message: None,
+                }),
+        );
+    }
+
+    chunks
+}
+
+/// Embeds all chunks of a session in batches, plus the session-level text on full reindexes
+fn embed_session(embedder: &mut Embedder, prepared: &mut PreparedSession) -> Result<()> {
+    let texts: Vec<&str> = prepared.chunks.iter().map(|c| c.text.as_str()).collect();
+    prepared.chunk_embeddings = Some(embedder.embed_batch(&texts)?);
+
+    // Also keep session-level embedding for backward compatibility
+    if !prepared.append {
+        let text_for_embedding = build_embedding_text(&prepared.session);
+        prepared.session_embedding = Some(embedder.embed(&text_for_embedding)?);
+    }
+
+    Ok(())
+}
+
+/// Builds the text to embed at session level, prioritizing summary and first prompt
+fn build_embedding_text(session: &ParsedSession) -> String {
+    let mut parts = Vec::new();
+
+    if let Some(ref summary) = session.summary {
+        parts.push(summary.clone());
+    }
+    if let Some(ref first_prompt) = session.first_prompt {
+        parts.push(first_prompt.clone());
+    }
+    if !session.full_text.is_empty() {
+        let truncated: String = session.full_text.chars().take(2000).collect();
+        parts.push(truncated);
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(session_id: &str, jsonl_path: PathBuf) -> SessionJob {
+        SessionJob {
+            entry: SessionIndexEntry {
+                session_id: session_id.to_string(),
+                full_path: None,
+                first_prompt: None,
+                summary: None,
+                slug: None,
+                project_path: None,
+                message_count: None,
+                created: None,
+                modified: None,
+                created_at: None,
+                last_activity_at: None,
+                file_mtime: None,
+                git_branch: None,
+            },
+            jsonl_path,
+            decoded_path: "/tmp/project".to_string(),
+            append: None,
+        }
+    }
+
+    #[test]
+    fn test_run_prepares_every_job() {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("sample-session.jsonl");
+        let mut jobs: Vec<SessionJob> = (0..10)
+            .map(|i| job(&format!("session-{}", i), fixture.clone()))
+            .collect();
+        jobs.push(job("missing", PathBuf::from("/nonexistent/session.jsonl")));
+
+        let config = Config::default();
+        let mut written = Vec::new();
+        let mut errors = Vec::new();
+        run(jobs, &config, None, 3, |session_id, result| match result {
+            Ok(prepared) => {
+                assert_eq!(prepared.session.session_id, session_id);
+                assert_eq!(prepared.session.project_path, "/tmp/project");
+                assert!(!prepared.chunks.is_empty());
+                assert!(!prepared.append);
+                assert!(prepared.chunk_embeddings.is_none());
+                written.push(session_id);
+            }
+            Err(_) => errors.push(session_id),
+        });
+
+        written.sort();
+        let mut expected: Vec<String> = (0..10).map(|i| format!("session-{}", i)).collect();
+        expected.sort();
+        assert_eq!(written, expected);
+        assert_eq!(errors, vec!["missing".to_string()]);
+    }
+}
@@ -84,6 +84,116 @@ pub fn extract_terms(text: &str) -> Vec<String> {
         .collect()
 }
 
+/// Splits an identifier at camelCase boundaries: `getUserById` → get, User, By, Id;
+/// `HTTPServer` → HTTP, Server. Digits stay attached (`sha256`, `Base64Decoder` → Base64, Decoder).
+pub fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..chars.len() {
+        let (pos, c) = chars[i];
+        let prev = chars[i - 1].1;
+        let next_is_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+        let boundary = c.is_uppercase()
+            && ((prev.is_lowercase() || prev.is_ascii_digit())
+                || (prev.is_uppercase() && next_is_lower));
+        if boundary {
+            parts.push(&word[start..pos]);
+            start = pos;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+/// Sub-tokens of the code identifiers in a text, indexed next to the text itself.
+///
+/// The FTS5 unicode61 tokenizer already splits on `_`, `/`, `.` and `-`, so
+/// `max_text_chars` and `src/db/queries.rs` are searchable by their parts; what it
+/// can't see are camelCase boundaries. For every word with such boundaries (once
+/// per distinct word) this emits its lowercased parts in order, so `getUserById`
+/// also matches `user by id` and `"user by id"`.
+pub fn code_subtokens(text: &str) -> String {
+    let mut seen = std::collections::HashSet::new();
+    let mut out = String::new();
+
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let parts = split_camel_case(word);
+        if parts.len() < 2 || !seen.insert(word) {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&parts.join(" ").to_lowercase());
+    }
+
+    out
+}
+
+/// True for characters of scripts written without spaces between words
+/// (CJK ideographs, kana, Hangul syllables)
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2A6DF}')
+}
+
+/// Overlapping two-character pieces of each CJK run in a text, one run per line:
+/// `数据库迁移` → `数据 据库 库迁 迁移`. Single-character runs are kept as they are.
+pub fn cjk_bigrams(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut run: Vec<char> = Vec::new();
+
+    let mut flush = |run: &mut Vec<char>| {
+        match run.len() {
+            0 => {}
+            1 => lines.push(run[0].to_string()),
+            _ => lines.push(
+                run.windows(2)
+                    .map(|w| w.iter().collect::<String>())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            run.push(c);
+        } else {
+            flush(&mut run);
+        }
+    }
+    flush(&mut run);
+
+    lines.join("\n")
+}
+
+/// Extra text indexed next to a chunk: code sub-tokens, plus CJK bigrams when enabled
+pub fn index_tokens(text: &str, cjk: bool) -> String {
+    let mut tokens = code_subtokens(text);
+    if cjk {
+        let bigrams = cjk_bigrams(text);
+        if !bigrams.is_empty() {
+            if !tokens.is_empty() {
+                tokens.push('\n');
+            }
+            tokens.push_str(&bigrams);
+        }
+    }
+    tokens
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +225,37 @@ mod tests {
         assert!(!terms.contains(&"is".to_string()));
         assert!(!terms.contains(&"a".to_string()));
     }
+
+    #[test]
+    fn test_split_camel_case() {
+        assert_eq!(
+            split_camel_case("getUserById"),
+            vec!["get", "User", "By", "Id"]
+        );
+        assert_eq!(split_camel_case("HTTPServer"), vec!["HTTP", "Server"]);
+        assert_eq!(split_camel_case("parseJSON"), vec!["parse", "JSON"]);
+        assert_eq!(split_camel_case("Base64Decoder"), vec!["Base64", "Decoder"]);
+        assert_eq!(split_camel_case("sha256"), vec!["sha256"]);
+        assert_eq!(split_camel_case("plain"), vec!["plain"]);
+        assert_eq!(split_camel_case(""), vec![""]);
+    }
+
+    #[test]
+    fn test_code_subtokens() {
+        let text = "Call getUserById from src/services/UserService.ts, then getUserById again; max_text_chars is fine";
+        assert_eq!(code_subtokens(text), "get user by id\nuser service");
+        assert_eq!(code_subtokens("nothing to split here"), "");
+    }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        assert_eq!(
+            cjk_bigrams("修复数据库迁移 bug"),
+            "修复 复数 数据 据库 库迁 迁移"
+        );
+        assert_eq!(cjk_bigrams("ログ と 設定"), "ログ\nと\n設定");
+        assert_eq!(cjk_bigrams("no cjk here"), "");
+        assert_eq!(index_tokens("fixUp 数据库", true), "fix up\n数据 据库");
+        assert_eq!(index_tokens("fixUp 数据库", false), "fix up");
+    }
 }
@@ -6,3 +6,4 @@ pub mod indexer;
 pub mod model;
 pub mod search;
 pub mod tui;
+pub mod watch;
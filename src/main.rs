@@ -6,10 +6,12 @@ mod indexer;
 mod model;
 mod search;
 mod tui;
+mod watch;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use colored::Colorize;
+use std::time::Duration;
 
 use cli::{Cli, Commands};
 use config::Config;
@@ -24,6 +26,7 @@ fn main() -> Result<()> {
             Commands::Search(args) => cmd_search(args),
             Commands::Index(args) => cmd_index(args),
             Commands::List(args) => cmd_list(args),
+            Commands::Watch(args) => cmd_watch(args),
             Commands::Config => cmd_config(),
         },
         Err(_) => {
@@ -37,13 +40,30 @@ fn main() -> Result<()> {
             let mut semantic = false;
             let mut no_tui = false;
             let mut json = false;
+            let mut days = 30;
+            let mut project = None;
+            let mut branch = None;
+            let mut tool = None;
+            let mut file = None;
+            let mut command = None;
             let mut query_parts = Vec::new();
-            for arg in &args {
+            let mut iter = args.iter();
+            while let Some(arg) = iter.next() {
                 match arg.as_str() {
                     "--exact" => exact = true,
                     "--semantic" => semantic = true,
                     "--no-tui" => no_tui = true,
                     "--json" => json = true,
+                    "--days" => {
+                        if let Some(n) = iter.next().and_then(|v| v.parse().ok()) {
+                            days = n;
+                        }
+                    }
+                    "--project" => project = iter.next().cloned(),
+                    "--branch" => branch = iter.next().cloned(),
+                    "--tool" => tool = iter.next().cloned(),
+                    "--file" => file = iter.next().cloned(),
+                    "--command" => command = iter.next().cloned(),
                     _ if !arg.starts_with('-') => query_parts.push(arg.as_str()),
                     _ => {} // ignore unknown flags
                 }
@@ -55,13 +75,18 @@ fn main() -> Result<()> {
             let query = query_parts.join(" ");
             cmd_search(cli::SearchArgs {
                 query,
-                days: 30,
-                project: None,
+                days,
+                project,
                 limit: 20,
                 no_tui,
                 json,
                 exact,
                 semantic,
+                branch,
+                kind: None,
+                tool,
+                file,
+                command,
                 bm25_weight: 3.0,
                 vec_weight: 1.0,
             })
@@ -70,20 +95,54 @@ fn main() -> Result<()> {
 }
 
 fn cmd_search(args: cli::SearchArgs) -> Result<()> {
+    // Split qualifiers (project:, after:, -term, ...) from the free text up front,
+    // so a malformed query fails with a clear message before any work is done
+    let parsed_query = search::query::parse_query(&args.query)
+        .map_err(|e| anyhow::anyhow!("Invalid query: {}", e))?;
+    if parsed_query.text.trim().is_empty() && !parsed_query.has_filters() {
+        anyhow::bail!("Invalid query: nothing to search for");
+    }
+
     let config = Config::load()?;
-    let db = Database::open(&config::db_path())?;
+    let db = open_database(&config, true)?;
 
     // Try to load embedder for vector search
-    let mut embedder = load_embedder_if_available();
+    let mut embedder = load_embedder_if_available(&config);
+
+    // Updating the index needs the index lock; if another process holds it,
+    // search what that process has committed so far rather than waiting
+    let index_lock = try_lock_index(&db);
 
-    // JIT index: quick check for new/changed sessions
-    {
+    // JIT index: quick check for new/changed sessions (a running watcher keeps it warm)
+    if index_lock.is_some() && !watch::is_watcher_alive() {
         let mut indexer = indexer::Indexer::new(&db, None, &config, false);
         if let Err(e) = indexer.jit_index() {
             log::warn!("JIT index error: {}", e);
         }
     }
 
+    // Semantic-only search can't see chunks without vectors, so embed them first
+    // (unless another process is indexing, in which case it embeds them)
+    let embed_first = args.semantic && embedder.is_some();
+    if embed_first && index_lock.is_some() {
+        let pending = db.count_pending_chunks()?;
+        if pending > 0 {
+            eprintln!(
+                "{} Embedding {} new chunks before searching...",
+                "→".green(),
+                pending
+            );
+            let mut indexer = indexer::Indexer::new(&db, embedder.take(), &config, false);
+            indexer.backfill_embeddings(None)?;
+            embedder = indexer.into_embedder();
+        }
+    }
+    // Release the lock before searching; the background backfill takes it itself
+    drop(index_lock);
+    if !embed_first && config.backfill_after_search && !watch::is_watcher_alive() {
+        spawn_background_backfill(&db);
+    }
+
     // Apply --exact / --semantic overrides
     let (bm25_weight, vec_weight) = if args.exact {
         (args.bm25_weight, 0.0)
@@ -100,18 +159,40 @@ fn cmd_search(args: cli::SearchArgs) -> Result<()> {
         embedder.as_mut()
     };
 
+    let options = search::SearchOptions {
+        limit: args.limit,
+        bm25_weight,
+        vec_weight,
+        rrf_k: config.rrf_k,
+        fuzzy_weight: if args.semantic {
+            0.0
+        } else {
+            config.fuzzy_weight
+        },
+        recency_halflife: config.recency_halflife,
+        exact: args.exact,
+        ann_probes: config.ann_probes,
+    };
+    let mut filter = search::filter::SearchFilter {
+        tool: args.tool.clone(),
+        file: args.file.clone(),
+        command: args.command.clone(),
+        project: args.project.clone(),
+        branch: args.branch.clone(),
+        kind: args.kind,
+        exclude_projects: config.exclude_projects.clone(),
+        ..Default::default()
+    }
+    .with_days(args.days);
+    parsed_query.apply(&mut filter);
+
     // Perform hybrid search
     let results = search::hybrid_search(
         &db,
         effective_embedder,
-        &args.query,
-        args.limit,
-        bm25_weight,
-        vec_weight,
-        config.rrf_k,
-        config.recency_halflife,
-        &config.exclude_projects,
-        args.exact,
+        &parsed_query.text,
+        &options,
+        &filter,
     )?;
 
     if results.is_empty() {
@@ -132,6 +213,9 @@ fn cmd_search(args: cli::SearchArgs) -> Result<()> {
     }
 
     if args.no_tui {
+        if let Some(note) = results.iter().find_map(|r| r.relaxation.describe()) {
+            eprintln!("{} No session matched every term; {}", "Info:".blue(), note);
+        }
         print_results_plain(&results);
         return Ok(());
     }
@@ -152,12 +236,26 @@ fn cmd_search(args: cli::SearchArgs) -> Result<()> {
 
 fn cmd_index(args: cli::IndexArgs) -> Result<()> {
     let config = Config::load()?;
-    let db = Database::open(&config::db_path())?;
+    let db = open_database(&config, false)?;
+    let _index_lock = db
+        .lock_index(Duration::from_millis(config.index_lock_timeout_ms))?
+        .context("Another ccsearch process is updating the index; try again when it finishes")?;
 
-    let embedder = load_embedder_if_available();
+    let embedder = load_embedder_if_available(&config);
 
     let mut indexer = indexer::Indexer::new(&db, embedder, &config, args.verbose);
 
+    if args.embeddings_only {
+        let mut stats = indexer::IndexStats::default();
+        indexer.backfill_with_progress(&mut stats)?;
+        indexer.update_vector_indexes(&mut stats)?;
+        eprintln!(
+            "Done: {} chunks embedded, {} still missing vectors",
+            stats.chunks_embedded, stats.chunks_missing_vectors
+        );
+        return Ok(());
+    }
+
     eprintln!("{} Indexing Claude Code sessions...\n", "→".green());
 
     let _stats = indexer.index_all(args.force, args.days)?;
@@ -165,15 +263,26 @@ fn cmd_index(args: cli::IndexArgs) -> Result<()> {
     Ok(())
 }
 
-fn cmd_list(args: cli::ListArgs) -> Result<()> {
+fn cmd_watch(args: cli::WatchArgs) -> Result<()> {
     let config = Config::load()?;
-    let db = Database::open(&config::db_path())?;
+    let db = open_database(&config, false)?;
 
-    // JIT index
-    {
-        let mut indexer = indexer::Indexer::new(&db, None, &config, false);
-        if let Err(e) = indexer.jit_index() {
-            log::warn!("JIT index error: {}", e);
+    let embedder = load_embedder_if_available(&config);
+
+    watch::run(&db, embedder, &config, args.verbose)
+}
+
+fn cmd_list(args: cli::ListArgs) -> Result<()> {
+    let config = Config::load()?;
+    let db = open_database(&config, true)?;
+
+    // JIT index (a running watcher keeps it warm; skipped while another process indexes)
+    if !watch::is_watcher_alive() {
+        if let Some(_index_lock) = try_lock_index(&db) {
+            let mut indexer = indexer::Indexer::new(&db, None, &config, false);
+            if let Err(e) = indexer.jit_index() {
+                log::warn!("JIT index error: {}", e);
+            }
         }
     }
 
@@ -249,14 +358,94 @@ fn cmd_config() -> Result<()> {
     Ok(())
 }
 
+/// Opens the index, telling the user what will be rebuilt because chunking
+/// settings or the embedding model changed since it was built. A read-only
+/// handle can still write once it takes the index lock.
+fn open_database(config: &Config, read_only: bool) -> Result<Database> {
+    let db = if read_only {
+        Database::open_read_only(&config::db_path(), config)?
+    } else {
+        Database::open(&config::db_path(), config)?
+    };
+    let drift = db.index_drift();
+    if let Some((ref old, ref new)) = drift.chunking {
+        eprintln!(
+            "{} Chunking settings changed ({} → {}); every session will be re-chunked.",
+            "Note:".yellow(),
+            old,
+            new
+        );
+    }
+    if let Some((ref old, ref new)) = drift.embedding_model {
+        eprintln!(
+            "{} Embedding model changed ({} → {}); stored vectors will be dropped.",
+            "Note:".yellow(),
+            old,
+            new
+        );
+        eprintln!("  Run `ccsearch index` to re-embed. Semantic search is unavailable or incomplete until it finishes.\n");
+    }
+    Ok(db)
+}
+
+/// Takes the index lock without waiting. None if another process is indexing
+/// (or the lock can't be taken), in which case the caller reads what is committed.
+fn try_lock_index(db: &Database) -> Option<db::lock::IndexLock> {
+    match db.lock_index(Duration::ZERO) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            log::info!("Another ccsearch process is updating the index; using its last commit");
+            None
+        }
+        Err(e) => {
+            log::warn!("Could not lock the index: {}", e);
+            None
+        }
+    }
+}
+
+/// Starts `ccsearch index --embeddings-only` as a detached process if any chunks
+/// are missing vectors, so results show immediately and vectors catch up afterwards
+fn spawn_background_backfill(db: &Database) {
+    match db.count_pending_chunks() {
+        Ok(0) => return,
+        Ok(_) => {}
+        Err(e) => {
+            log::warn!("Could not count chunks missing vectors: {}", e);
+            return;
+        }
+    }
+
+    let spawned = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .args(["index", "--embeddings-only"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = spawned {
+        log::warn!("Could not start background embedding backfill: {}", e);
+    }
+}
+
 /// Attempts to load the embedding model, returns None if not available
-fn load_embedder_if_available() -> Option<indexer::embedder::Embedder> {
+fn load_embedder_if_available(config: &Config) -> Option<indexer::embedder::Embedder> {
     let base_dir = config::ccsearch_dir();
 
+    let spec = match model::ModelSpec::from_config(config) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("{} {:#}", "Warning:".yellow(), e);
+            eprintln!("  Falling back to BM25 keyword search only.\n");
+            return None;
+        }
+    };
+
     // Check if model is downloaded
-    if !model::is_model_downloaded(&base_dir) {
+    if !model::is_model_downloaded(&base_dir, &spec) {
         // Try to download
-        match model::ensure_model(&base_dir) {
+        match model::ensure_model(&base_dir, &spec) {
             Ok(_) => {}
             Err(e) => {
                 log::warn!("Could not download embedding model: {}", e);
@@ -270,8 +459,13 @@ fn load_embedder_if_available() -> Option<indexer::embedder::Embedder> {
         }
     }
 
-    let model_dir = model::model_dir(&base_dir);
-    match indexer::embedder::Embedder::new(&model_dir) {
+    let model_dir = model::model_dir(&base_dir, &spec);
+    match indexer::embedder::Embedder::new(
+        &model_dir,
+        &spec,
+        config.embedding_threads,
+        config.embedding_batch_size,
+    ) {
         Ok(e) => Some(e),
         Err(e) => {
             log::warn!("Failed to load embedder: {}", e);
@@ -317,6 +511,22 @@ fn print_results_plain(results: &[search::SearchResult]) {
                 .map(|b| format!("[{}]", b).magenta().to_string())
                 .unwrap_or_default()
         );
+        if let Some(ref msg) = result.matched_message {
+            let when = msg
+                .timestamp
+                .as_deref()
+                .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+                .map(|dt| format!(", {}", dt.format("%Y-%m-%d %H:%M")))
+                .unwrap_or_default();
+            println!(
+                "   {}",
+                format!(
+                    "message {} of {}{}, {}",
+                    msg.number, msg.total, when, msg.role
+                )
+                .dimmed()
+            );
+        }
         println!("   id: {}", result.session_id.dimmed());
         println!();
     }
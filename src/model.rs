@@ -1,52 +1,217 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-const MODEL_FILES: &[(&str, &str)] = &[
-    (
-        "model.onnx",
-        "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/onnx/model.onnx",
-    ),
-    (
-        "tokenizer.json",
-        "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/tokenizer.json",
-    ),
-    (
-        "config.json",
-        "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/config.json",
-    ),
+use crate::config::{Config, EmbeddingPooling};
+
+/// A model known by name, downloaded from Hugging Face on first use
+struct BuiltinModel {
+    name: &'static str,
+    repo: &'static str,
+    /// Paths within the repository; each is saved under its file name
+    files: &'static [&'static str],
+    size: &'static str,
+    pooling: EmbeddingPooling,
+    query_prefix: &'static str,
+    document_prefix: &'static str,
+}
+
+const BUILTIN_MODELS: &[BuiltinModel] = &[
+    BuiltinModel {
+        name: "all-MiniLM-L6-v2",
+        repo: "sentence-transformers/all-MiniLM-L6-v2",
+        files: &["onnx/model.onnx", "tokenizer.json", "config.json"],
+        size: "~80MB",
+        pooling: EmbeddingPooling::Mean,
+        query_prefix: "",
+        document_prefix: "",
+    },
+    BuiltinModel {
+        name: "bge-small-en-v1.5",
+        repo: "BAAI/bge-small-en-v1.5",
+        files: &["onnx/model.onnx", "tokenizer.json", "config.json"],
+        size: "~130MB",
+        pooling: EmbeddingPooling::Cls,
+        query_prefix: "Represent this sentence for searching relevant passages: ",
+        document_prefix: "",
+    },
+    BuiltinModel {
+        name: "multilingual-e5-small",
+        repo: "intfloat/multilingual-e5-small",
+        files: &["onnx/model.onnx", "tokenizer.json", "config.json"],
+        size: "~470MB",
+        pooling: EmbeddingPooling::Mean,
+        query_prefix: "query: ",
+        document_prefix: "passage: ",
+    },
 ];
 
+/// The embedding model selected in config: where its files come from and how
+/// its output is turned into one vector per text
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSpec {
+    pub name: String,
+    /// Download URLs, or bare file names expected to be in the model directory already
+    pub files: Vec<String>,
+    pub pooling: EmbeddingPooling,
+    pub query_prefix: String,
+    pub document_prefix: String,
+    /// Approximate download size, when known
+    pub size: Option<String>,
+}
+
+impl ModelSpec {
+    /// Resolves `embedding_model` and its overrides. A built-in name supplies
+    /// defaults for everything; any other name needs `embedding_model_files`.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let name = config.embedding_model.trim();
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Invalid embedding_model name {:?}", config.embedding_model);
+        }
+        let builtin = BUILTIN_MODELS.iter().find(|m| m.name == name);
+
+        let mut spec = match builtin {
+            Some(model) => ModelSpec {
+                name: name.to_string(),
+                files: model
+                    .files
+                    .iter()
+                    .map(|path| {
+                        format!(
+                            "https://huggingface.co/{}/resolve/main/{}",
+                            model.repo, path
+                        )
+                    })
+                    .collect(),
+                pooling: model.pooling,
+                query_prefix: model.query_prefix.to_string(),
+                document_prefix: model.document_prefix.to_string(),
+                size: Some(model.size.to_string()),
+            },
+            None if config.embedding_model_files.is_empty() => {
+                let known: Vec<&str> = BUILTIN_MODELS.iter().map(|m| m.name).collect();
+                bail!(
+                    "Unknown embedding_model {:?}: use one of {} or set embedding_model_files",
+                    name,
+                    known.join(", ")
+                );
+            }
+            None => ModelSpec {
+                name: name.to_string(),
+                files: Vec::new(),
+                pooling: EmbeddingPooling::default(),
+                query_prefix: String::new(),
+                document_prefix: String::new(),
+                size: None,
+            },
+        };
+
+        if !config.embedding_model_files.is_empty() {
+            spec.files = config.embedding_model_files.clone();
+            spec.size = None;
+        }
+        if let Some(pooling) = config.embedding_pooling {
+            spec.pooling = pooling;
+        }
+        if let Some(ref prefix) = config.embedding_query_prefix {
+            spec.query_prefix = prefix.clone();
+        }
+        if let Some(ref prefix) = config.embedding_document_prefix {
+            spec.document_prefix = prefix.clone();
+        }
+
+        spec.onnx_file()?;
+        if !spec.file_names().any(|f| f == "tokenizer.json") {
+            bail!(
+                "embedding_model_files for {:?} must include tokenizer.json",
+                spec.name
+            );
+        }
+        Ok(spec)
+    }
+
+    /// Identifies the vectors this model produces: two specs with the same id
+    /// embed text the same way (stored in index_meta)
+    pub fn id(&self) -> String {
+        format!(
+            "{} pooling={} query_prefix={:?} document_prefix={:?}",
+            self.name,
+            self.pooling.as_str(),
+            self.query_prefix,
+            self.document_prefix
+        )
+    }
+
+    /// Local file names, in the order the files are listed
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|f| file_name(f))
+    }
+
+    /// Local file name of the ONNX model (the first `.onnx` file)
+    pub fn onnx_file(&self) -> Result<&str> {
+        self.file_names()
+            .find(|f| f.ends_with(".onnx"))
+            .with_context(|| format!("No .onnx file listed for embedding model {:?}", self.name))
+    }
+}
+
+/// File name a model file is saved under: the last path segment of a URL, or the entry itself
+fn file_name(entry: &str) -> &str {
+    if !entry.contains("://") {
+        return entry;
+    }
+    let path = entry.split(['?', '#']).next().unwrap_or(entry);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
 /// Returns the model directory path
-pub fn model_dir(base_dir: &Path) -> PathBuf {
-    base_dir.join("models").join("all-MiniLM-L6-v2")
+pub fn model_dir(base_dir: &Path, spec: &ModelSpec) -> PathBuf {
+    base_dir.join("models").join(&spec.name)
 }
 
 /// Checks if the model is already downloaded
-pub fn is_model_downloaded(base_dir: &Path) -> bool {
-    let dir = model_dir(base_dir);
-    MODEL_FILES
-        .iter()
-        .all(|(filename, _)| dir.join(filename).exists())
+pub fn is_model_downloaded(base_dir: &Path, spec: &ModelSpec) -> bool {
+    let dir = model_dir(base_dir, spec);
+    spec.file_names()
+        .all(|filename| dir.join(filename).exists())
 }
 
 /// Downloads the model files if not already present
-pub fn ensure_model(base_dir: &Path) -> Result<PathBuf> {
-    let dir = model_dir(base_dir);
+pub fn ensure_model(base_dir: &Path, spec: &ModelSpec) -> Result<PathBuf> {
+    let dir = model_dir(base_dir, spec);
 
-    if is_model_downloaded(base_dir) {
+    if is_model_downloaded(base_dir, spec) {
         log::debug!("Model already downloaded at {:?}", dir);
         return Ok(dir);
     }
 
-    eprintln!("Downloading embedding model (all-MiniLM-L6-v2)...");
-    eprintln!("This is a one-time download (~80MB).\n");
+    // Bare file names can't be fetched; the user has to put them in place
+    let missing: Vec<&str> = spec
+        .files
+        .iter()
+        .filter(|f| !f.contains("://") && !dir.join(f.as_str()).exists())
+        .map(|f| f.as_str())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Model file(s) {} not found in {:?}",
+            missing.join(", "),
+            dir
+        );
+    }
+
+    eprintln!("Downloading embedding model ({})...", spec.name);
+    match spec.size {
+        Some(ref size) => eprintln!("This is a one-time download ({}).\n", size),
+        None => eprintln!("This is a one-time download.\n"),
+    }
 
     fs::create_dir_all(&dir).context("Failed to create model directory")?;
 
-    for (filename, url) in MODEL_FILES {
+    for url in &spec.files {
+        let filename = file_name(url);
         let dest = dir.join(filename);
         if dest.exists() {
             log::debug!("{} already exists, skipping", filename);
@@ -113,3 +278,67 @@ fn download_file(url: &str, dest: &Path, display_name: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_model_spec() {
+        let spec = ModelSpec::from_config(&Config::default()).unwrap();
+        assert_eq!(spec.name, "all-MiniLM-L6-v2");
+        assert_eq!(spec.pooling, EmbeddingPooling::Mean);
+        assert_eq!(spec.onnx_file().unwrap(), "model.onnx");
+        assert_eq!(
+            spec.file_names().collect::<Vec<_>>(),
+            vec!["model.onnx", "tokenizer.json", "config.json"]
+        );
+
+        let config = Config {
+            embedding_model: "multilingual-e5-small".to_string(),
+            ..Config::default()
+        };
+        let spec = ModelSpec::from_config(&config).unwrap();
+        assert_eq!(spec.query_prefix, "query: ");
+        assert_eq!(spec.document_prefix, "passage: ");
+    }
+
+    #[test]
+    fn test_custom_model_spec() {
+        let config = Config {
+            embedding_model: "my-model".to_string(),
+            ..Config::default()
+        };
+        assert!(ModelSpec::from_config(&config).is_err());
+
+        let config = Config {
+            embedding_model: "my-model".to_string(),
+            embedding_model_files: vec![
+                "https://example.com/m/resolve/main/onnx/model_quantized.onnx?download=1"
+                    .to_string(),
+                "tokenizer.json".to_string(),
+            ],
+            embedding_pooling: Some(EmbeddingPooling::Cls),
+            embedding_query_prefix: Some("search_query: ".to_string()),
+            ..Config::default()
+        };
+        let spec = ModelSpec::from_config(&config).unwrap();
+        assert_eq!(spec.onnx_file().unwrap(), "model_quantized.onnx");
+        assert_eq!(spec.pooling, EmbeddingPooling::Cls);
+        assert_eq!(spec.query_prefix, "search_query: ");
+        assert_eq!(spec.document_prefix, "");
+
+        let config = Config {
+            embedding_model: "my-model".to_string(),
+            embedding_model_files: vec!["model.onnx".to_string()],
+            ..Config::default()
+        };
+        assert!(ModelSpec::from_config(&config).is_err());
+
+        let config = Config {
+            embedding_model: "../elsewhere".to_string(),
+            ..Config::default()
+        };
+        assert!(ModelSpec::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::db::Database;
+
+/// index_meta key holding how many embeddings the centroids were trained on
+pub const ANN_TRAINED_KEY: &str = "ann_trained_vectors";
+
+/// Sample vectors drawn per list when training centroids
+const TRAINING_SAMPLES_PER_LIST: usize = 64;
+
+/// k-means passes over the training sample
+const TRAINING_ITERATIONS: usize = 10;
+
+/// Upper bound on the number of lists, however large the corpus
+const MAX_LISTS: usize = 4096;
+
+/// Embeddings assigned to lists per transaction
+const ASSIGN_BATCH: usize = 1024;
+
+/// Brings the IVF (inverted file) index over chunk embeddings up to date and
+/// returns its number of lists (0 = no index, vector search scans everything).
+///
+/// Below `ann_min_vectors` embeddings the index is dropped. Centroids are
+/// (re)trained when missing or when the corpus has doubled or halved since the
+/// last training; otherwise only embeddings added since the last run are assigned
+/// to their nearest list. Deleted embeddings need no upkeep.
+pub fn maintain(db: &Database, config: &Config) -> Result<usize> {
+    let count = db.count_chunk_embeddings()?;
+    let mut centroids = db.get_ann_centroids()?;
+
+    if config.ann_probes == 0 || count == 0 || count < config.ann_min_vectors {
+        if !centroids.is_empty() {
+            db.replace_ann_centroids(&[])?;
+            db.set_meta(ANN_TRAINED_KEY, "0")?;
+        }
+        return Ok(0);
+    }
+
+    let trained: usize = db
+        .get_meta(ANN_TRAINED_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if centroids.is_empty() || count >= trained * 2 || count * 2 <= trained {
+        let lists = list_count(count);
+        let sample = db.sample_chunk_embeddings(lists * TRAINING_SAMPLES_PER_LIST)?;
+        centroids = train(&sample, lists, TRAINING_ITERATIONS);
+        db.replace_ann_centroids(&centroids)?;
+        db.set_meta(ANN_TRAINED_KEY, &count.to_string())?;
+        log::info!(
+            "Trained vector index: {} lists over {} embeddings",
+            centroids.len(),
+            count
+        );
+    }
+
+    loop {
+        let batch = db.get_unassigned_chunk_embeddings(ASSIGN_BATCH)?;
+        if batch.is_empty() {
+            break;
+        }
+        let assignments: Vec<(i64, i64)> = batch
+            .iter()
+            .map(|(chunk_id, embedding)| (*chunk_id, nearest(embedding, &centroids) as i64))
+            .collect();
+        db.set_chunk_lists(&assignments)?;
+    }
+
+    Ok(centroids.len())
+}
+
+/// Number of lists for a corpus: about √n, the usual IVF balance between
+/// centroid comparisons and list scan length
+pub fn list_count(vectors: usize) -> usize {
+    ((vectors as f64).sqrt().round() as usize).clamp(1, MAX_LISTS)
+}
+
+/// Trains `lists` unit-length centroids with spherical k-means.
+/// Seeds are picked farthest-first, so the result is deterministic for a given sample.
+pub fn train(sample: &[Vec<f32>], lists: usize, iterations: usize) -> Vec<Vec<f32>> {
+    let lists = lists.min(sample.len());
+    if lists == 0 {
+        return Vec::new();
+    }
+    let dim = sample[0].len();
+
+    // Each seed is the sample vector least similar to every seed so far
+    let mut centroids = vec![normalized(&sample[0])];
+    let mut closest: Vec<f32> = sample.iter().map(|v| dot(v, &centroids[0])).collect();
+    while centroids.len() < lists {
+        let (next, _) = closest
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("sample is not empty");
+        let seed = normalized(&sample[next]);
+        for (similarity, vector) in closest.iter_mut().zip(sample) {
+            *similarity = similarity.max(dot(vector, &seed));
+        }
+        centroids.push(seed);
+    }
+
+    for _ in 0..iterations {
+        let mut sums = vec![vec![0.0f32; dim]; lists];
+        let mut counts = vec![0usize; lists];
+        for vector in sample {
+            let list = nearest(vector, &centroids);
+            for (sum, x) in sums[list].iter_mut().zip(vector) {
+                *sum += x;
+            }
+            counts[list] += 1;
+        }
+
+        // A list that attracted nothing keeps its previous centroid
+        for ((centroid, sum), count) in centroids.iter_mut().zip(&sums).zip(&counts) {
+            if *count > 0 {
+                *centroid = normalized(sum);
+            }
+        }
+    }
+
+    centroids
+}
+
+/// Index of the centroid closest to `vector`
+pub fn nearest(vector: &[f32], centroids: &[Vec<f32>]) -> usize {
+    let mut best = (0, f32::MIN);
+    for (i, centroid) in centroids.iter().enumerate() {
+        let score = dot(vector, centroid);
+        if score > best.1 {
+            best = (i, score);
+        }
+    }
+    best.0
+}
+
+/// IDs of the `probes` lists whose centroids are closest to `query`, closest first
+pub fn nearest_lists(query: &[f32], centroids: &[Vec<f32>], probes: usize) -> Vec<i64> {
+    let mut scored: Vec<(i64, f32)> = centroids
+        .iter()
+        .enumerate()
+        .map(|(i, centroid)| (i as i64, dot(query, centroid)))
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(probes);
+    scored.into_iter().map(|(id, _)| id).collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter().map(|x| x / norm).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unit vector along `axis`, nudged by `jitter` on the next axis
+    fn point(axis: usize, jitter: f32) -> Vec<f32> {
+        let mut v = vec![0.0; 4];
+        v[axis] = 1.0;
+        v[(axis + 1) % 4] = jitter;
+        normalized(&v)
+    }
+
+    #[test]
+    fn test_train_finds_clusters() {
+        let sample: Vec<Vec<f32>> = (0..40)
+            .map(|i| point(i % 4, (i / 4) as f32 * 0.01))
+            .collect();
+        let centroids = train(&sample, 4, TRAINING_ITERATIONS);
+        assert_eq!(centroids.len(), 4);
+
+        // Every cluster ends up with its own centroid
+        let mut lists: Vec<usize> = (0..4)
+            .map(|axis| nearest(&point(axis, 0.0), &centroids))
+            .collect();
+        lists.sort();
+        lists.dedup();
+        assert_eq!(lists.len(), 4);
+
+        for centroid in &centroids {
+            assert!((dot(centroid, centroid) - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_train_small_sample() {
+        assert!(train(&[], 8, 3).is_empty());
+        assert_eq!(train(&[point(0, 0.0), point(1, 0.0)], 8, 3).len(), 2);
+    }
+
+    #[test]
+    fn test_nearest_lists_ordered_by_similarity() {
+        let centroids = vec![point(0, 0.0), point(1, 0.0), point(2, 0.0)];
+        let query = normalized(&[0.2, 0.0, 1.0, 0.0]);
+        assert_eq!(nearest_lists(&query, &centroids, 2), vec![2, 0]);
+        assert_eq!(nearest_lists(&query, &centroids, 10).len(), 3);
+    }
+
+    #[test]
+    fn test_list_count() {
+        assert_eq!(list_count(1), 1);
+        assert_eq!(list_count(10_000), 100);
+        assert_eq!(list_count(usize::MAX / 2), MAX_LISTS);
+    }
+}
@@ -1,10 +1,65 @@
 use anyhow::Result;
+use std::collections::HashSet;
 
+use super::filter::SearchFilter;
+use super::fts_query;
 use crate::db::queries::FtsResult;
 use crate::db::Database;
 
-/// Performs BM25 keyword search using SQLite FTS5
-pub fn search(db: &Database, query: &str, limit: usize, exact: bool) -> Result<Vec<FtsResult>> {
+/// Upper bound on sessions fetched per operand when measuring term rarity
+const OPERAND_POOL: usize = 10_000;
+
+/// How far a keyword query had to be loosened to find anything
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+#[serde(tag = "level", rename_all = "snake_case")]
+pub enum Relaxation {
+    /// Every term matched
+    #[default]
+    AllTerms,
+    /// The rarest terms were dropped
+    DroppedTerms { dropped: Vec<String> },
+    /// Sessions matching any term, ranked by how many they match
+    AnyTerm,
+}
+
+impl Relaxation {
+    pub fn is_partial(&self) -> bool {
+        *self != Relaxation::AllTerms
+    }
+
+    /// Short note for output, e.g. "showing partial matches (ignoring: redis)"
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Relaxation::AllTerms => None,
+            Relaxation::DroppedTerms { dropped } => Some(format!(
+                "showing partial matches (ignoring: {})",
+                dropped.join(", ")
+            )),
+            Relaxation::AnyTerm => Some("showing partial matches (any term)".to_string()),
+        }
+    }
+}
+
+/// BM25 results and the FTS5 expression that produced them
+#[derive(Debug, Default)]
+pub struct Bm25Results {
+    pub results: Vec<FtsResult>,
+    pub relaxation: Relaxation,
+    /// The (possibly relaxed) FTS5 query, for picking the best chunk to preview
+    pub fts_query: String,
+}
+
+/// Performs BM25 keyword search using SQLite FTS5.
+/// When every term together matches nothing, the query is relaxed in stages:
+/// first the rarest terms are dropped, then any term may match, with sessions
+/// matching more terms ranked first (coordination-level ranking).
+pub fn search(
+    db: &Database,
+    query: &str,
+    limit: usize,
+    exact: bool,
+    filter: &SearchFilter,
+) -> Result<Bm25Results> {
     let sanitized = if exact {
         build_fts5_phrase(query)
     } else {
@@ -12,44 +67,80 @@ pub fn search(db: &Database, query: &str, limit: usize, exact: bool) -> Result<V
     };
 
     if sanitized.is_empty() {
-        return Ok(Vec::new());
+        return Ok(Bm25Results::default());
     }
 
-    db.fts_search(&sanitized, limit)
-}
+    let results = db.fts_search_filtered(&sanitized, limit, filter)?;
+    let strict = Bm25Results {
+        results,
+        relaxation: Relaxation::AllTerms,
+        fts_query: sanitized,
+    };
+    if !strict.results.is_empty() || exact {
+        return Ok(strict);
+    }
+    let Some(conjunction) = fts_query::conjunction(query) else {
+        return Ok(strict);
+    };
 
-/// Builds a valid FTS5 MATCH query from a natural language query string.
-/// Splits into words, each gets exact+prefix matching, AND-ed together.
-pub fn build_fts5_query(query: &str) -> String {
-    let words: Vec<&str> = query
-        .split(|c: char| !c.is_alphanumeric() && c != '*' && c != '"')
-        .filter(|w| !w.is_empty())
-        .collect();
+    // Sessions matching each operand on its own
+    let mut hits: Vec<HashSet<String>> = Vec::with_capacity(conjunction.operand_count());
+    for i in 0..conjunction.operand_count() {
+        let sessions = db
+            .fts_search_filtered(&conjunction.operand(i), OPERAND_POOL, filter)?
+            .into_iter()
+            .map(|r| r.session_id)
+            .collect();
+        hits.push(sessions);
+    }
 
-    if words.is_empty() {
-        return String::new();
+    // Drop the rarest operands one at a time, keeping at least two
+    let mut by_rarity: Vec<usize> = (0..conjunction.operand_count()).collect();
+    by_rarity.sort_by_key(|&i| hits[i].len());
+    for dropped in 1..conjunction.operand_count().saturating_sub(1) {
+        let skip = &by_rarity[..dropped];
+        let fts_query = conjunction.all_except(skip);
+        let results = db.fts_search_filtered(&fts_query, limit, filter)?;
+        if !results.is_empty() {
+            let mut skip = skip.to_vec();
+            skip.sort_unstable();
+            return Ok(Bm25Results {
+                results,
+                relaxation: Relaxation::DroppedTerms {
+                    dropped: skip.iter().map(|&i| conjunction.label(i)).collect(),
+                },
+                fts_query,
+            });
+        }
     }
 
-    words
-        .iter()
-        .map(|w| {
-            if w.len() >= 3 && !w.ends_with('*') && !w.contains('"') {
-                format!("(\"{}\" OR {}*)", w, w)
-            } else {
-                format!("\"{}\"", w)
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" AND ")
+    // Any operand, ranked by how many operands each session matches, then by BM25
+    let fts_query = conjunction.any();
+    let mut results = db.fts_search_filtered(&fts_query, limit * 4, filter)?;
+    if results.is_empty() {
+        return Ok(strict);
+    }
+    let matched = |session_id: &str| hits.iter().filter(|h| h.contains(session_id)).count();
+    results.sort_by_key(|r| std::cmp::Reverse(matched(&r.session_id)));
+    results.truncate(limit);
+
+    Ok(Bm25Results {
+        results,
+        relaxation: Relaxation::AnyTerm,
+        fts_query,
+    })
+}
+
+/// Builds a valid FTS5 MATCH query from a user query string.
+/// Loose words get exact+prefix matching and are AND-ed together; quoted phrases,
+/// OR, NOT/-term, NEAR and parentheses are supported (see `fts_query`).
+pub fn build_fts5_query(query: &str) -> String {
+    fts_query::compile(query)
 }
 
 /// Builds an exact phrase FTS5 query — matches the literal token sequence.
-fn build_fts5_phrase(query: &str) -> String {
-    let trimmed = query.trim();
-    if trimmed.is_empty() {
-        return String::new();
-    }
-    format!("\"{}\"", trimmed)
+pub fn build_fts5_phrase(query: &str) -> String {
+    fts_query::compile_phrase(query)
 }
 
 #[cfg(test)]
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+
+/// Restricts which sessions a search may return.
+/// Applied inside the FTS and vector SQL queries so that filtered-out sessions
+/// never occupy slots in the candidate pool.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// Only sessions that invoked this tool (case-insensitive, e.g. "Edit", "Bash")
+    pub tool: Option<String>,
+    /// Only sessions with a tool call on a file path containing this substring
+    pub file: Option<String>,
+    /// Only sessions that ran a shell command containing this substring
+    pub command: Option<String>,
+    /// Only sessions last active at or after this time
+    pub after: Option<DateTime<Utc>>,
+    /// Only sessions last active before this time
+    pub before: Option<DateTime<Utc>>,
+    /// Only sessions whose project path matches (substring, or glob if it contains `*`, `?` or `[`)
+    pub project: Option<String>,
+    /// Only sessions on a matching git branch (substring, or glob like `project`)
+    pub branch: Option<String>,
+    /// Only main sessions or only agent (subagent) sessions
+    pub kind: Option<SessionKind>,
+    /// Only sessions whose ID starts with this prefix
+    pub session_id: Option<String>,
+    /// Only sessions with a message by this role ("user" or "assistant")
+    /// that matches `role_text` on its own (search syntax, see `fts_query::message_condition`)
+    pub role: Option<String>,
+    pub role_text: String,
+    /// Drop sessions whose project path contains any of these strings (case-insensitive)
+    pub exclude_projects: Vec<String>,
+    /// Drop sessions whose conversation mentions any of these terms
+    pub exclude_terms: Vec<String>,
+}
+
+/// Whether a session was started by the user or spawned as a subagent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SessionKind {
+    /// Sessions started by the user
+    Main,
+    /// Subagent sessions (agent-*.jsonl)
+    Agent,
+}
+
+impl SearchFilter {
+    /// Restricts results to sessions active within the last `days` days (0 = no limit)
+    pub fn with_days(mut self, days: u32) -> Self {
+        if days > 0 {
+            self.after = Some(Utc::now() - chrono::Duration::days(days as i64));
+        }
+        self
+    }
+}
+
+/// True if a project/branch pattern should be matched with GLOB rather than as a substring
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
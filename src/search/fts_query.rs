@@ -0,0 +1,794 @@
+//! Compiles user queries into FTS5 MATCH expressions.
+//!
+//! Supported syntax (operators are case-sensitive, like FTS5 itself):
+//! - loose words, AND-ed together: `token refresh`
+//! - quoted phrases: `"dark mode" toggle`
+//! - `OR` between terms or groups: `sqlite OR postgres`
+//! - negation with `-term` or `NOT term`: `auth -oauth`, `auth NOT oauth`
+//! - proximity: `NEAR(token expiry, 5)`
+//! - parentheses: `(login OR signin) error`
+//!
+//! `message_condition` evaluates the same syntax against a single text column
+//! with SQL `LIKE`, for conditions on individual messages (which have no FTS index).
+//!
+//! Every word reaches SQLite as a quoted string, so user input can never be
+//! read as FTS5 syntax. Malformed input degrades instead of failing: unbalanced
+//! parentheses are closed or ignored, dangling operators are dropped, and a
+//! group made only of negations (which FTS5 can't express) is dropped too.
+
+use crate::indexer::tokenizer;
+
+/// Words of at least this many characters also match as a prefix
+const MIN_PREFIX_LEN: usize = 3;
+
+/// NEAR distance when none is given (the FTS5 default)
+const DEFAULT_NEAR_DISTANCE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Comma,
+    Phrase(String),
+    Word(String),
+}
+
+/// Parsed query tree
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// A single word; `prefix` is set when the user wrote `word*`
+    Term {
+        word: String,
+        prefix: bool,
+    },
+    Phrase(String),
+    Near {
+        phrases: Vec<String>,
+        distance: usize,
+    },
+    And {
+        include: Vec<Node>,
+        exclude: Vec<Node>,
+    },
+    Or(Vec<Node>),
+}
+
+/// Compiles a query into an FTS5 MATCH expression.
+/// Returns an empty string if nothing searchable is left.
+pub fn compile(query: &str) -> String {
+    parse(query).map(|n| emit(&n)).unwrap_or_default()
+}
+
+/// The top-level AND-ed operands of a query, which can be recombined when the
+/// full query is too strict (see `bm25::search`)
+#[derive(Debug, Clone)]
+pub struct Conjunction {
+    operands: Vec<Node>,
+    exclude: Vec<Node>,
+}
+
+impl Conjunction {
+    pub fn operand_count(&self) -> usize {
+        self.operands.len()
+    }
+
+    /// FTS5 expression matching a single operand
+    pub fn operand(&self, index: usize) -> String {
+        emit(&self.operands[index])
+    }
+
+    /// How an operand is shown to the user (e.g. in "ignoring: redis")
+    pub fn label(&self, index: usize) -> String {
+        match &self.operands[index] {
+            Node::Term { word, .. } => word.clone(),
+            Node::Phrase(phrase) => format!("\"{}\"", phrase),
+            node => emit(node),
+        }
+    }
+
+    /// FTS5 expression requiring every operand except those in `skip`
+    pub fn all_except(&self, skip: &[usize]) -> String {
+        let include: Vec<Node> = self
+            .operands
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !skip.contains(i))
+            .map(|(_, n)| n.clone())
+            .collect();
+        emit(&and(include, self.exclude.clone()))
+    }
+
+    /// FTS5 expression matching any operand
+    pub fn any(&self) -> String {
+        emit(&and(
+            vec![Node::Or(self.operands.clone())],
+            self.exclude.clone(),
+        ))
+    }
+}
+
+/// Splits a query into its top-level AND-ed operands.
+/// None if it has fewer than two (nothing to relax).
+pub fn conjunction(query: &str) -> Option<Conjunction> {
+    match parse(query)? {
+        Node::And { include, exclude } if include.len() >= 2 => Some(Conjunction {
+            operands: include,
+            exclude,
+        }),
+        _ => None,
+    }
+}
+
+fn parse(query: &str) -> Option<Node> {
+    let tokens = lex(query);
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut node = parser.parse_or();
+    // Stray closing parentheses end a group early; keep parsing what follows
+    while parser.pos < parser.tokens.len() {
+        parser.pos += 1;
+        let rest = parser.parse_or();
+        node = match (node, rest) {
+            (
+                Some(Node::And {
+                    mut include,
+                    exclude,
+                }),
+                Some(b),
+            ) => {
+                include.push(b);
+                Some(and(include, exclude))
+            }
+            (Some(a), Some(b)) => Some(and(vec![a, b], Vec::new())),
+            (a, b) => a.or(b),
+        };
+    }
+    node
+}
+
+/// Compiles a query into a single literal FTS5 phrase (for `--exact`)
+pub fn compile_phrase(query: &str) -> String {
+    // FTS5 reads a NUL as the end of the string, so control characters become spaces
+    let cleaned: String = query
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let trimmed = cleaned.trim();
+    if !trimmed.chars().any(|c| c.is_alphanumeric()) {
+        return String::new();
+    }
+    quote(trimmed)
+}
+
+/// Compiles a query into a MATCH expression for the trigram index: each word of
+/// three or more characters matches through any of its trigrams, so a misspelled
+/// word still matches text sharing some of its trigrams. Words are AND-ed and BM25
+/// ranks text sharing more (and rarer) trigrams higher; `shares_trigrams` then
+/// drops text that shares too few.
+pub fn compile_trigrams(query: &str) -> String {
+    word_trigrams(query)
+        .iter()
+        .map(|trigrams| {
+            let quoted: Vec<String> = trigrams.iter().map(|t| quote(t)).collect();
+            format!("({})", quoted.join(" OR "))
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Share of a word's trigrams that text must contain to count as a fuzzy match
+/// (a one-letter typo in a 6+ letter word keeps at least half)
+const MIN_TRIGRAM_SHARE: f64 = 0.5;
+
+/// The lowercase trigrams of each query word of three or more characters
+pub fn word_trigrams(query: &str) -> Vec<Vec<String>> {
+    positive_terms(query)
+        .iter()
+        .filter_map(|word| {
+            let chars: Vec<char> = word.to_lowercase().chars().collect();
+            if chars.len() < 3 {
+                return None;
+            }
+            let mut trigrams: Vec<String> = chars.windows(3).map(|w| w.iter().collect()).collect();
+            trigrams.dedup();
+            Some(trigrams)
+        })
+        .collect()
+}
+
+/// True if `text` contains at least `MIN_TRIGRAM_SHARE` of every word's trigrams
+/// (see `word_trigrams`), i.e. it is a plausible fuzzy match for the whole query
+pub fn shares_trigrams(words: &[Vec<String>], text: &str) -> bool {
+    let text = text.to_lowercase();
+    words.iter().all(|trigrams| {
+        let shared = trigrams
+            .iter()
+            .filter(|t| text.contains(t.as_str()))
+            .count();
+        shared as f64 >= MIN_TRIGRAM_SHARE * trigrams.len() as f64
+    })
+}
+
+/// Compiles a query into an SQL condition on `column` that a single row must
+/// satisfy, with `?N` parameters numbered from `first_param`. Operators keep
+/// their meaning: `a OR b` matches a row containing either word, `-c` a row
+/// without it. Words match as case-insensitive substrings, phrases (and NEAR
+/// groups) as their words in order. Returns the SQL and one LIKE pattern per
+/// parameter, or None if nothing searchable is left.
+pub fn message_condition(
+    query: &str,
+    column: &str,
+    first_param: usize,
+) -> Option<(String, Vec<String>)> {
+    let node = parse(query)?;
+    let mut patterns = Vec::new();
+    let sql = emit_like(&node, column, first_param, &mut patterns);
+    Some((sql, patterns))
+}
+
+/// The words a matching document must (or may) contain, ignoring operators and
+/// negated terms; used to find and highlight the relevant part of a result
+pub fn positive_terms(query: &str) -> Vec<String> {
+    let tokens = lex(query);
+    let mut parser = Parser { tokens, pos: 0 };
+    let mut terms = Vec::new();
+    while parser.pos < parser.tokens.len() {
+        if let Some(node) = parser.parse_or() {
+            collect_terms(&node, &mut terms);
+        }
+        parser.pos += 1;
+    }
+    terms
+}
+
+fn collect_terms(node: &Node, terms: &mut Vec<String>) {
+    match node {
+        Node::Term { word, .. } => terms.push(word.clone()),
+        Node::Phrase(phrase) => terms.extend(words(phrase).map(str::to_string)),
+        Node::Near { phrases, .. } => {
+            for phrase in phrases {
+                terms.extend(words(phrase).map(str::to_string));
+            }
+        }
+        Node::And { include, .. } => include.iter().for_each(|n| collect_terms(n, terms)),
+        Node::Or(nodes) => nodes.iter().for_each(|n| collect_terms(n, terms)),
+    }
+}
+
+/// Alphanumeric runs of a string (what the FTS5 tokenizer indexes)
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+}
+
+/// Splits a query into parentheses, commas, quoted phrases and words.
+/// An unterminated quote runs to the end of the input.
+fn lex(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    phrase.push(c);
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | ',' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                // `-"phrase"` negates the phrase that follows
+                if word == "-" && chars.peek() == Some(&'"') {
+                    tokens.push(Token::Word("NOT".to_string()));
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    /// or := and ("OR" and)*
+    fn parse_or(&mut self) -> Option<Node> {
+        let mut branches = Vec::new();
+        branches.extend(self.parse_and());
+        while self.peek_word("OR") {
+            self.pos += 1;
+            branches.extend(self.parse_and());
+        }
+        match branches.len() {
+            0 => None,
+            1 => branches.pop(),
+            _ => Some(Node::Or(branches)),
+        }
+    }
+
+    /// and := unary (["AND"] unary)*, stopping at OR, `)` or the end
+    fn parse_and(&mut self) -> Option<Node> {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+
+        loop {
+            match self.peek() {
+                None | Some(Token::RParen) => break,
+                Some(Token::Word(w)) if w == "OR" => break,
+                Some(Token::Word(w)) if w == "AND" => self.pos += 1,
+                Some(Token::Comma) => self.pos += 1,
+                _ => {
+                    let (negated, nodes) = self.parse_unary();
+                    if negated {
+                        exclude.extend(nodes);
+                    } else {
+                        include.extend(nodes);
+                    }
+                }
+            }
+        }
+
+        // FTS5 can't match "everything except", so a purely negative group is dropped
+        if include.is_empty() {
+            return None;
+        }
+        if include.len() == 1 && exclude.is_empty() {
+            return include.pop();
+        }
+        Some(and(include, exclude))
+    }
+
+    /// unary := ("NOT" | "-") unary | primary
+    /// Returns whether the operand is negated, and its nodes (a loose word may
+    /// yield several terms, e.g. `auth-bug`)
+    fn parse_unary(&mut self) -> (bool, Vec<Node>) {
+        if self.peek_word("NOT") {
+            self.pos += 1;
+            let (negated, nodes) = self.parse_unary();
+            return (!negated, nodes);
+        }
+        if let Some(Token::Word(w)) = self.peek() {
+            if let Some(rest) = w.strip_prefix('-') {
+                if rest.chars().any(|c| c.is_alphanumeric()) {
+                    let nodes = word_terms(rest);
+                    self.pos += 1;
+                    return (true, nodes);
+                }
+            }
+        }
+        (false, self.parse_primary())
+    }
+
+    fn parse_primary(&mut self) -> Vec<Node> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Vec::new();
+        };
+        self.pos += 1;
+
+        match token {
+            Token::LParen => {
+                let node = self.parse_or();
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                }
+                node.into_iter().collect()
+            }
+            Token::Phrase(phrase) => phrase_node(&phrase).into_iter().collect(),
+            Token::Word(w) if w == "NEAR" && self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                self.parse_near().into_iter().collect()
+            }
+            Token::Word(w) => word_terms(&w),
+            Token::RParen | Token::Comma => Vec::new(),
+        }
+    }
+
+    /// Parses the inside of `NEAR(...)`, after the opening parenthesis
+    fn parse_near(&mut self) -> Option<Node> {
+        let mut phrases = Vec::new();
+        let mut distance = DEFAULT_NEAR_DISTANCE;
+
+        while let Some(token) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+            match token {
+                Token::RParen => break,
+                Token::Comma => {
+                    if let Some(Token::Word(w)) = self.peek() {
+                        if let Ok(n) = w.parse::<usize>() {
+                            distance = n;
+                            self.pos += 1;
+                        }
+                    }
+                }
+                Token::Phrase(phrase) => phrases.extend(phrase_text(&phrase)),
+                Token::Word(w) => phrases.extend(words(&w).map(str::to_string)),
+                // Nested groups aren't allowed inside NEAR; read their words as phrases
+                Token::LParen => {}
+            }
+        }
+
+        match phrases.len() {
+            0 => None,
+            1 => phrases.pop().map(Node::Phrase),
+            _ => Some(Node::Near { phrases, distance }),
+        }
+    }
+}
+
+fn and(include: Vec<Node>, exclude: Vec<Node>) -> Node {
+    Node::And { include, exclude }
+}
+
+/// A loose word becomes one term per alphanumeric run; a trailing `*` makes the
+/// last run an explicit prefix
+fn word_terms(word: &str) -> Vec<Node> {
+    let prefix = word.ends_with('*');
+    let runs: Vec<&str> = words(word).collect();
+    let last = runs.len().saturating_sub(1);
+    runs.into_iter()
+        .enumerate()
+        .map(|(i, run)| Node::Term {
+            word: run.to_string(),
+            prefix: prefix && i == last,
+        })
+        .collect()
+}
+
+/// Normalizes a phrase to its words; None if it has nothing searchable
+fn phrase_text(phrase: &str) -> Option<String> {
+    let text = words(phrase).collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn phrase_node(phrase: &str) -> Option<Node> {
+    phrase_text(phrase).map(Node::Phrase)
+}
+
+/// Quotes a string as an FTS5 string literal
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn emit(node: &Node) -> String {
+    match node {
+        Node::Term { word, prefix } => {
+            let mut alternatives = Vec::new();
+            if !prefix {
+                alternatives.push(quote(word));
+            }
+            if *prefix || word.chars().count() >= MIN_PREFIX_LEN {
+                alternatives.push(format!("{}*", quote(word)));
+            }
+            // camelCase identifiers also match their indexed parts (see `tokenizer::code_subtokens`)
+            let parts = tokenizer::split_camel_case(word);
+            if parts.len() > 1 {
+                let phrase = quote(&parts.join(" ").to_lowercase());
+                alternatives.push(if *prefix {
+                    format!("{}*", phrase)
+                } else {
+                    phrase
+                });
+            }
+            // CJK words match inside longer runs through their bigrams (when indexed)
+            if word.chars().count() >= 2 && word.chars().all(tokenizer::is_cjk) {
+                alternatives.push(quote(&tokenizer::cjk_bigrams(word)));
+            }
+            match alternatives.len() {
+                1 => alternatives.remove(0),
+                _ => format!("({})", alternatives.join(" OR ")),
+            }
+        }
+        Node::Phrase(phrase) => quote(phrase),
+        Node::Near { phrases, distance } => format!(
+            "NEAR({}, {})",
+            phrases
+                .iter()
+                .map(|p| quote(p))
+                .collect::<Vec<_>>()
+                .join(" "),
+            distance
+        ),
+        Node::And { include, exclude } => {
+            let mut out = include
+                .iter()
+                .map(emit_operand)
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            if include.len() > 1 && !exclude.is_empty() {
+                out = format!("({})", out);
+            }
+            for node in exclude {
+                out.push_str(" NOT ");
+                out.push_str(&emit_operand(node));
+            }
+            out
+        }
+        Node::Or(nodes) => nodes
+            .iter()
+            .map(emit_operand)
+            .collect::<Vec<_>>()
+            .join(" OR "),
+    }
+}
+
+/// Emits a node as an operand of AND/OR/NOT, parenthesizing compound expressions
+fn emit_operand(node: &Node) -> String {
+    match node {
+        Node::And { .. } | Node::Or(_) => format!("({})", emit(node)),
+        _ => emit(node),
+    }
+}
+
+fn emit_like(node: &Node, column: &str, first_param: usize, patterns: &mut Vec<String>) -> String {
+    match node {
+        Node::Term { word, .. } => like(column, first_param, patterns, word),
+        Node::Phrase(phrase) => like(column, first_param, patterns, phrase),
+        Node::Near { phrases, .. } => {
+            let conds: Vec<String> = phrases
+                .iter()
+                .map(|p| like(column, first_param, patterns, p))
+                .collect();
+            format!("({})", conds.join(" AND "))
+        }
+        Node::And { include, exclude } => {
+            let mut conds: Vec<String> = include
+                .iter()
+                .map(|n| emit_like(n, column, first_param, patterns))
+                .collect();
+            for node in exclude {
+                let cond = emit_like(node, column, first_param, patterns);
+                conds.push(format!("NOT {}", cond));
+            }
+            format!("({})", conds.join(" AND "))
+        }
+        Node::Or(nodes) => {
+            let conds: Vec<String> = nodes
+                .iter()
+                .map(|n| emit_like(n, column, first_param, patterns))
+                .collect();
+            format!("({})", conds.join(" OR "))
+        }
+    }
+}
+
+/// `column LIKE ?N` for the words of `text` in order, adding the pattern to
+/// `patterns`. Words are alphanumeric runs, so they hold no LIKE wildcards.
+fn like(column: &str, first_param: usize, patterns: &mut Vec<String>, text: &str) -> String {
+    patterns.push(format!("%{}%", words(text).collect::<Vec<_>>().join("%")));
+    format!("{} LIKE ?{}", column, first_param + patterns.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn test_loose_words() {
+        assert_eq!(
+            compile("auth bug"),
+            "(\"auth\" OR \"auth\"*) AND (\"bug\" OR \"bug\"*)"
+        );
+        assert_eq!(compile("fix it"), "(\"fix\" OR \"fix\"*) AND \"it\"");
+        assert_eq!(compile("auth*"), "\"auth\"*");
+        assert_eq!(compile(""), "");
+        assert_eq!(compile(" -- !! "), "");
+    }
+
+    #[test]
+    fn test_camel_case_expansion() {
+        assert_eq!(
+            compile("UserById"),
+            "(\"UserById\" OR \"UserById\"* OR \"user by id\")"
+        );
+        assert_eq!(compile("getUser*"), "(\"getUser\"* OR \"get user\"*)");
+        assert_eq!(
+            compile("数据库"),
+            "(\"数据库\" OR \"数据库\"* OR \"数据 据库\")"
+        );
+    }
+
+    #[test]
+    fn test_phrases_and_operators() {
+        assert_eq!(compile("\"dark mode\" on"), "\"dark mode\" AND \"on\"");
+        assert_eq!(compile("db OR sql"), "\"db\" OR (\"sql\" OR \"sql\"*)");
+        assert_eq!(compile("ab -cd"), "\"ab\" NOT \"cd\"");
+        assert_eq!(compile("ab NOT cd"), "\"ab\" NOT \"cd\"");
+        assert_eq!(compile("ab -\"cd ef\""), "\"ab\" NOT \"cd ef\"");
+        assert_eq!(compile("(ab OR cd) ef"), "(\"ab\" OR \"cd\") AND \"ef\"");
+        assert_eq!(compile("NEAR(ab cd, 5)"), "NEAR(\"ab\" \"cd\", 5)");
+        assert_eq!(compile("NEAR(ab \"cd ef\")"), "NEAR(\"ab\" \"cd ef\", 10)");
+        // Lowercase operators are plain words
+        assert_eq!(compile("ab or cd"), "\"ab\" AND \"or\" AND \"cd\"");
+    }
+
+    #[test]
+    fn test_message_condition() {
+        let (sql, patterns) = message_condition("redis OR postgres", "text", 4).unwrap();
+        assert_eq!(sql, "(text LIKE ?4 OR text LIKE ?5)");
+        assert_eq!(patterns, vec!["%redis%", "%postgres%"]);
+
+        let (sql, patterns) =
+            message_condition("(auth -oauth) \"dark mode\" tok*", "m.text", 1).unwrap();
+        assert_eq!(
+            sql,
+            "((m.text LIKE ?1 AND NOT m.text LIKE ?2) AND m.text LIKE ?3 AND m.text LIKE ?4)"
+        );
+        assert_eq!(patterns, vec!["%auth%", "%oauth%", "%dark%mode%", "%tok%"]);
+
+        assert!(message_condition(" -- ", "text", 1).is_none());
+        assert!(message_condition("-only", "text", 1).is_none());
+    }
+
+    #[test]
+    fn test_malformed_input_degrades() {
+        assert_eq!(compile("(ab cd"), "\"ab\" AND \"cd\"");
+        assert_eq!(compile("ab) cd"), "\"ab\" AND \"cd\"");
+        assert_eq!(compile("ab OR"), "\"ab\"");
+        assert_eq!(compile("-ab"), "");
+        assert_eq!(compile("NOT ab OR cd"), "\"cd\"");
+        assert_eq!(compile("\"unterminated ab"), "\"unterminated ab\"");
+        assert_eq!(compile("go \"\"hi\"\""), "\"go\" AND \"hi\"");
+        assert_eq!(compile("NEAR"), "(\"NEAR\" OR \"NEAR\"*)");
+    }
+
+    #[test]
+    fn test_conjunction() {
+        assert!(conjunction("auth").is_none());
+        assert!(conjunction("ab OR cd").is_none());
+
+        let c = conjunction("jwt \"token refresh\" redis -mock").unwrap();
+        assert_eq!(c.operand_count(), 3);
+        assert_eq!(c.label(1), "\"token refresh\"");
+        assert_eq!(c.operand(0), "(\"jwt\" OR \"jwt\"*)");
+        assert_eq!(
+            c.all_except(&[0, 2]),
+            "\"token refresh\" NOT (\"mock\" OR \"mock\"*)"
+        );
+        assert_eq!(
+            c.any(),
+            "((\"jwt\" OR \"jwt\"*) OR \"token refresh\" OR (\"redis\" OR \"redis\"*)) NOT (\"mock\" OR \"mock\"*)"
+        );
+    }
+
+    #[test]
+    fn test_positive_terms() {
+        assert_eq!(
+            positive_terms("(login OR \"sign in\") -oauth NEAR(a b)"),
+            vec!["login", "sign", "in", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_trigrams() {
+        assert_eq!(
+            compile_trigrams("Redis ok -mock"),
+            "(\"red\" OR \"edi\" OR \"dis\")"
+        );
+        assert_eq!(compile_trigrams("aaaa bcd"), "(\"aaa\") AND (\"bcd\")");
+        assert_eq!(compile_trigrams("ab"), "");
+    }
+
+    #[test]
+    fn test_trigram_share() {
+        let words = word_trigrams("postgress");
+        assert!(shares_trigrams(&words, "Migrating to Postgres 16"));
+        assert!(!shares_trigrams(&words, "the post position"));
+        // Every word must be close enough
+        let words = word_trigrams("postgress migraton");
+        assert!(shares_trigrams(&words, "postgres migration"));
+        assert!(!shares_trigrams(&words, "postgres upgrade"));
+    }
+
+    #[test]
+    fn test_phrase_mode() {
+        assert_eq!(compile_phrase("phase 1"), "\"phase 1\"");
+        assert_eq!(compile_phrase("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(compile_phrase(" ?! "), "");
+        assert_eq!(compile_phrase("a\0b"), "\"a b\"");
+    }
+
+    /// Random queries built from operator-heavy fragments
+    fn random_query(rng: &mut fastrand::Rng) -> String {
+        const FRAGMENTS: &[&str] = &[
+            "auth", "bug", "OR", "AND", "NOT", "NEAR", "(", ")", "\"", ",", "-", "*", ":", "^",
+            "+", "{", "}", "'", "\\", "5", "-auth", "auth*", "NEAR(", "col:", " ", " ", "é",
+            "日本", "\0", "\u{200b}", "\t",
+        ];
+        let len = rng.usize(0..16);
+        (0..len)
+            .map(|_| {
+                if rng.u8(..) < 16 {
+                    rng.char(..).to_string()
+                } else {
+                    FRAGMENTS[rng.usize(..FRAGMENTS.len())].to_string()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arbitrary_input_is_valid_fts5() {
+        let config = crate::config::Config {
+            trigram_index: true,
+            ..Default::default()
+        };
+        let db = Database::open_in_memory_with(&config).unwrap();
+        let conn = db.conn();
+        conn.execute(
+            "INSERT INTO chunks (session_id, chunk_index, text) VALUES ('s', 0, 'auth bug near the NEAR operator')",
+            [],
+        )
+        .unwrap();
+
+        let mut rng = fastrand::Rng::with_seed(0x5eed);
+        for _ in 0..5000 {
+            let query = random_query(&mut rng);
+            for compiled in [
+                compile(&query),
+                compile_phrase(&query),
+                compile_trigrams(&query),
+            ] {
+                if compiled.is_empty() {
+                    continue;
+                }
+                for table in [
+                    "chunks_fts",
+                    "sessions_fts",
+                    "tool_calls_fts",
+                    "chunks_trigram",
+                ] {
+                    let sql = format!("SELECT count(*) FROM {table} WHERE {table} MATCH ?1");
+                    let result: rusqlite::Result<i64> =
+                        conn.query_row(&sql, [&compiled], |row| row.get(0));
+                    assert!(
+                        result.is_ok(),
+                        "query {:?} compiled to {:?}: {:?}",
+                        query,
+                        compiled,
+                        result
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,20 @@
+pub mod ann;
 pub mod bm25;
+pub mod filter;
+pub mod fts_query;
+pub mod quantize;
+pub mod query;
 pub mod rrf;
 pub mod vector;
 
 use anyhow::Result;
 
-use crate::db::queries::SessionRow;
+use crate::db::queries::{ChunkMatch, FtsResult, SessionRow};
 use crate::db::Database;
 use crate::indexer::embedder::Embedder;
+use crate::indexer::parser::ChunkKind;
+use bm25::Relaxation;
+use filter::SearchFilter;
 
 /// A ranked search result with metadata
 #[derive(Debug, Clone, serde::Serialize)]
@@ -15,9 +23,46 @@ pub struct SearchResult {
     pub score: f64,
     pub bm25_rank: Option<usize>,
     pub vec_rank: Option<usize>,
+    /// Rank among typo-tolerant (trigram) matches, if that leg ran
+    pub fuzzy_rank: Option<usize>,
     pub session: SessionRow,
     /// The best matching chunk text for this session (if chunk-based search was used)
     pub matched_text: Option<String>,
+    /// Whether the matched chunk came from the conversation or from tool output
+    pub matched_kind: Option<ChunkKind>,
+    /// The message within the matched chunk that best matches the query
+    pub matched_message: Option<MessageRef>,
+    /// Whether the keyword match needed a relaxed query (some terms ignored)
+    pub relaxation: Relaxation,
+}
+
+/// Locates a search hit within its session ("message 42 of 180")
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MessageRef {
+    /// 1-based position of the message in the session
+    pub number: i64,
+    /// Total number of messages in the session
+    pub total: i64,
+    pub role: String,
+    pub timestamp: Option<String>,
+}
+
+/// Below this many keyword hits, typo-tolerant matches are mixed in
+const FUZZY_MIN_HITS: usize = 3;
+
+/// Ranking parameters for a hybrid search
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub limit: usize,
+    pub bm25_weight: f64,
+    pub vec_weight: f64,
+    pub rrf_k: f64,
+    /// RRF weight of the trigram leg (0 disables it)
+    pub fuzzy_weight: f64,
+    pub recency_halflife: f64,
+    pub exact: bool,
+    /// ANN lists scanned per vector search (0 = always compare every embedding)
+    pub ann_probes: usize,
 }
 
 /// Performs hybrid search: BM25 + vector + RRF fusion + recency boost
@@ -25,50 +70,70 @@ pub fn hybrid_search(
     db: &Database,
     embedder: Option<&mut Embedder>,
     query: &str,
-    limit: usize,
-    bm25_weight: f64,
-    vec_weight: f64,
-    rrf_k: f64,
-    recency_halflife: f64,
-    exclude_projects: &[String],
-    exact: bool,
+    options: &SearchOptions,
+    filter: &SearchFilter,
 ) -> Result<Vec<SearchResult>> {
+    let SearchOptions {
+        limit,
+        bm25_weight,
+        vec_weight,
+        rrf_k,
+        fuzzy_weight,
+        recency_halflife,
+        exact,
+        ann_probes,
+    } = *options;
+
+    // A query made only of qualifiers has nothing to rank by: list matching
+    // sessions, most recently active first
+    let filter_only = bm25::build_fts5_query(query).is_empty();
+
     // BM25 search (uses chunks if available, falls back to sessions)
-    let bm25_results = bm25::search(db, query, limit * 2, exact)?;
+    let bm25 = if filter_only {
+        bm25::Bm25Results {
+            results: db
+                .filter_sessions(filter, limit * 2)?
+                .into_iter()
+                .map(|session_id| FtsResult {
+                    session_id,
+                    rank: 0.0,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    } else {
+        bm25::search(db, query, limit * 2, exact, filter)?
+    };
+    let bm25_results = bm25.results;
 
     // Vector search (uses chunk embeddings if available, falls back to session embeddings)
-    let vec_results = if let Some(embedder) = embedder {
-        vector::search(db, embedder, query, limit * 2)?
-    } else {
-        Vec::new()
+    let vec_results = match embedder {
+        Some(embedder) if !filter_only => {
+            vector::search(db, embedder, query, limit * 2, filter, ann_probes)?
+        }
+        _ => Vec::new(),
     };
 
     // RRF fusion
-    let fused = rrf::fuse(&bm25_results, &vec_results, bm25_weight, vec_weight, rrf_k);
+    let bm25_weight = if filter_only { 1.0 } else { bm25_weight };
+    let mut fused = rrf::fuse(&bm25_results, &vec_results, bm25_weight, vec_weight, rrf_k);
+
+    // Typo-tolerant leg: only when exact tokens found little (e.g. "authentcation")
+    let few_hits = bm25_results.len() < FUZZY_MIN_HITS || bm25.relaxation.is_partial();
+    if !exact && !filter_only && fuzzy_weight > 0.0 && few_hits && db.has_trigram_index()? {
+        let fuzzy_results = db.fuzzy_search(query, limit * 2, filter)?;
+        rrf::fuse_fuzzy(&mut fused, &fuzzy_results, fuzzy_weight, rrf_k);
+    }
 
     let now = chrono::Utc::now();
 
-    // Build FTS5 query for chunk text retrieval (match the search mode)
-    let fts_query = if exact {
-        format!("\"{}\"", query.trim())
-    } else {
-        bm25::build_fts5_query(query)
-    };
+    // FTS5 query for chunk text retrieval (the one that found the BM25 results)
+    let fts_query = bm25.fts_query;
 
     // Fetch full session data and apply recency boost
     let mut results = Vec::new();
     for rrf_result in fused.into_iter().take(limit * 2) {
         if let Ok(Some(session)) = db.get_session(&rrf_result.session_id) {
-            // Skip excluded projects
-            if exclude_projects.iter().any(|ex| {
-                session
-                    .project_path
-                    .to_lowercase()
-                    .contains(&ex.to_lowercase())
-            }) {
-                continue;
-            }
-
             let score = if recency_halflife > 0.0 {
                 let age_days = chrono::DateTime::parse_from_rfc3339(&session.modified_at)
                     .map(|dt| (now - dt.to_utc()).num_hours() as f64 / 24.0)
@@ -80,20 +145,33 @@ pub fn hybrid_search(
             };
 
             // Get the best matching chunk text for preview
-            let matched_text = if !fts_query.is_empty() {
+            let matched_chunk = if !fts_query.is_empty() {
                 db.get_best_matching_chunk(&fts_query, &rrf_result.session_id)
                     .unwrap_or(None)
             } else {
                 None
             };
 
+            let matched_message = matched_chunk
+                .as_ref()
+                .and_then(|chunk| locate_message(db, &rrf_result.session_id, chunk, query));
+
             results.push(SearchResult {
                 session_id: rrf_result.session_id,
                 score,
                 bm25_rank: rrf_result.bm25_rank,
                 vec_rank: rrf_result.vec_rank,
+                fuzzy_rank: rrf_result.fuzzy_rank,
                 session,
-                matched_text,
+                matched_text: matched_chunk.as_ref().map(|c| c.text.clone()),
+                matched_kind: matched_chunk.map(|c| c.kind),
+                matched_message,
+                // Vector-only hits weren't found by the relaxed keyword query
+                relaxation: if rrf_result.bm25_rank.is_some() {
+                    bm25.relaxation.clone()
+                } else {
+                    Relaxation::AllTerms
+                },
             });
         }
     }
@@ -109,3 +187,33 @@ pub fn hybrid_search(
 
     Ok(results)
 }
+
+/// Picks the message in the chunk's range that mentions the most query terms
+fn locate_message(
+    db: &Database,
+    session_id: &str,
+    chunk: &ChunkMatch,
+    query: &str,
+) -> Option<MessageRef> {
+    let (first, last) = (chunk.first_ordinal?, chunk.last_ordinal?);
+    let messages = db.get_messages_in_range(session_id, first, last).ok()?;
+    let total = db.get_session_message_total(session_id).ok()?;
+
+    let terms: Vec<String> = fts_query::positive_terms(query)
+        .into_iter()
+        .map(|t| t.to_lowercase())
+        .collect();
+
+    // max_by_key returns the last maximum; iterate in reverse so ties go to the earliest message
+    let best = messages.iter().rev().max_by_key(|m| {
+        let lower = m.text.to_lowercase();
+        terms.iter().filter(|t| lower.contains(t.as_str())).count()
+    })?;
+
+    Some(MessageRef {
+        number: best.ordinal + 1,
+        total,
+        role: best.role.clone(),
+        timestamp: best.timestamp.clone(),
+    })
+}
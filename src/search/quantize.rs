@@ -0,0 +1,172 @@
+use crate::config::VectorQuantization;
+
+/// Encodes an embedding in its compact form.
+/// int8: little-endian f32 scale followed by one `i8` per dimension (value ≈ byte × scale).
+/// binary: one bit per dimension, set when the value is positive, packed LSB first.
+pub fn encode(embedding: &[f32], mode: VectorQuantization) -> Vec<u8> {
+    match mode {
+        VectorQuantization::None => Vec::new(),
+        VectorQuantization::Int8 => {
+            let max = embedding.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+            let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+            let mut code = Vec::with_capacity(4 + embedding.len());
+            code.extend_from_slice(&scale.to_le_bytes());
+            code.extend(
+                embedding
+                    .iter()
+                    .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8 as u8),
+            );
+            code
+        }
+        VectorQuantization::Binary => {
+            let mut code = vec![0u8; embedding.len().div_ceil(8)];
+            for (i, x) in embedding.iter().enumerate() {
+                if *x > 0.0 {
+                    code[i / 8] |= 1 << (i % 8);
+                }
+            }
+            code
+        }
+    }
+}
+
+/// Decodes an int8 code back to an embedding, each value within half a
+/// quantization step of the original
+pub fn decode_int8(code: &[u8]) -> Vec<f32> {
+    if code.len() < 4 {
+        return Vec::new();
+    }
+    let scale = int8_scale(code);
+    code[4..].iter().map(|b| *b as i8 as f32 * scale).collect()
+}
+
+/// A query embedding encoded once for scoring against many stored codes
+pub struct QueryCode {
+    mode: VectorQuantization,
+    code: Vec<u8>,
+    dim: usize,
+}
+
+impl QueryCode {
+    pub fn new(embedding: &[f32], mode: VectorQuantization) -> Self {
+        Self {
+            mode,
+            code: encode(embedding, mode),
+            dim: embedding.len(),
+        }
+    }
+
+    /// Approximate cosine similarity with a stored code (higher is closer).
+    /// int8 uses an integer dot product; binary uses the Hamming distance
+    /// (`1 - 2 × differing bits / dimensions`).
+    pub fn score(&self, code: &[u8]) -> f32 {
+        match self.mode {
+            VectorQuantization::None => 0.0,
+            VectorQuantization::Int8 => {
+                if code.len() != self.code.len() {
+                    return f32::MIN;
+                }
+                let dot: i32 = self.code[4..]
+                    .iter()
+                    .zip(&code[4..])
+                    .map(|(a, b)| (*a as i8 as i32) * (*b as i8 as i32))
+                    .sum();
+                dot as f32 * int8_scale(&self.code) * int8_scale(code)
+            }
+            VectorQuantization::Binary => {
+                if code.len() != self.code.len() || self.dim == 0 {
+                    return f32::MIN;
+                }
+                let differing = hamming(&self.code, code);
+                1.0 - 2.0 * differing as f32 / self.dim as f32
+            }
+        }
+    }
+}
+
+fn int8_scale(code: &[u8]) -> f32 {
+    f32::from_le_bytes([code[0], code[1], code[2], code[3]])
+}
+
+/// Number of differing bits, compared eight bytes at a time
+fn hamming(a: &[u8], b: &[u8]) -> u32 {
+    let words = a.chunks_exact(8).zip(b.chunks_exact(8));
+    let mut differing: u32 = words
+        .map(|(x, y)| {
+            let x = u64::from_le_bytes(x.try_into().expect("8-byte chunk"));
+            let y = u64::from_le_bytes(y.try_into().expect("8-byte chunk"));
+            (x ^ y).count_ones()
+        })
+        .sum();
+    let tail = a.len() / 8 * 8;
+    for (x, y) in a[tail..].iter().zip(&b[tail..]) {
+        differing += (x ^ y).count_ones();
+    }
+    differing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(v: &[f32]) -> Vec<f32> {
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.iter().map(|x| x / norm).collect()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_code_sizes() {
+        let embedding = vec![0.5f32; 384];
+        assert_eq!(encode(&embedding, VectorQuantization::Int8).len(), 388);
+        assert_eq!(encode(&embedding, VectorQuantization::Binary).len(), 48);
+        assert!(encode(&embedding, VectorQuantization::None).is_empty());
+        assert_eq!(encode(&[1.0; 10], VectorQuantization::Binary).len(), 2);
+    }
+
+    #[test]
+    fn test_int8_score_approximates_cosine() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        for _ in 0..20 {
+            let a = normalized(&(0..64).map(|_| rng.f32() - 0.5).collect::<Vec<_>>());
+            let b = normalized(&(0..64).map(|_| rng.f32() - 0.5).collect::<Vec<_>>());
+            let query = QueryCode::new(&a, VectorQuantization::Int8);
+            let approx = query.score(&encode(&b, VectorQuantization::Int8));
+            assert!((approx - cosine(&a, &b)).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_int8_decodes_close_to_original() {
+        let embedding: Vec<f32> = (0..32).map(|i| (i as f32 - 16.0) / 20.0).collect();
+        let decoded = decode_int8(&encode(&embedding, VectorQuantization::Int8));
+        let step = 0.8 / 127.0;
+        assert_eq!(decoded.len(), embedding.len());
+        for (x, y) in embedding.iter().zip(&decoded) {
+            assert!((x - y).abs() <= step / 2.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_binary_score_counts_sign_agreement() {
+        let a = [1.0, -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+        let query = QueryCode::new(&a, VectorQuantization::Binary);
+        assert_eq!(query.score(&encode(&a, VectorQuantization::Binary)), 1.0);
+
+        let flipped: Vec<f32> = a.iter().map(|x| -x).collect();
+        assert_eq!(
+            query.score(&encode(&flipped, VectorQuantization::Binary)),
+            -1.0
+        );
+
+        // Two of ten signs differ
+        let mut near = a;
+        near[0] = -1.0;
+        near[9] = -1.0;
+        let score = query.score(&encode(&near, VectorQuantization::Binary));
+        assert!((score - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,329 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::filter::SearchFilter;
+
+/// A search query split into free text and field qualifiers, e.g.
+/// `token refresh project:api branch:main after:2024-05-01 role:user -mock`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// Free text sent to BM25 and vector search
+    pub text: String,
+    /// `-term`: drop sessions that mention this term
+    pub exclude_terms: Vec<String>,
+    /// `project:` substring or glob
+    pub project: Option<String>,
+    /// `-project:` substrings to exclude
+    pub exclude_projects: Vec<String>,
+    /// `branch:` substring or glob
+    pub branch: Option<String>,
+    /// `after:` lower bound (inclusive) on last activity
+    pub after: Option<DateTime<Utc>>,
+    /// `before:` upper bound (exclusive) on last activity
+    pub before: Option<DateTime<Utc>>,
+    /// `role:` user or assistant
+    pub role: Option<String>,
+    /// `tool:` tool name
+    pub tool: Option<String>,
+    /// `file:` file path substring
+    pub file: Option<String>,
+    /// `id:` session ID prefix
+    pub id: Option<String>,
+}
+
+/// A query that can't be turned into a search
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum QueryError {
+    #[error("`{0}:` needs a value, e.g. `{0}:something`")]
+    MissingValue(String),
+    #[error("invalid date `{value}` in `{key}:` (expected YYYY-MM-DD)")]
+    InvalidDate { key: String, value: String },
+    #[error("invalid role `{0}` (expected `role:user` or `role:assistant`)")]
+    InvalidRole(String),
+    #[error("`-{0}:` is not supported; only `-project:` can be negated")]
+    NegatedQualifier(String),
+    #[error("`after:` must be earlier than `before:`")]
+    EmptyDateRange,
+}
+
+/// Qualifiers recognized in queries; any other `word:` stays free text
+pub const QUALIFIERS: &[&str] = &[
+    "project", "branch", "after", "before", "role", "tool", "file", "id",
+];
+
+/// Parses a query into free text, negated terms and qualifiers.
+/// Values may be quoted (`project:"my app"`); quoted phrases in the free text are kept as-is.
+pub fn parse_query(input: &str) -> Result<ParsedQuery, QueryError> {
+    let mut parsed = ParsedQuery::default();
+    let mut text_parts: Vec<String> = Vec::new();
+
+    // Inside parentheses, `-term` belongs to the boolean expression (see `fts_query`)
+    let mut depth = 0usize;
+
+    for token in split_tokens(input) {
+        let (negated, body) = match token.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() && depth == 0 => (true, rest),
+            _ => (false, token.as_str()),
+        };
+
+        if let Some((key, value)) = split_qualifier(body) {
+            let value = unquote(value);
+            if value.is_empty() {
+                return Err(QueryError::MissingValue(key));
+            }
+            if negated {
+                if key != "project" {
+                    return Err(QueryError::NegatedQualifier(key));
+                }
+                parsed.exclude_projects.push(value);
+                continue;
+            }
+            match key.as_str() {
+                "project" => parsed.project = Some(value),
+                "branch" => parsed.branch = Some(value),
+                "after" => parsed.after = Some(parse_date(&key, &value)?),
+                "before" => parsed.before = Some(parse_date(&key, &value)?),
+                "role" => parsed.role = Some(parse_role(&value)?),
+                "tool" => parsed.tool = Some(value),
+                "file" => parsed.file = Some(value),
+                "id" => parsed.id = Some(value),
+                _ => unreachable!("split_qualifier only returns known qualifiers"),
+            }
+            continue;
+        }
+
+        if negated {
+            let term = unquote(body);
+            // A lone "-" or "--flag"-like punctuation is not a searchable term
+            if term.chars().any(|c| c.is_alphanumeric()) {
+                parsed.exclude_terms.push(term);
+            }
+            continue;
+        }
+
+        depth = paren_depth(depth, &token);
+        text_parts.push(token);
+    }
+
+    if let (Some(after), Some(before)) = (parsed.after, parsed.before) {
+        if after >= before {
+            return Err(QueryError::EmptyDateRange);
+        }
+    }
+
+    parsed.text = text_parts.join(" ");
+    Ok(parsed)
+}
+
+impl ParsedQuery {
+    /// True if the query has qualifiers or negated terms in addition to (or instead of) free text
+    pub fn has_filters(&self) -> bool {
+        *self
+            != ParsedQuery {
+                text: self.text.clone(),
+                ..Default::default()
+            }
+    }
+
+    /// Copies the qualifiers into a search filter (qualifiers override what is already set)
+    pub fn apply(&self, filter: &mut SearchFilter) {
+        let set = |slot: &mut Option<String>, value: &Option<String>| {
+            if value.is_some() {
+                slot.clone_from(value);
+            }
+        };
+        set(&mut filter.project, &self.project);
+        set(&mut filter.branch, &self.branch);
+        set(&mut filter.tool, &self.tool);
+        set(&mut filter.file, &self.file);
+        set(&mut filter.session_id, &self.id);
+
+        if self.after.is_some() || self.before.is_some() {
+            filter.after = self.after;
+            filter.before = self.before;
+        }
+        if let Some(ref role) = self.role {
+            filter.role = Some(role.clone());
+            filter.role_text.clone_from(&self.text);
+        }
+        filter
+            .exclude_projects
+            .extend(self.exclude_projects.iter().cloned());
+        filter
+            .exclude_terms
+            .extend(self.exclude_terms.iter().cloned());
+    }
+}
+
+/// Splits on whitespace, keeping double-quoted sections (including the quotes) together.
+/// A quote left open runs to the end of the input.
+fn split_tokens(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_quotes {
+        current.push('"');
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Nesting depth after a free-text token, ignoring parentheses inside quotes
+fn paren_depth(mut depth: usize, token: &str) -> usize {
+    let mut in_quotes = false;
+    for c in token.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Splits `key:value` if `key` is a known qualifier (case-insensitive)
+fn split_qualifier(token: &str) -> Option<(String, &str)> {
+    let (key, value) = token.split_once(':')?;
+    let key = key.to_lowercase();
+    QUALIFIERS.contains(&key.as_str()).then_some((key, value))
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Accepts YYYY-MM-DD (midnight UTC) or a full RFC 3339 timestamp
+fn parse_date(key: &str, value: &str) -> Result<DateTime<Utc>, QueryError> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.to_utc())
+        .map_err(|_| QueryError::InvalidDate {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+}
+
+fn parse_role(value: &str) -> Result<String, QueryError> {
+    match value.to_lowercase().as_str() {
+        "user" => Ok("user".to_string()),
+        "assistant" | "claude" => Ok("assistant".to_string()),
+        _ => Err(QueryError::InvalidRole(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_example() {
+        let q =
+            parse_query("token refresh project:api branch:main after:2024-05-01 role:user -mock")
+                .unwrap();
+        assert_eq!(q.text, "token refresh");
+        assert_eq!(q.project.as_deref(), Some("api"));
+        assert_eq!(q.branch.as_deref(), Some("main"));
+        assert_eq!(q.after.unwrap().to_rfc3339(), "2024-05-01T00:00:00+00:00");
+        assert_eq!(q.role.as_deref(), Some("user"));
+        assert_eq!(q.exclude_terms, vec!["mock"]);
+        assert!(q.has_filters());
+    }
+
+    #[test]
+    fn test_parse_plain_text() {
+        let q = parse_query("fix: the \"dark mode\" toggle http://localhost:3000").unwrap();
+        assert_eq!(
+            q.text,
+            "fix: the \"dark mode\" toggle http://localhost:3000"
+        );
+        assert!(!q.has_filters());
+    }
+
+    #[test]
+    fn test_negation_inside_group_stays_in_text() {
+        let q = parse_query("(auth -oauth) OR login -mock").unwrap();
+        assert_eq!(q.text, "(auth -oauth) OR login");
+        assert_eq!(q.exclude_terms, vec!["mock"]);
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let q =
+            parse_query("project:\"my app\" FILE:src/auth.rs id:abc123 -project:scratch").unwrap();
+        assert_eq!(q.text, "");
+        assert_eq!(q.project.as_deref(), Some("my app"));
+        assert_eq!(q.file.as_deref(), Some("src/auth.rs"));
+        assert_eq!(q.id.as_deref(), Some("abc123"));
+        assert_eq!(q.exclude_projects, vec!["scratch"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_query("bug project:"),
+            Err(QueryError::MissingValue("project".to_string()))
+        );
+        assert!(matches!(
+            parse_query("after:yesterday"),
+            Err(QueryError::InvalidDate { .. })
+        ));
+        assert_eq!(
+            parse_query("role:bot"),
+            Err(QueryError::InvalidRole("bot".to_string()))
+        );
+        assert_eq!(
+            parse_query("-tool:Bash"),
+            Err(QueryError::NegatedQualifier("tool".to_string()))
+        );
+        assert_eq!(
+            parse_query("after:2024-06-01 before:2024-05-01"),
+            Err(QueryError::EmptyDateRange)
+        );
+        // An unclosed quote closes at the end of the query
+        assert_eq!(
+            parse_query("he said \"unclosed phrase").unwrap().text,
+            "he said \"unclosed phrase\""
+        );
+    }
+
+    #[test]
+    fn test_apply_to_filter() {
+        let q = parse_query("refresh token role:user tool:Edit before:2024-05-01").unwrap();
+        let mut filter = SearchFilter {
+            tool: Some("Bash".to_string()),
+            after: Some(Utc::now()),
+            ..Default::default()
+        };
+        q.apply(&mut filter);
+        assert_eq!(filter.tool.as_deref(), Some("Edit"));
+        assert_eq!(filter.after, None);
+        assert!(filter.before.is_some());
+        assert_eq!(filter.role.as_deref(), Some("user"));
+        assert_eq!(filter.role_text, "refresh token");
+    }
+}
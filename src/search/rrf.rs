@@ -9,6 +9,7 @@ pub struct RrfResult {
     pub score: f64,
     pub bm25_rank: Option<usize>,
     pub vec_rank: Option<usize>,
+    pub fuzzy_rank: Option<usize>,
 }
 
 /// Combines BM25 and vector search results using Reciprocal Rank Fusion.
@@ -36,6 +37,7 @@ pub fn fuse(
                 score: 0.0,
                 bm25_rank: None,
                 vec_rank: None,
+                fuzzy_rank: None,
             });
         entry.score += bm25_weight / (rank as f64 + 1.0 + k);
         entry.bm25_rank = Some(rank + 1);
@@ -50,6 +52,7 @@ pub fn fuse(
                 score: 0.0,
                 bm25_rank: None,
                 vec_rank: None,
+                fuzzy_rank: None,
             });
         entry.score += vec_weight / (rank as f64 + 1.0 + k);
         entry.vec_rank = Some(rank + 1);
@@ -57,13 +60,38 @@ pub fn fuse(
 
     // Sort by score descending
     let mut results: Vec<RrfResult> = scores.into_values().collect();
+    sort_by_score(&mut results);
+
+    results
+}
+
+/// Adds typo-tolerant (trigram) matches as a third ranked list to already fused results
+pub fn fuse_fuzzy(fused: &mut Vec<RrfResult>, fuzzy_results: &[FtsResult], weight: f64, k: f64) {
+    for (rank, result) in fuzzy_results.iter().enumerate() {
+        let score = weight / (rank as f64 + 1.0 + k);
+        match fused.iter_mut().find(|r| r.session_id == result.session_id) {
+            Some(entry) => {
+                entry.score += score;
+                entry.fuzzy_rank = Some(rank + 1);
+            }
+            None => fused.push(RrfResult {
+                session_id: result.session_id.clone(),
+                score,
+                bm25_rank: None,
+                vec_rank: None,
+                fuzzy_rank: Some(rank + 1),
+            }),
+        }
+    }
+    sort_by_score(fused);
+}
+
+fn sort_by_score(results: &mut [RrfResult]) {
     results.sort_by(|a, b| {
         b.score
             .partial_cmp(&a.score)
             .unwrap_or(std::cmp::Ordering::Equal)
     });
-
-    results
 }
 
 #[cfg(test)]
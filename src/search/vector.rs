@@ -1,5 +1,7 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
+use super::ann;
+use super::filter::SearchFilter;
 use crate::db::queries::VecResult;
 use crate::db::Database;
 use crate::indexer::embedder::Embedder;
@@ -10,11 +12,56 @@ pub fn search(
     embedder: &mut Embedder,
     query: &str,
     limit: usize,
+    filter: &SearchFilter,
+    probes: usize,
 ) -> Result<Vec<VecResult>> {
     if !db.has_vector_search() {
         return Ok(Vec::new());
     }
 
-    let query_embedding = embedder.embed(query)?;
-    db.vec_search(&query_embedding, limit)
+    let query_embedding = embedder.embed_query(query)?;
+    search_embedding(db, &query_embedding, limit, filter, probes)
+}
+
+/// Finds the sessions closest to an embedding. When the ANN index exists and
+/// `probes` > 0, only the `probes` nearest lists are scanned; otherwise (or if
+/// that finds fewer than `limit` sessions, e.g. under a narrow filter) every
+/// embedding is compared. Refuses to compare vectors from another model: of
+/// different dimensions, or not yet dropped after the model changed.
+pub fn search_embedding(
+    db: &Database,
+    query_embedding: &[f32],
+    limit: usize,
+    filter: &SearchFilter,
+    probes: usize,
+) -> Result<Vec<VecResult>> {
+    if let Some((stored, model)) = db.stale_embedding_model()? {
+        bail!(
+            "The index holds vectors from {} but the embedding model is {}; \
+             run `ccsearch index` to re-embed them, or search with --exact",
+            stored,
+            model
+        );
+    }
+    if let Some(dim) = db.chunk_embedding_dim()? {
+        if dim != query_embedding.len() {
+            bail!(
+                "The index holds {}-dimensional vectors but the embedding model produces {}; \
+                 run `ccsearch index --force` to rebuild them, or search with --exact",
+                dim,
+                query_embedding.len()
+            );
+        }
+    }
+    if probes > 0 {
+        let centroids = db.get_ann_centroids()?;
+        if !centroids.is_empty() {
+            let lists = ann::nearest_lists(query_embedding, &centroids, probes);
+            let results = db.vec_search(query_embedding, limit, filter, Some(&lists))?;
+            if results.len() >= limit {
+                return Ok(results);
+            }
+        }
+    }
+    db.vec_search(query_embedding, limit, filter, None)
 }
@@ -15,6 +15,9 @@ use ratatui::{
 };
 use std::io::stdout;
 
+use crate::search::filter::is_glob;
+use crate::search::fts_query;
+use crate::search::query::{parse_query, ParsedQuery};
 use crate::search::SearchResult;
 
 /// Time range filter for results
@@ -59,6 +62,8 @@ impl TimeFilter {
 pub struct App {
     pub results: Vec<SearchResult>,
     pub query: String,
+    /// Search terms of the query (without qualifiers or operators), used for highlighting
+    pub highlight: String,
     pub selected: usize,
     pub list_state: ListState,
     pub filter: String,
@@ -71,9 +76,14 @@ pub struct App {
 
 impl App {
     pub fn new(results: Vec<SearchResult>, query: String) -> Self {
+        let text = parse_query(&query)
+            .map(|q| q.text)
+            .unwrap_or_else(|_| query.clone());
+        let highlight = fts_query::positive_terms(&text).join(" ");
         Self {
             results,
             query,
+            highlight,
             selected: 0,
             list_state: ListState::default().with_selected(Some(0)),
             filter: String::new(),
@@ -91,10 +101,31 @@ impl App {
         self.list_state.select(Some(index));
     }
 
+    /// Parses the `/` filter with the same query language as the search itself.
+    /// Qualifiers that need the index (tool:, file:, role:) are rejected.
+    pub fn parsed_filter(&self) -> Result<ParsedQuery, String> {
+        let parsed = parse_query(&self.filter).map_err(|e| e.to_string())?;
+        for (key, value) in [
+            ("tool", &parsed.tool),
+            ("file", &parsed.file),
+            ("role", &parsed.role),
+        ] {
+            if value.is_some() {
+                return Err(format!(
+                    "`{}:` only works in the search query, not in the result filter",
+                    key
+                ));
+            }
+        }
+        Ok(parsed)
+    }
+
     /// Returns filtered results based on text filter and time filter
     pub fn filtered_results(&self) -> Vec<&SearchResult> {
         let now = chrono::Utc::now();
         let max_age = self.time_filter.max_age_hours();
+        // While the filter doesn't parse, keep showing everything
+        let parsed_filter = self.parsed_filter().unwrap_or_default();
 
         self.results
             .iter()
@@ -112,34 +143,79 @@ impl App {
                     }
                 }
 
-                // Text filter
-                if !self.filter.is_empty() {
-                    let lower_filter = self.filter.to_lowercase();
-                    return r
-                        .session
-                        .summary
-                        .as_deref()
-                        .unwrap_or("")
-                        .to_lowercase()
-                        .contains(&lower_filter)
-                        || r.session
-                            .first_prompt
-                            .as_deref()
-                            .unwrap_or("")
-                            .to_lowercase()
-                            .contains(&lower_filter)
-                        || r.session
-                            .project_path
-                            .to_lowercase()
-                            .contains(&lower_filter);
-                }
-
-                true
+                // Text filter and qualifiers
+                matches_filter(r, &parsed_filter)
             })
             .collect()
     }
 }
 
+/// Client-side version of the search filter, applied to already loaded results
+fn matches_filter(r: &SearchResult, q: &ParsedQuery) -> bool {
+    let contains =
+        |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+    let matches_pattern = |value: &str, pattern: &str| {
+        if is_glob(pattern) {
+            glob::Pattern::new(pattern)
+                .map(|p| p.matches(value))
+                .unwrap_or(false)
+        } else {
+            contains(value, pattern)
+        }
+    };
+
+    let session = &r.session;
+    let text_fields = [
+        session.summary.as_deref().unwrap_or(""),
+        session.first_prompt.as_deref().unwrap_or(""),
+        &session.project_path,
+    ];
+
+    if !q.text.is_empty() && !text_fields.iter().any(|f| contains(f, &q.text)) {
+        return false;
+    }
+    if q.exclude_terms
+        .iter()
+        .any(|t| text_fields.iter().any(|f| contains(f, t)))
+    {
+        return false;
+    }
+    if let Some(ref project) = q.project {
+        if !matches_pattern(&session.project_path, project) {
+            return false;
+        }
+    }
+    if q.exclude_projects
+        .iter()
+        .any(|p| contains(&session.project_path, p))
+    {
+        return false;
+    }
+    if let Some(ref branch) = q.branch {
+        if !matches_pattern(session.git_branch.as_deref().unwrap_or(""), branch) {
+            return false;
+        }
+    }
+    if let Some(ref id) = q.id {
+        if !session.session_id.starts_with(id.as_str()) {
+            return false;
+        }
+    }
+    if q.after.is_some() || q.before.is_some() {
+        let Ok(modified) = chrono::DateTime::parse_from_rfc3339(&session.modified_at) else {
+            return false;
+        };
+        let modified = modified.to_utc();
+        if q.after.is_some_and(|after| modified < after)
+            || q.before.is_some_and(|before| modified >= before)
+        {
+            return false;
+        }
+    }
+
+    true
+}
+
 /// Runs the interactive TUI picker and returns (session_id, project_path)
 pub fn run(results: Vec<SearchResult>, query: &str) -> Result<Option<(String, String)>> {
     if results.is_empty() {
@@ -199,10 +275,15 @@ fn run_event_loop(
 
             // Preview pane
             let selected_result = filtered_owned.get(app.selected);
-            picker::render_preview(f, main_chunks[1], selected_result, &app.query);
+            picker::render_preview(f, main_chunks[1], selected_result, &app.highlight);
 
-            // Help bar
-            picker::render_help_bar(f, chunks[1]);
+            // Help bar (shows the filter and any error while filtering)
+            let filter_error = app.parsed_filter().err();
+            if app.filter_mode || !app.filter.is_empty() {
+                picker::render_filter_bar(f, chunks[1], &app.filter, filter_error.as_deref());
+            } else {
+                picker::render_help_bar(f, chunks[1]);
+            }
         })?;
 
         if app.should_quit {
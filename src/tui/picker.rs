@@ -7,7 +7,8 @@ use ratatui::{
 
 use super::theme::Theme;
 use super::TimeFilter;
-use crate::search::SearchResult;
+use crate::indexer::parser::ChunkKind;
+use crate::search::{MessageRef, SearchResult};
 
 /// Renders the search results list on the left
 pub fn render_results_list(
@@ -62,6 +63,11 @@ pub fn render_results_list(
         .collect();
 
     let time_label = format!(" [{}]", time_filter.label());
+    let partial_label = results
+        .iter()
+        .find_map(|r| r.relaxation.describe())
+        .map(|note| format!(" {} ", note))
+        .unwrap_or_default();
 
     let list = List::new(items)
         .block(
@@ -75,6 +81,7 @@ pub fn render_results_list(
                     ),
                     Span::styled(time_label, Theme::highlight()),
                     Span::styled(" (tab to cycle) ", Theme::subtitle()),
+                    Span::styled(partial_label, Theme::date()),
                 ])),
         )
         .highlight_style(Theme::selected());
@@ -146,12 +153,18 @@ pub fn render_preview(f: &mut Frame, area: Rect, result: Option<&SearchResult>,
             .unwrap_or(&result.session.full_text);
         let snippet = extract_snippet(source_text, query, 500);
         if !snippet.is_empty() {
-            let label = if result.matched_text.is_some() {
-                "Matching chunk:"
-            } else {
-                "Matching text:"
+            let label = match result.matched_kind {
+                Some(ChunkKind::ToolOutput) => "Matching tool output:",
+                Some(ChunkKind::Conversation) => "Matching chunk:",
+                None => "Matching text:",
             };
             lines.push(Line::from(Span::styled(label, Theme::subtitle())));
+            if let Some(ref msg) = result.matched_message {
+                lines.push(Line::from(Span::styled(
+                    format_message_ref(msg),
+                    Theme::date(),
+                )));
+            }
             for line in snippet.lines() {
                 let mut spans = vec![Span::raw("  ".to_string())];
                 spans.extend(highlight_query_in_line(line, query));
@@ -196,6 +209,24 @@ pub fn render_help_bar(f: &mut Frame, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+/// Renders the result filter being typed after `/`, with any parse error
+pub fn render_filter_bar(f: &mut Frame, area: Rect, filter: &str, error: Option<&str>) {
+    let mut spans = vec![
+        Span::styled(" / ", Theme::title()),
+        Span::styled(filter.to_string(), Theme::normal()),
+    ];
+    match error {
+        Some(error) => spans.push(Span::styled(format!("  {}", error), Theme::error())),
+        None => spans.push(Span::styled(
+            "  (project: branch: after: before: id: -term)",
+            Theme::help_text(),
+        )),
+    }
+
+    let paragraph = Paragraph::new(Line::from(spans)).style(Theme::status_bar());
+    f.render_widget(paragraph, area);
+}
+
 /// Splits a line into owned spans, highlighting case-insensitive occurrences
 /// of the full query phrase. Only highlights exact phrase matches.
 fn highlight_query_in_line(line: &str, query: &str) -> Vec<Span<'static>> {
@@ -300,3 +331,13 @@ fn format_date(date_str: &str) -> String {
         .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
         .unwrap_or_else(|_| date_str.chars().take(16).collect())
 }
+
+/// Formats a message location like "Message 42 of 180 · 2024-05-03 14:12 · assistant"
+fn format_message_ref(msg: &MessageRef) -> String {
+    let mut parts = vec![format!("Message {} of {}", msg.number, msg.total)];
+    if let Some(ref ts) = msg.timestamp {
+        parts.push(format_date(ts));
+    }
+    parts.push(msg.role.clone());
+    parts.join(" · ")
+}
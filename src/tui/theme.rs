@@ -53,4 +53,8 @@ impl Theme {
     pub fn help_text() -> Style {
         Style::default().fg(Color::DarkGray)
     }
+
+    pub fn error() -> Style {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    }
 }
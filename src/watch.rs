@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::claude;
+use crate::config::{self, Config};
+use crate::db::{lock::IndexLock, Database};
+use crate::indexer::{embedder::Embedder, Indexer};
+
+/// How often a running watcher refreshes its heartbeat file
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A heartbeat older than this means the watcher is gone
+const HEARTBEAT_STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Heartbeat file written by `ccsearch watch` (contains the watcher's PID)
+pub fn heartbeat_path() -> PathBuf {
+    config::ccsearch_dir().join("watch.pid")
+}
+
+/// True if a `ccsearch watch` process has refreshed its heartbeat recently,
+/// in which case the index is already warm and searches can skip JIT indexing
+pub fn is_watcher_alive() -> bool {
+    heartbeat_is_fresh(&heartbeat_path(), SystemTime::now())
+}
+
+fn heartbeat_is_fresh(path: &Path, now: SystemTime) -> bool {
+    let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) else {
+        return false;
+    };
+    match now.duration_since(modified) {
+        Ok(age) => age < HEARTBEAT_STALE_AFTER,
+        // Modified "in the future" (clock skew): treat as fresh
+        Err(_) => true,
+    }
+}
+
+fn write_heartbeat(path: &Path) -> Result<()> {
+    std::fs::write(path, std::process::id().to_string())
+        .with_context(|| format!("Failed to write heartbeat {:?}", path))
+}
+
+/// Watches ~/.claude/projects and incrementally indexes sessions as they change.
+/// Bursts of writes are debounced: indexing runs once no event has arrived for
+/// `config.watch_debounce_ms`. Runs until interrupted.
+pub fn run(
+    db: &Database,
+    embedder: Option<Embedder>,
+    config: &Config,
+    verbose: bool,
+) -> Result<()> {
+    let projects_dir = claude::claude_projects_dir()?;
+    let heartbeat = heartbeat_path();
+    let debounce = Duration::from_millis(config.watch_debounce_ms);
+    let lock_timeout = Duration::from_millis(config.index_lock_timeout_ms);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("Failed to create filesystem watcher")?;
+    watcher
+        .watch(&projects_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {:?}", projects_dir))?;
+
+    let mut indexer = Indexer::new(db, embedder, config, verbose);
+
+    write_heartbeat(&heartbeat)?;
+    let mut last_heartbeat = Instant::now();
+
+    // Time of the most recent relevant event, while changes are pending
+    let mut pending_since: Option<Instant> = None;
+    let mut saw_removal = false;
+
+    // Catch up on anything that changed while no watcher was running; if
+    // another process is indexing, catch up in the loop once it is done
+    match lock_index(db, lock_timeout) {
+        Some(_index_lock) => {
+            let _stats = indexer.index_all(false, None)?;
+        }
+        None => {
+            eprintln!("→ Another ccsearch process is indexing; catching up when it finishes");
+            pending_since = Some(Instant::now());
+            saw_removal = true;
+        }
+    }
+
+    eprintln!("→ Watching {} for changes...", projects_dir.display());
+
+    loop {
+        let timeout = match pending_since {
+            Some(last_event) => debounce.saturating_sub(last_event.elapsed()),
+            None => HEARTBEAT_INTERVAL,
+        };
+
+        match rx.recv_timeout(timeout.min(HEARTBEAT_INTERVAL)) {
+            Ok(Ok(event)) => {
+                if event.paths.iter().any(|p| is_session_file(p)) {
+                    saw_removal |= matches!(event.kind, EventKind::Remove(_));
+                    pending_since = Some(Instant::now());
+                }
+            }
+            Ok(Err(e)) => log::warn!("Watch error: {}", e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+            write_heartbeat(&heartbeat)?;
+            last_heartbeat = Instant::now();
+        }
+
+        if let Some(last_event) = pending_since {
+            if last_event.elapsed() >= debounce {
+                // Only one process indexes at a time; retry after another debounce period
+                let Some(_index_lock) = lock_index(db, lock_timeout) else {
+                    pending_since = Some(Instant::now());
+                    continue;
+                };
+                pending_since = None;
+                if let Err(e) = indexer.jit_index() {
+                    log::warn!("Watch index error: {}", e);
+                }
+                let mut stats = crate::indexer::IndexStats::default();
+                if std::mem::take(&mut saw_removal) {
+                    if let Err(e) = indexer.reconcile(&mut stats) {
+                        log::warn!("Watch prune error: {}", e);
+                    }
+                }
+                if let Err(e) = indexer.update_vector_indexes(&mut stats) {
+                    log::warn!("Watch vector index error: {}", e);
+                }
+                if verbose {
+                    log::info!("Index updated");
+                }
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&heartbeat);
+    Ok(())
+}
+
+/// Takes the index lock for one indexing pass, waiting up to `timeout`.
+/// None if another process is still indexing.
+fn lock_index(db: &Database, timeout: Duration) -> Option<IndexLock> {
+    match db.lock_index(timeout) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            log::info!("Another ccsearch process is indexing; will retry");
+            None
+        }
+        Err(e) => {
+            log::warn!("Could not lock the index: {}", e);
+            None
+        }
+    }
+}
+
+/// Session transcripts and sessions-index.json files are the only files indexing reads
+fn is_session_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+        || path
+            .file_name()
+            .is_some_and(|name| name == "sessions-index.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_freshness() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watch.pid");
+        assert!(!heartbeat_is_fresh(&path, SystemTime::now()));
+
+        write_heartbeat(&path).unwrap();
+        assert!(heartbeat_is_fresh(&path, SystemTime::now()));
+        assert!(!heartbeat_is_fresh(
+            &path,
+            SystemTime::now() + HEARTBEAT_STALE_AFTER + Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn test_is_session_file() {
+        assert!(is_session_file(Path::new("/p/-home-me/abc.jsonl")));
+        assert!(is_session_file(Path::new(
+            "/p/-home-me/sessions-index.json"
+        )));
+        assert!(!is_session_file(Path::new("/p/-home-me/abc.jsonl.tmp")));
+        assert!(!is_session_file(Path::new("/p/-home-me/notes.json")));
+    }
+}
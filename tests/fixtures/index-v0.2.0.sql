@@ -0,0 +1,132 @@
+-- An index.db as written by ccsearch v0.2.0 (schema copied from its
+-- src/db/schema.rs), with two sessions. Loaded by test_migrations.rs.
+
+-- Session metadata
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id TEXT PRIMARY KEY,
+    project_path TEXT NOT NULL,
+    first_prompt TEXT,
+    summary TEXT,
+    slug TEXT,
+    git_branch TEXT,
+    message_count INTEGER,
+    created_at TEXT NOT NULL,
+    modified_at TEXT NOT NULL,
+    file_mtime INTEGER NOT NULL,
+    indexed_at TEXT NOT NULL,
+    full_text TEXT NOT NULL DEFAULT ''
+);
+
+-- FTS5 virtual table for BM25 keyword search
+CREATE VIRTUAL TABLE IF NOT EXISTS sessions_fts USING fts5(
+    session_id UNINDEXED,
+    first_prompt,
+    summary,
+    full_text,
+    content='sessions',
+    content_rowid='rowid'
+);
+
+-- Index metadata for staleness tracking
+CREATE TABLE IF NOT EXISTS index_meta (
+    key TEXT PRIMARY KEY,
+    value TEXT
+);
+
+-- Conversation chunks for fine-grained search
+CREATE TABLE IF NOT EXISTS chunks (
+    chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    text TEXT NOT NULL DEFAULT '',
+    UNIQUE(session_id, chunk_index)
+);
+
+-- FTS5 on chunks for BM25 keyword search
+-- Column mapping (positional, excluding content_rowid):
+--   FTS5[0] session_id  -> chunks.session_id
+--   FTS5[1] chunk_index -> chunks.chunk_index
+--   FTS5[2] text        -> chunks.text
+CREATE VIRTUAL TABLE IF NOT EXISTS chunks_fts USING fts5(
+    session_id UNINDEXED,
+    chunk_index UNINDEXED,
+    text,
+    content='chunks',
+    content_rowid='chunk_id'
+);
+
+DROP TRIGGER IF EXISTS sessions_ai;
+CREATE TRIGGER sessions_ai AFTER INSERT ON sessions BEGIN
+    INSERT INTO sessions_fts(rowid, session_id, first_prompt, summary, full_text)
+    VALUES (new.rowid, new.session_id, new.first_prompt, new.summary, new.full_text);
+END;
+
+DROP TRIGGER IF EXISTS sessions_ad;
+CREATE TRIGGER sessions_ad AFTER DELETE ON sessions BEGIN
+    INSERT INTO sessions_fts(sessions_fts, rowid, session_id, first_prompt, summary, full_text)
+    VALUES ('delete', old.rowid, old.session_id, old.first_prompt, old.summary, old.full_text);
+END;
+
+DROP TRIGGER IF EXISTS sessions_au;
+CREATE TRIGGER sessions_au AFTER UPDATE ON sessions BEGIN
+    INSERT INTO sessions_fts(sessions_fts, rowid, session_id, first_prompt, summary, full_text)
+    VALUES ('delete', old.rowid, old.session_id, old.first_prompt, old.summary, old.full_text);
+    INSERT INTO sessions_fts(rowid, session_id, first_prompt, summary, full_text)
+    VALUES (new.rowid, new.session_id, new.first_prompt, new.summary, new.full_text);
+END;
+
+DROP TRIGGER IF EXISTS chunks_ai;
+CREATE TRIGGER chunks_ai AFTER INSERT ON chunks BEGIN
+    INSERT INTO chunks_fts(rowid, session_id, chunk_index, text)
+    VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text);
+END;
+
+DROP TRIGGER IF EXISTS chunks_ad;
+CREATE TRIGGER chunks_ad AFTER DELETE ON chunks BEGIN
+    INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text)
+    VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text);
+END;
+
+DROP TRIGGER IF EXISTS chunks_au;
+CREATE TRIGGER chunks_au AFTER UPDATE ON chunks BEGIN
+    INSERT INTO chunks_fts(chunks_fts, rowid, session_id, chunk_index, text)
+    VALUES ('delete', old.chunk_id, old.session_id, old.chunk_index, old.text);
+    INSERT INTO chunks_fts(rowid, session_id, chunk_index, text)
+    VALUES (new.chunk_id, new.session_id, new.chunk_index, new.text);
+END;
+
+CREATE TABLE IF NOT EXISTS session_embeddings (
+    session_id TEXT PRIMARY KEY,
+    embedding BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chunk_embeddings (
+    chunk_id INTEGER PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    embedding BLOB NOT NULL
+);
+
+-- Two sessions; s2 has a chunk that was never embedded
+INSERT INTO sessions (session_id, project_path, first_prompt, summary, slug, git_branch,
+                      message_count, created_at, modified_at, file_mtime, indexed_at, full_text)
+VALUES
+    ('s1', '/home/user/api', 'Fix the login timeout', 'Login timeout fix', NULL, 'main',
+     4, '2025-01-10T09:00:00Z', '2025-01-10T10:00:00Z', 1736503200, '2025-01-10T10:05:00Z',
+     'Fix the login timeout. The session token expired too early.'),
+    ('s2', '/home/user/engine', 'Why does the borrow checker reject this', NULL, NULL, NULL,
+     6, '2025-01-12T14:00:00Z', '2025-01-12T15:30:00Z', 1736695800, '2025-01-12T15:35:00Z',
+     'Why does the borrow checker reject this closure? Move the value into the closure.');
+
+INSERT INTO chunks (session_id, chunk_index, text) VALUES
+    ('s1', 0, 'Fix the login timeout. The session token expired too early.'),
+    ('s2', 0, 'Why does the borrow checker reject this closure?'),
+    ('s2', 1, 'Move the value into the closure so it outlives the thread.');
+
+-- 384-dimensional unit vectors: chunk 1 along axis 0, chunk 2 along axis 1
+INSERT INTO chunk_embeddings (chunk_id, session_id, embedding) VALUES
+    (1, 's1', unhex('0000803F' || hex(zeroblob(1532)))),
+    (2, 's2', unhex(hex(zeroblob(4)) || '0000803F' || hex(zeroblob(1528))));
+
+INSERT INTO session_embeddings (session_id, embedding) VALUES
+    ('s1', unhex('0000803F' || hex(zeroblob(1532)))),
+    ('s2', unhex(hex(zeroblob(4)) || '0000803F' || hex(zeroblob(1528))));
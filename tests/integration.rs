@@ -1,5 +1,7 @@
 mod integration {
     mod test_indexer;
+    mod test_locking;
+    mod test_migrations;
     mod test_rrf;
     mod test_search;
 }
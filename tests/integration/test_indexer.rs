@@ -46,6 +46,42 @@ fn test_parse_conversation_jsonl() {
     assert!(parsed.full_text.contains("refresh token"));
 }
 
+#[test]
+fn test_parse_tool_calls() {
+    let path = fixture_path("sample-session.jsonl");
+    let parsed =
+        ccsearch::indexer::parser::parse_conversation_jsonl(&path).unwrap();
+
+    assert_eq!(parsed.tool_calls.len(), 2);
+    assert_eq!(parsed.tool_calls[0].tool_name, "read_file");
+    assert_eq!(
+        parsed.tool_calls[0].file_path.as_deref(),
+        Some("src/auth/token.rs")
+    );
+    assert_eq!(parsed.tool_calls[1].tool_name, "edit_file");
+}
+
+#[test]
+fn test_parse_messages_and_chunk_ranges() {
+    let path = fixture_path("sample-session.jsonl");
+    let parsed = ccsearch::indexer::parser::parse_conversation_jsonl(&path).unwrap();
+
+    assert!(!parsed.messages.is_empty());
+    assert_eq!(parsed.messages[0].ordinal, 0);
+    assert_eq!(parsed.messages[0].role, "user");
+    assert_eq!(parsed.messages[1].role, "assistant");
+
+    // Small chunks should each cover a sub-range of the messages
+    let chunks = ccsearch::indexer::parser::chunk_conversation(&parsed, 200, 20);
+    assert!(chunks.len() > 1);
+    assert_eq!(chunks[0].first_message, Some(0));
+    let last = parsed.messages.last().unwrap().ordinal;
+    assert_eq!(chunks.last().unwrap().last_message, Some(last));
+    for pair in chunks.windows(2) {
+        assert!(pair[0].first_message <= pair[1].first_message);
+    }
+}
+
 #[test]
 fn test_chunk_text() {
     let text = "a".repeat(10000);
@@ -78,3 +114,71 @@ fn test_file_mtime() {
     // mtime should be a reasonable Unix timestamp (after 2020)
     assert!(mtime > 1577836800); // 2020-01-01
 }
+
+#[test]
+fn test_parse_appended_lines_from_checkpoint() {
+    use ccsearch::indexer::parser::{
+        checkpoint_matches, parse_conversation_jsonl, parse_conversation_jsonl_from,
+    };
+    use std::io::Write;
+
+    let original = std::fs::read_to_string(fixture_path("sample-session.jsonl")).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+    std::fs::write(&path, &original).unwrap();
+
+    let first = parse_conversation_jsonl(&path).unwrap();
+    assert_eq!(first.checkpoint.byte_offset, original.len() as u64);
+    assert_eq!(first.checkpoint.message_count, first.messages.len());
+    assert!(checkpoint_matches(&path, &first.checkpoint).unwrap());
+
+    // A trailing line that is still being written is left for the next run
+    let appended = r#"{"type":"user","uuid":"appended-1","message":{"role":"user","content":"Now add rate limiting to the login endpoint"},"timestamp":"2025-01-15T11:00:00.000Z"}"#;
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    write!(file, "{}", &appended[..40]).unwrap();
+    file.flush().unwrap();
+
+    let partial = parse_conversation_jsonl_from(&path, Some(&first.checkpoint)).unwrap();
+    assert!(partial.messages.is_empty());
+    assert_eq!(partial.checkpoint, first.checkpoint);
+
+    writeln!(file, "{}", &appended[40..]).unwrap();
+    file.flush().unwrap();
+
+    let next = parse_conversation_jsonl_from(&path, Some(&first.checkpoint)).unwrap();
+    assert_eq!(next.messages.len(), 1);
+    assert_eq!(next.messages[0].ordinal, first.messages.len());
+    assert!(next.full_text.contains("rate limiting"));
+    assert!(next.first_prompt.is_some());
+    assert_eq!(next.checkpoint.message_count, first.messages.len() + 1);
+    assert!(checkpoint_matches(&path, &first.checkpoint).unwrap());
+
+    // Rewriting the file invalidates the checkpoint
+    std::fs::write(&path, original.replacen("401", "403", 1)).unwrap();
+    assert!(!checkpoint_matches(&path, &first.checkpoint).unwrap());
+}
+
+#[test]
+fn test_rewrite_before_the_last_4kb_invalidates_checkpoint() {
+    use ccsearch::indexer::parser::{checkpoint_matches, parse_conversation_jsonl};
+
+    // Over 4 KB of later lines, so the edit is far from the checkpoint offset
+    let mut original = std::fs::read_to_string(fixture_path("sample-session.jsonl")).unwrap();
+    for i in 0..100 {
+        original.push_str(&format!(
+            "{{\"type\":\"human\",\"message\":{{\"role\":\"user\",\"content\":\"follow-up question {}\"}}}}\n",
+            i
+        ));
+    }
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.jsonl");
+    std::fs::write(&path, &original).unwrap();
+    let first = parse_conversation_jsonl(&path).unwrap();
+    assert!(original.len() > 8192);
+
+    // Same length, same last 4 KB, different first line
+    let rewritten = original.replacen("401", "403", 1);
+    assert_eq!(rewritten.len(), original.len());
+    std::fs::write(&path, &rewritten).unwrap();
+    assert!(!checkpoint_matches(&path, &first.checkpoint).unwrap());
+}
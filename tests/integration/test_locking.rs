@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use ccsearch::config::Config;
+use ccsearch::db::Database;
+use ccsearch::indexer::parser::ParsedSession;
+
+fn session(session_id: &str) -> ParsedSession {
+    ParsedSession {
+        session_id: session_id.to_string(),
+        project_path: "/home/user/api".to_string(),
+        first_prompt: Some("retry the flaky upload".to_string()),
+        summary: None,
+        slug: None,
+        git_branch: None,
+        message_count: 2,
+        created_at: "2026-02-15T10:00:00Z".to_string(),
+        modified_at: "2026-02-15T10:00:00Z".to_string(),
+        full_text: "retry the flaky upload with backoff".to_string(),
+    }
+}
+
+fn session_ids(db: &Database) -> Vec<String> {
+    db.list_sessions(None, None, 100)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.session_id)
+        .collect()
+}
+
+#[test]
+fn test_one_writer_while_readers_see_last_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let config = Config::default();
+    let writer = Database::open(&path, &config).unwrap();
+    let reader = Database::open(&path, &config).unwrap();
+
+    let now = chrono::Utc::now().to_rfc3339();
+    writer
+        .upsert_session(&session("committed"), 0, &now)
+        .unwrap();
+
+    // The writer holds the index lock with a write in progress
+    let lock = writer.lock_index(Duration::ZERO).unwrap();
+    assert!(lock.is_some());
+    let tx = writer.write_transaction().unwrap();
+    writer
+        .upsert_session(&session("in-progress"), 0, &now)
+        .unwrap();
+
+    // A second indexer gives up after the timeout...
+    assert!(reader
+        .lock_index(Duration::from_millis(50))
+        .unwrap()
+        .is_none());
+
+    // ...while searches read the last commit without waiting
+    assert_eq!(session_ids(&reader), vec!["committed".to_string()]);
+    let results = reader
+        .fts_search_filtered("flaky", 10, &Default::default())
+        .unwrap();
+    assert_eq!(results.len(), 1);
+
+    tx.commit().unwrap();
+    drop(lock);
+
+    let mut ids = session_ids(&reader);
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec!["committed".to_string(), "in-progress".to_string()]
+    );
+    assert!(reader.lock_index(Duration::ZERO).unwrap().is_some());
+}
+
+#[test]
+fn test_reader_opens_while_a_write_is_in_progress() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let writer = Database::open(&path, &Config::default()).unwrap();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let lock = writer.lock_index(Duration::ZERO).unwrap();
+    assert!(lock.is_some());
+    writer
+        .upsert_session(&session("committed"), 0, &now)
+        .unwrap();
+    let tx = writer.write_transaction().unwrap();
+    writer
+        .upsert_session(&session("in-progress"), 0, &now)
+        .unwrap();
+
+    // Opening an up-to-date index writes nothing, so it succeeds without
+    // waiting for the write even when it won't wait at all
+    let config = Config {
+        busy_timeout_ms: 0,
+        ..Default::default()
+    };
+    let reader = Database::open_read_only(&path, &config).unwrap();
+    assert_eq!(session_ids(&reader), vec!["committed".to_string()]);
+
+    // Until it takes the index lock, the reader can't write
+    assert!(reader.upsert_session(&session("stray"), 0, &now).is_err());
+    assert!(reader.lock_index(Duration::ZERO).unwrap().is_none());
+
+    tx.commit().unwrap();
+    drop(lock);
+
+    let _lock = reader.lock_index(Duration::ZERO).unwrap().unwrap();
+    reader.upsert_session(&session("stray"), 0, &now).unwrap();
+    assert_eq!(session_ids(&reader).len(), 3);
+}
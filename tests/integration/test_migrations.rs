@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use ccsearch::config::Config;
+use ccsearch::db::migrations::{self, SCHEMA_VERSION};
+use ccsearch::db::Database;
+use ccsearch::search::bm25;
+use ccsearch::search::filter::SearchFilter;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Writes the v0.2.0 fixture database to `path`
+fn create_v0_2_0_db(path: &Path) {
+    let sql = std::fs::read_to_string(fixture_path("index-v0.2.0.sql")).unwrap();
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.execute_batch("PRAGMA journal_mode=WAL;").unwrap();
+    conn.execute_batch(&sql).unwrap();
+}
+
+fn schema_version(conn: &rusqlite::Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM index_meta WHERE key = 'schema_version'",
+        [],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+fn axis(i: usize) -> Vec<f32> {
+    let mut v = vec![0.0; 384];
+    v[i] = 1.0;
+    v
+}
+
+#[test]
+fn test_v0_2_0_database_upgraded_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    create_v0_2_0_db(&path);
+
+    let db = Database::open(&path, &Config::default()).unwrap();
+    assert_eq!(schema_version(db.conn()), Some(SCHEMA_VERSION.to_string()));
+
+    // Sessions, chunks and vectors survive
+    let session = db.get_session("s2").unwrap().unwrap();
+    assert_eq!(session.project_path, "/home/user/engine");
+    assert_eq!(session.message_count, Some(6));
+    let filter = SearchFilter::default();
+    let found = bm25::search(&db, "borrow checker", 10, false, &filter).unwrap();
+    assert_eq!(found.results[0].session_id, "s2");
+    let found = bm25::search(&db, "timeout", 10, false, &filter).unwrap();
+    assert_eq!(found.results[0].session_id, "s1");
+
+    let nearest = db.vec_search(&axis(1), 2, &filter, None).unwrap();
+    assert_eq!(nearest[0].session_id, "s2");
+    assert!(nearest[0].distance.abs() < 1e-6);
+
+    // Only the chunk that never had a vector waits for embedding
+    assert_eq!(db.count_pending_chunks().unwrap(), 1);
+    assert_eq!(
+        db.get_pending_chunks(10).unwrap()[0].text,
+        "Move the value into the closure so it outlives the thread."
+    );
+
+    // Tables added since v0.2.0 work
+    let call = ccsearch::indexer::parser::ToolCall {
+        tool_name: "Bash".to_string(),
+        file_path: None,
+        command: Some("cargo test".to_string()),
+        pattern: None,
+    };
+    db.insert_tool_call("s2", 0, &call).unwrap();
+    let filter = SearchFilter {
+        command: Some("cargo".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(db.filter_sessions(&filter, 10).unwrap(), vec!["s2"]);
+    assert!(db.get_parse_checkpoint("s1").unwrap().is_none());
+    db.set_session_orphaned("s1", true).unwrap();
+    drop(db);
+
+    // The backup is the untouched v0.2.0 database
+    let backup = migrations::backup_path(&path, 1);
+    assert_eq!(backup, dir.path().join("index.db.v1.bak"));
+    let old = rusqlite::Connection::open(&backup).unwrap();
+    assert_eq!(schema_version(&old), None);
+    let sessions: i64 = old
+        .query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(sessions, 2);
+    let has_messages: bool = old
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'messages')",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(!has_messages);
+    drop(old);
+
+    // A current database is opened as is, without another backup
+    std::fs::remove_file(&backup).unwrap();
+    let db = Database::open(&path, &Config::default()).unwrap();
+    assert!(!backup.exists());
+    assert_eq!(schema_version(db.conn()), Some(SCHEMA_VERSION.to_string()));
+    assert_eq!(db.list_indexed_sessions().unwrap().len(), 2);
+}
+
+#[test]
+fn test_database_from_newer_version_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    {
+        let db = Database::open(&path, &Config::default()).unwrap();
+        db.set_meta("schema_version", &(SCHEMA_VERSION + 1).to_string())
+            .unwrap();
+    }
+
+    let err = Database::open(&path, &Config::default())
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(err.contains("newer ccsearch"));
+    assert!(!migrations::backup_path(&path, SCHEMA_VERSION + 1).exists());
+}
+
+#[test]
+fn test_new_database_not_backed_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let db = Database::open(&path, &Config::default()).unwrap();
+    assert_eq!(schema_version(db.conn()), Some(SCHEMA_VERSION.to_string()));
+    let files: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".bak"))
+        .collect();
+    assert!(files.is_empty());
+}
@@ -7,9 +7,35 @@ fn fixture_path(name: &str) -> PathBuf {
         .join(name)
 }
 
+/// Helper to build a chunk without a message range
+fn chunk(
+    kind: ccsearch::indexer::parser::ChunkKind,
+    text: &str,
+) -> ccsearch::indexer::parser::Chunk {
+    ccsearch::indexer::parser::Chunk {
+        kind,
+        text: text.to_string(),
+        first_message: None,
+        last_message: None,
+    }
+}
+
+/// Helper to run a keyword search without filters
+fn fts(
+    db: &ccsearch::db::Database,
+    query: &str,
+) -> anyhow::Result<Vec<ccsearch::db::queries::FtsResult>> {
+    db.fts_search_filtered(query, 10, &Default::default())
+}
+
 /// Helper to set up a test database with fixture data
 fn setup_test_db() -> ccsearch::db::Database {
-    let db = ccsearch::db::Database::open_in_memory().unwrap();
+    setup_test_db_with(&ccsearch::config::Config::default())
+}
+
+/// Helper to set up a test database with fixture data and optional indexes from `config`
+fn setup_test_db_with(config: &ccsearch::config::Config) -> ccsearch::db::Database {
+    let db = ccsearch::db::Database::open_in_memory_with(config).unwrap();
 
     let index_path = fixture_path("sessions-index.json");
     let entries = ccsearch::indexer::parser::parse_session_index(&index_path).unwrap();
@@ -58,7 +84,7 @@ fn setup_test_db() -> ccsearch::db::Database {
 fn test_fts_search_auth() {
     let db = setup_test_db();
 
-    let results = db.fts_search("authentication", 10).unwrap();
+    let results = fts(&db, "authentication").unwrap();
     assert!(
         !results.is_empty(),
         "Should find results for 'authentication'"
@@ -73,7 +99,7 @@ fn test_fts_search_auth() {
 fn test_fts_search_dark_mode() {
     let db = setup_test_db();
 
-    let results = db.fts_search("\"dark mode\"", 10).unwrap();
+    let results = fts(&db, "\"dark mode\"").unwrap();
     assert!(!results.is_empty(), "Should find results for 'dark mode'");
     assert_eq!(
         results[0].session_id, "def67890-aaaa-bbbb-cccc-ddddeeeeffff",
@@ -85,7 +111,7 @@ fn test_fts_search_dark_mode() {
 fn test_fts_search_database() {
     let db = setup_test_db();
 
-    let results = db.fts_search("database", 10).unwrap();
+    let results = fts(&db, "database").unwrap();
     assert!(!results.is_empty(), "Should find results for 'database'");
 
     // The DB refactor session should appear in results
@@ -100,7 +126,7 @@ fn test_fts_search_database() {
 fn test_fts_search_no_results() {
     let db = setup_test_db();
 
-    let results = db.fts_search("\"zzzznonexistent1234\"", 10).unwrap();
+    let results = fts(&db, "\"zzzznonexistent1234\"").unwrap();
     assert!(results.is_empty(), "Should return no results for gibberish");
 }
 
@@ -123,6 +149,41 @@ fn test_list_sessions_with_project_filter() {
     assert!(sessions.is_empty(), "No sessions for nonexistent project");
 }
 
+#[test]
+fn test_list_days_match_search_days() {
+    use ccsearch::search::filter::SearchFilter;
+
+    let db = setup_test_db();
+    let now = chrono::Utc::now();
+    // Started a month ago, resumed today
+    let session = ccsearch::indexer::parser::ParsedSession {
+        session_id: "resumed".to_string(),
+        project_path: "/home/user/api".to_string(),
+        first_prompt: Some("resume the flamegraph profiling".to_string()),
+        summary: None,
+        slug: None,
+        git_branch: None,
+        message_count: 2,
+        created_at: (now - chrono::Duration::days(30)).to_rfc3339(),
+        modified_at: now.to_rfc3339(),
+        full_text: "resume the flamegraph profiling".to_string(),
+    };
+    db.upsert_session(&session, 0, &now.to_rfc3339()).unwrap();
+
+    let listed: Vec<String> = db
+        .list_sessions(Some(7), None, 100)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.session_id)
+        .collect();
+    assert_eq!(listed, vec!["resumed".to_string()]);
+
+    let filter = SearchFilter::default().with_days(7);
+    let found = db.fts_search_filtered("flamegraph", 10, &filter).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].session_id, "resumed");
+}
+
 #[test]
 fn test_get_session() {
     let db = setup_test_db();
@@ -179,6 +240,1138 @@ fn test_upsert_replaces_existing() {
     assert_eq!(updated.summary.as_deref(), Some("Updated summary"));
 
     // FTS should also be updated
-    let results = db.fts_search("\"Updated summary\"", 10).unwrap();
+    let results = fts(&db, "\"Updated summary\"").unwrap();
     assert!(!results.is_empty());
 }
+
+#[test]
+fn test_tool_call_search_and_filter() {
+    use ccsearch::indexer::parser::{ChunkKind, ToolCall};
+    use ccsearch::search::filter::SearchFilter;
+
+    let db = setup_test_db();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+    let settings_id = "def67890-aaaa-bbbb-cccc-ddddeeeeffff";
+
+    db.insert_chunk(
+        auth_id,
+        0,
+        &chunk(
+            ChunkKind::Conversation,
+            "User: the login token expires immediately",
+        ),
+    )
+    .unwrap();
+    db.insert_chunk(
+        settings_id,
+        0,
+        &chunk(
+            ChunkKind::Conversation,
+            "User: add a dark mode toggle to the login page",
+        ),
+    )
+    .unwrap();
+    db.insert_tool_call(
+        auth_id,
+        0,
+        &ToolCall {
+            tool_name: "Edit".to_string(),
+            file_path: Some("src/auth/token.rs".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    db.insert_tool_call(
+        settings_id,
+        0,
+        &ToolCall {
+            tool_name: "Bash".to_string(),
+            command: Some("cargo flamegraph --bin web".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // Shell commands are searchable as free text
+    let results = fts(&db, "flamegraph").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].session_id, settings_id);
+
+    // Filters restrict the candidate sessions
+    let filter = SearchFilter {
+        file: Some("auth/token".to_string()),
+        ..Default::default()
+    };
+    let results = db.fts_search_filtered("login", 10, &filter).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].session_id, auth_id);
+
+    let filter = SearchFilter {
+        tool: Some("bash".to_string()),
+        command: Some("flamegraph".to_string()),
+        ..Default::default()
+    };
+    let results = db.fts_search_filtered("login", 10, &filter).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].session_id, settings_id);
+}
+
+#[test]
+fn test_tool_output_chunks() {
+    use ccsearch::indexer::parser::ChunkKind;
+
+    let db = setup_test_db();
+    let db_session = "ghi11111-2222-3333-4444-555566667777";
+
+    db.insert_chunk(
+        db_session,
+        0,
+        &chunk(ChunkKind::Conversation, "User: the dashboard query is slow"),
+    )
+    .unwrap();
+    db.insert_chunk(
+        db_session,
+        1,
+        &chunk(
+            ChunkKind::ToolOutput,
+            "Tool output: error[E0599]: no method named `fetch_all` found",
+        ),
+    )
+    .unwrap();
+
+    let results = fts(&db, "fetch_all").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].session_id, db_session);
+
+    let matched = db
+        .get_best_matching_chunk("E0599", db_session)
+        .unwrap()
+        .unwrap();
+    assert_eq!(matched.kind, ChunkKind::ToolOutput);
+    assert!(matched.text.contains("fetch_all"));
+}
+
+#[test]
+fn test_messages_roundtrip() {
+    let db = setup_test_db();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+
+    let parsed =
+        ccsearch::indexer::parser::parse_conversation_jsonl(&fixture_path("sample-session.jsonl"))
+            .unwrap();
+    for message in &parsed.messages {
+        db.insert_message(auth_id, message).unwrap();
+    }
+
+    assert_eq!(
+        db.get_session_message_total(auth_id).unwrap(),
+        parsed.message_count as i64
+    );
+
+    let messages = db.get_messages_in_range(auth_id, 0, 1).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].role, "user");
+    assert!(messages[0].text.contains("401 error"));
+    assert_eq!(messages[1].role, "assistant");
+}
+
+#[test]
+fn test_orphaned_sessions_hidden_and_deleted() {
+    use ccsearch::indexer::parser::ChunkKind;
+
+    let db = setup_test_db();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+
+    db.insert_chunk(
+        auth_id,
+        0,
+        &chunk(
+            ChunkKind::Conversation,
+            "the refresh token expires immediately",
+        ),
+    )
+    .unwrap();
+    let results = fts(&db, "refresh").unwrap();
+    assert_eq!(results.len(), 1);
+
+    // Marked sessions stay indexed but are hidden from search and listing
+    db.set_session_orphaned(auth_id, true).unwrap();
+    assert!(fts(&db, "refresh").unwrap().is_empty());
+    assert_eq!(db.list_sessions(None, None, 100).unwrap().len(), 2);
+    assert!(db
+        .list_indexed_sessions()
+        .unwrap()
+        .contains(&(auth_id.to_string(), true)));
+
+    db.set_session_orphaned(auth_id, false).unwrap();
+    assert_eq!(fts(&db, "refresh").unwrap().len(), 1);
+
+    // Deleting removes the session and everything derived from it
+    db.delete_session(auth_id).unwrap();
+    assert!(db.get_session(auth_id).unwrap().is_none());
+    assert!(fts(&db, "refresh").unwrap().is_empty());
+    assert_eq!(db.next_chunk_index(auth_id).unwrap(), 0);
+    assert_eq!(db.list_indexed_sessions().unwrap().len(), 2);
+}
+
+#[test]
+fn test_chunks_pending_embedding_until_vector_stored() {
+    use ccsearch::indexer::parser::ChunkKind;
+
+    let db = setup_test_db();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+    assert_eq!(db.count_pending_chunks().unwrap(), 0);
+
+    let first = db
+        .insert_chunk(auth_id, 0, &chunk(ChunkKind::Conversation, "first chunk"))
+        .unwrap();
+    let second = db
+        .insert_chunk(auth_id, 1, &chunk(ChunkKind::Conversation, "second chunk"))
+        .unwrap();
+    assert_eq!(db.count_pending_chunks().unwrap(), 2);
+
+    // Newest first
+    let pending = db.get_pending_chunks(10).unwrap();
+    let ids: Vec<i64> = pending.iter().map(|c| c.chunk_id).collect();
+    assert_eq!(ids, vec![second, first]);
+    assert_eq!(pending[0].session_id, auth_id);
+    assert_eq!(pending[0].text, "second chunk");
+
+    db.upsert_chunk_embedding(second, auth_id, &[1.0, 0.0]).unwrap();
+    let pending = db.get_pending_chunks(10).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].chunk_id, first);
+    assert_eq!(db.get_pending_chunks(0).unwrap().len(), 0);
+}
+
+#[test]
+fn test_session_metadata_filters() {
+    use ccsearch::search::filter::{SearchFilter, SessionKind};
+
+    let db = setup_test_db();
+    let now = chrono::Utc::now();
+
+    // An agent session in another project, active just now
+    let agent = ccsearch::indexer::parser::ParsedSession {
+        session_id: "agent-1234abcd".to_string(),
+        project_path: "/Users/user/projects/api-server".to_string(),
+        first_prompt: Some("Investigate the authentication middleware".to_string()),
+        summary: None,
+        slug: None,
+        git_branch: Some("main".to_string()),
+        message_count: 2,
+        created_at: now.to_rfc3339(),
+        modified_at: now.to_rfc3339(),
+        full_text: "authentication middleware".to_string(),
+    };
+    db.upsert_session(&agent, 0, &now.to_rfc3339()).unwrap();
+
+    let ids = |filter: &SearchFilter| -> Vec<String> {
+        let mut ids: Vec<String> = db
+            .fts_search_filtered("authentication", 10, filter)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.session_id)
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    assert_eq!(ids(&SearchFilter::default()).len(), 2);
+
+    let filter = SearchFilter {
+        kind: Some(SessionKind::Agent),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["agent-1234abcd"]);
+
+    let filter = SearchFilter {
+        kind: Some(SessionKind::Main),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["abc12345-1111-2222-3333-444455556666"]);
+
+    // Project: substring, or glob when it has wildcards
+    let filter = SearchFilter {
+        project: Some("webapp".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["abc12345-1111-2222-3333-444455556666"]);
+    let filter = SearchFilter {
+        project: Some("*/api-*".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["agent-1234abcd"]);
+
+    let filter = SearchFilter {
+        branch: Some("fix/*".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["abc12345-1111-2222-3333-444455556666"]);
+
+    // Fixture sessions were last active in February 2026
+    let filter = SearchFilter::default().with_days(1);
+    assert_eq!(ids(&filter), vec!["agent-1234abcd"]);
+    let filter = SearchFilter {
+        before: Some(now - chrono::Duration::days(1)),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["abc12345-1111-2222-3333-444455556666"]);
+    assert_eq!(ids(&SearchFilter::default().with_days(0)).len(), 2);
+
+    let filter = SearchFilter {
+        exclude_projects: vec!["API-server".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["abc12345-1111-2222-3333-444455556666"]);
+}
+
+#[test]
+fn test_query_qualifiers_filter_search() {
+    use ccsearch::search::filter::SearchFilter;
+    use ccsearch::search::query::parse_query;
+
+    let db = setup_test_db();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+
+    let parsed =
+        ccsearch::indexer::parser::parse_conversation_jsonl(&fixture_path("sample-session.jsonl"))
+            .unwrap();
+    for message in &parsed.messages {
+        db.insert_message(auth_id, message).unwrap();
+    }
+
+    let search = |query: &str| -> Vec<String> {
+        let parsed = parse_query(query).unwrap();
+        let mut filter = SearchFilter::default();
+        parsed.apply(&mut filter);
+        if parsed.text.is_empty() {
+            return db.filter_sessions(&filter, 10).unwrap();
+        }
+        db.fts_search_filtered(&parsed.text, 10, &filter)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.session_id)
+            .collect()
+    };
+
+    assert_eq!(search("authentication"), vec![auth_id]);
+    assert!(search("authentication -token").is_empty());
+    assert_eq!(search("authentication -kubernetes"), vec![auth_id]);
+    assert_eq!(search("authentication id:abc123"), vec![auth_id]);
+    assert!(search("authentication id:zzz").is_empty());
+
+    // The user asked about the 401, the assistant answered
+    assert_eq!(search("401 role:user"), vec![auth_id]);
+    assert!(search("401 role:assistant").is_empty());
+    // Operators apply within the role's messages: OR is not read as a word
+    assert_eq!(search("401 OR kubernetes role:user"), vec![auth_id]);
+    assert!(search("401 OR kubernetes role:assistant").is_empty());
+    assert!(search("(401 -token) role:user").is_empty());
+
+    // Qualifiers alone list matching sessions
+    assert_eq!(search("id:abc12345"), vec![auth_id]);
+    assert_eq!(search("project:webapp before:2030-01-01").len(), 3);
+    assert!(search("after:2030-01-01").is_empty());
+}
+
+#[test]
+fn test_bm25_relaxes_queries_without_full_match() {
+    use ccsearch::search::bm25::{self, Relaxation};
+    use ccsearch::search::filter::SearchFilter;
+
+    let db = setup_test_db();
+    let filter = SearchFilter::default();
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+
+    let strict = bm25::search(&db, "authentication token", 10, false, &filter).unwrap();
+    assert_eq!(strict.relaxation, Relaxation::AllTerms);
+    assert_eq!(strict.results[0].session_id, auth_id);
+
+    // One misremembered word: drop it and keep the rest AND-ed
+    let dropped = bm25::search(&db, "authentication token kubernetes", 10, false, &filter).unwrap();
+    assert_eq!(
+        dropped.relaxation,
+        Relaxation::DroppedTerms {
+            dropped: vec!["kubernetes".to_string()]
+        }
+    );
+    assert_eq!(dropped.results[0].session_id, auth_id);
+    assert!(dropped
+        .relaxation
+        .describe()
+        .unwrap()
+        .contains("ignoring: kubernetes"));
+
+    // Two terms that never co-occur: any term, sessions matching more terms first
+    let any = bm25::search(&db, "authentication kubernetes", 10, false, &filter).unwrap();
+    assert_eq!(any.relaxation, Relaxation::AnyTerm);
+    assert_eq!(any.results.len(), 1);
+    assert_eq!(any.results[0].session_id, auth_id);
+
+    // Nothing matches at all, and exact phrases are never relaxed
+    let none = bm25::search(&db, "kubernetes helm", 10, false, &filter).unwrap();
+    assert!(none.results.is_empty());
+    assert_eq!(none.relaxation, Relaxation::AllTerms);
+    let exact = bm25::search(&db, "authentication kubernetes", 10, true, &filter).unwrap();
+    assert!(exact.results.is_empty());
+    assert_eq!(exact.relaxation, Relaxation::AllTerms);
+}
+
+#[test]
+fn test_trigram_index_matches_typos() {
+    use ccsearch::indexer::parser::ChunkKind;
+    use ccsearch::search::filter::SearchFilter;
+    use ccsearch::search::{hybrid_search, SearchOptions};
+
+    let config = ccsearch::config::Config {
+        trigram_index: true,
+        ..Default::default()
+    };
+    let db = setup_test_db_with(&config);
+    let auth_id = "abc12345-1111-2222-3333-444455556666";
+    db.insert_chunk(
+        auth_id,
+        0,
+        &chunk(ChunkKind::Conversation, "the authentication token expires immediately"),
+    )
+    .unwrap();
+    assert!(db.has_trigram_index().unwrap());
+
+    let options = SearchOptions {
+        limit: 10,
+        bm25_weight: config.bm25_weight,
+        vec_weight: 0.0,
+        rrf_k: config.rrf_k,
+        fuzzy_weight: config.fuzzy_weight,
+        recency_halflife: 0.0,
+        exact: false,
+        ann_probes: config.ann_probes,
+    };
+    let results = hybrid_search(
+        &db,
+        None,
+        "authentcation",
+        &options,
+        &SearchFilter::default(),
+    )
+    .unwrap();
+    assert_eq!(results[0].session_id, auth_id);
+    assert_eq!(results[0].bm25_rank, None);
+    assert_eq!(results[0].fuzzy_rank, Some(1));
+
+    // A chunk sharing only a trigram or two with the typo ("aut", "ion") is not a match
+    let billing_id = "def67890-aaaa-bbbb-cccc-ddddeeeeffff";
+    db.insert_chunk(
+        billing_id,
+        0,
+        &chunk(ChunkKind::Conversation, "autoscaling configuration"),
+    )
+    .unwrap();
+    let fuzzy = db
+        .fuzzy_search("authentcation", 10, &SearchFilter::default())
+        .unwrap();
+    assert_eq!(fuzzy.len(), 1);
+    assert_eq!(fuzzy[0].session_id, auth_id);
+
+    // Without the fuzzy leg the typo finds nothing
+    let strict = SearchOptions {
+        fuzzy_weight: 0.0,
+        ..options
+    };
+    let results =
+        hybrid_search(&db, None, "authentcation", &strict, &SearchFilter::default()).unwrap();
+    assert!(results.is_empty());
+}
+
+#[test]
+fn test_trigram_index_follows_config() {
+    use ccsearch::config::Config;
+    use ccsearch::indexer::parser::ChunkKind;
+    use ccsearch::search::filter::SearchFilter;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let enabled = Config {
+        trigram_index: true,
+        ..Default::default()
+    };
+
+    {
+        let db = ccsearch::db::Database::open(&path, &Config::default()).unwrap();
+        assert!(!db.has_trigram_index().unwrap());
+        let now = chrono::Utc::now().to_rfc3339();
+        let session = ccsearch::indexer::parser::ParsedSession {
+            session_id: "s1".to_string(),
+            project_path: "/test".to_string(),
+            first_prompt: None,
+            summary: None,
+            slug: None,
+            git_branch: None,
+            message_count: 1,
+            created_at: now.clone(),
+            modified_at: now.clone(),
+            full_text: String::new(),
+        };
+        db.upsert_session(&session, 0, &now).unwrap();
+        db.insert_chunk("s1", 0, &chunk(ChunkKind::Conversation, "postgres migration"))
+            .unwrap();
+    }
+
+    // Enabling it indexes chunks that already exist
+    {
+        let db = ccsearch::db::Database::open(&path, &enabled).unwrap();
+        let results = db
+            .fuzzy_search("postgress", 10, &SearchFilter::default())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].session_id, "s1");
+    }
+
+    let db = ccsearch::db::Database::open(&path, &Config::default()).unwrap();
+    assert!(!db.has_trigram_index().unwrap());
+}
+
+#[test]
+fn test_code_identifiers_match_their_parts() {
+    use ccsearch::indexer::parser::ChunkKind;
+    use ccsearch::search::bm25;
+    use ccsearch::search::filter::SearchFilter;
+
+    let db = setup_test_db();
+    let db_session = "ghi11111-2222-3333-4444-555566667777";
+    db.insert_chunk(
+        db_session,
+        0,
+        &chunk(
+            ChunkKind::Conversation,
+            "Changed getUserById in src/db/queries.rs to respect max_text_chars",
+        ),
+    )
+    .unwrap();
+
+    let filter = SearchFilter::default();
+    for query in ["user by id", "\"user by id\"", "UserById", "queries", "text chars"] {
+        let found = bm25::search(&db, query, 10, false, &filter).unwrap();
+        assert_eq!(
+            found.results.first().map(|r| r.session_id.as_str()),
+            Some(db_session),
+            "query {:?}",
+            query
+        );
+    }
+}
+
+#[test]
+fn test_chunks_fts_upgraded_with_code_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+
+    // A chunks table and FTS index as created before code tokens existed
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chunks (
+                chunk_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                text TEXT NOT NULL DEFAULT '',
+                UNIQUE(session_id, chunk_index)
+            );
+            CREATE VIRTUAL TABLE chunks_fts USING fts5(
+                session_id UNINDEXED, chunk_index UNINDEXED, text,
+                content='chunks', content_rowid='chunk_id'
+            );
+            INSERT INTO chunks (session_id, chunk_index, text) VALUES ('s1', 0, 'fix parseConfigFile');
+            INSERT INTO chunks_fts(chunks_fts) VALUES ('rebuild');",
+        )
+        .unwrap();
+    }
+
+    let db = ccsearch::db::Database::open(&path, &ccsearch::config::Config::default()).unwrap();
+    let count: i64 = db
+        .conn()
+        .query_row(
+            "SELECT count(*) FROM chunks_fts WHERE chunks_fts MATCH '\"config file\"'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_tokenizer_change_rebuilds_keyword_index() {
+    use ccsearch::config::Config;
+    use ccsearch::db::Database;
+    use ccsearch::indexer::parser::ChunkKind;
+    use ccsearch::search::bm25;
+    use ccsearch::search::filter::SearchFilter;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let filter = SearchFilter::default();
+    let found = |db: &Database, query: &str| {
+        !bm25::search(db, query, 10, false, &filter)
+            .unwrap()
+            .results
+            .is_empty()
+    };
+
+    {
+        let db = Database::open(&path, &Config::default()).unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        let session = ccsearch::indexer::parser::ParsedSession {
+            session_id: "s1".to_string(),
+            project_path: "/test".to_string(),
+            first_prompt: Some("Migrating the schema".to_string()),
+            summary: None,
+            slug: None,
+            git_branch: None,
+            message_count: 1,
+            created_at: now.clone(),
+            modified_at: now.clone(),
+            full_text: String::new(),
+        };
+        db.upsert_session(&session, 0, &now).unwrap();
+        db.insert_chunk(
+            "s1",
+            0,
+            &chunk(ChunkKind::Conversation, "we are migrating the café 数据库迁移 scripts"),
+        )
+        .unwrap();
+
+        assert!(found(&db, "cafe"));
+        assert!(!found(&db, "migration"));
+        assert!(!found(&db, "据库迁"));
+    }
+
+    let config = Config {
+        stemming: true,
+        remove_diacritics: 0,
+        cjk_bigrams: true,
+        ..Default::default()
+    };
+    let db = Database::open(&path, &config).unwrap();
+    assert_eq!(
+        db.conn()
+            .query_row(
+                "SELECT value FROM index_meta WHERE key = 'fts_tokenizer'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap(),
+        "porter unicode61 remove_diacritics 0 +cjk_bigrams"
+    );
+    assert!(found(&db, "migration"));
+    assert!(found(&db, "据库迁"));
+    assert!(!found(&db, "cafe"));
+
+    // New chunks are tokenized the same way
+    db.insert_chunk("s1", 1, &chunk(ChunkKind::Conversation, "修复日志"))
+        .unwrap();
+    assert!(found(&db, "日志"));
+}
+
+/// Unit vector near `axis`, with some noise on every dimension
+fn noisy_unit_vector(rng: &mut fastrand::Rng, dim: usize, axis: usize) -> Vec<f32> {
+    let mut v: Vec<f32> = (0..dim).map(|_| rng.f32() * 0.3).collect();
+    v[axis] += 1.0;
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    v.iter().map(|x| x / norm).collect()
+}
+
+/// Helper to store a session with one embedded chunk per embedding
+fn add_embedded_session(db: &ccsearch::db::Database, session_id: &str, embeddings: &[Vec<f32>]) {
+    use ccsearch::indexer::parser::ChunkKind;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let session = ccsearch::indexer::parser::ParsedSession {
+        session_id: session_id.to_string(),
+        project_path: "/test".to_string(),
+        first_prompt: None,
+        summary: None,
+        slug: None,
+        git_branch: None,
+        message_count: 1,
+        created_at: now.clone(),
+        modified_at: now.clone(),
+        full_text: String::new(),
+    };
+    db.upsert_session(&session, 0, &now).unwrap();
+    for (i, embedding) in embeddings.iter().enumerate() {
+        let chunk_id = db
+            .insert_chunk(
+                session_id,
+                i as i32,
+                &chunk(ChunkKind::Conversation, "text"),
+            )
+            .unwrap();
+        db.upsert_chunk_embedding(chunk_id, session_id, embedding)
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_ann_index_probes_nearest_lists() {
+    use ccsearch::config::Config;
+    use ccsearch::search::filter::SearchFilter;
+    use ccsearch::search::{ann, vector};
+
+    const DIM: usize = 16;
+    let config = Config {
+        ann_min_vectors: 50,
+        ann_probes: 3,
+        ..Default::default()
+    };
+    let db = ccsearch::db::Database::open_in_memory_with(&config).unwrap();
+    let mut rng = fastrand::Rng::with_seed(0xa11);
+    let mut near = |axis: usize| noisy_unit_vector(&mut rng, DIM, axis);
+    let add_session = |session_id: &str, embeddings: &[Vec<f32>]| {
+        add_embedded_session(&db, session_id, embeddings)
+    };
+
+    for s in 0..40 {
+        let embeddings: Vec<Vec<f32>> = (0..5).map(|_| near(s % DIM)).collect();
+        add_session(&format!("s{}", s), &embeddings);
+    }
+
+    // Too few embeddings: no index, searches compare everything
+    let small = Config {
+        ann_min_vectors: 1000,
+        ann_probes: config.ann_probes,
+        ..Default::default()
+    };
+    assert_eq!(ann::maintain(&db, &small).unwrap(), 0);
+
+    let lists = ann::maintain(&db, &config).unwrap();
+    assert_eq!(lists, ann::list_count(200));
+    assert!(db.get_unassigned_chunk_embeddings(10).unwrap().is_empty());
+
+    // Probing the nearest lists finds the same best matches as a full scan
+    let filter = SearchFilter::default();
+    for axis in [0, 5, 11] {
+        let query = near(axis);
+        let probed = vector::search_embedding(&db, &query, 2, &filter, config.ann_probes).unwrap();
+        let exact = db.vec_search(&query, 2, &filter, None).unwrap();
+        assert_eq!(probed[0].session_id, exact[0].session_id);
+        let session: usize = probed[0].session_id[1..].parse().unwrap();
+        assert_eq!(session % DIM, axis);
+    }
+
+    // A new embedding is searchable before it is assigned to a list
+    let target = near(7);
+    add_session("new", std::slice::from_ref(&target));
+    let results = vector::search_embedding(&db, &target, 1, &filter, 1).unwrap();
+    assert_eq!(results[0].session_id, "new");
+    assert_eq!(db.get_unassigned_chunk_embeddings(10).unwrap().len(), 1);
+
+    // The next update only assigns it; the centroids are kept
+    let centroids = db.get_ann_centroids().unwrap();
+    assert_eq!(ann::maintain(&db, &config).unwrap(), lists);
+    assert!(db.get_unassigned_chunk_embeddings(10).unwrap().is_empty());
+    assert_eq!(db.get_ann_centroids().unwrap(), centroids);
+    let results = vector::search_embedding(&db, &target, 1, &filter, 1).unwrap();
+    assert_eq!(results[0].session_id, "new");
+
+    // Deleted sessions drop out of the index with their embeddings
+    db.delete_session("new").unwrap();
+    let results = vector::search_embedding(&db, &target, 1, &filter, 1).unwrap();
+    assert_ne!(results[0].session_id, "new");
+
+    // Once the corpus is below the threshold the index is dropped
+    assert_eq!(ann::maintain(&db, &small).unwrap(), 0);
+    assert!(db.get_ann_centroids().unwrap().is_empty());
+}
+
+#[test]
+fn test_quantized_vectors_replace_full_precision() {
+    use ccsearch::config::{Config, VectorQuantization};
+    use ccsearch::search::filter::SearchFilter;
+
+    const DIM: usize = 64;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let mut rng = fastrand::Rng::with_seed(0x9);
+
+    // Random unit vectors, one topic per session; chunks and queries lie close to a topic
+    let mut unit = |base: Option<&Vec<f32>>, noise: f32| -> Vec<f32> {
+        let v: Vec<f32> = (0..DIM)
+            .map(|i| base.map_or(0.0, |b| b[i]) + (rng.f32() - 0.5) * noise)
+            .collect();
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.iter().map(|x| x / norm).collect()
+    };
+    let topics: Vec<Vec<f32>> = (0..60).map(|_| unit(None, 1.0)).collect();
+    let queries: Vec<Vec<f32>> = (0..5).map(|t| unit(Some(&topics[t * 7]), 0.1)).collect();
+    let sessions: Vec<Vec<Vec<f32>>> = topics
+        .iter()
+        .map(|topic| (0..9).map(|_| unit(Some(topic), 0.1)).collect())
+        .collect();
+    let target = unit(None, 1.0);
+    let filter = SearchFilter::default();
+    let vectors = sessions.len() as u64 * 9;
+
+    // Full-precision best matches to compare against
+    let expected: Vec<(String, f64)> = {
+        let db = ccsearch::db::Database::open(&path, &Config::default()).unwrap();
+        for (s, embeddings) in sessions.iter().enumerate() {
+            add_embedded_session(&db, &format!("s{}", s), embeddings);
+        }
+        assert_eq!(
+            db.chunk_embedding_bytes().unwrap(),
+            (vectors * DIM as u64 * 4, 0)
+        );
+        queries
+            .iter()
+            .map(|q| {
+                let best = db.vec_search(q, 1, &filter, None).unwrap().remove(0);
+                (best.session_id, best.distance)
+            })
+            .collect()
+    };
+    let full_size = std::fs::metadata(&path).unwrap().len();
+
+    for (mode, code_size) in [
+        (VectorQuantization::Int8, 0),
+        (VectorQuantization::Binary, DIM as u64 / 8),
+    ] {
+        // Switching modes re-encodes the embeddings already stored
+        let config = Config {
+            vector_quantization: mode,
+            ..Default::default()
+        };
+        let db = ccsearch::db::Database::open(&path, &config).unwrap();
+        assert_eq!(
+            db.chunk_embedding_bytes().unwrap(),
+            (vectors * (4 + DIM as u64), vectors * code_size)
+        );
+        assert_eq!(db.chunk_embedding_dim().unwrap(), Some(DIM));
+
+        for (query, expected) in queries.iter().zip(&expected) {
+            let results = db.vec_search(query, 3, &filter, None).unwrap();
+            assert_eq!(results[0].session_id, expected.0, "{:?}", mode);
+            // int8 scores stay close to full precision
+            assert!((results[0].distance - expected.1).abs() < 0.02);
+        }
+
+        // Codes follow their embeddings
+        add_embedded_session(&db, "extra", std::slice::from_ref(&target));
+        assert_eq!(
+            db.chunk_embedding_bytes().unwrap().1,
+            (vectors + 1) * code_size
+        );
+        let results = db.vec_search(&target, 1, &filter, None).unwrap();
+        assert_eq!(results[0].session_id, "extra");
+        db.delete_session("extra").unwrap();
+        assert_eq!(db.chunk_embedding_bytes().unwrap().1, vectors * code_size);
+    }
+    // The file gives back most of the space of the full-precision vectors
+    let saved = full_size - std::fs::metadata(&path).unwrap().len();
+    assert!(
+        saved > vectors * DIM as u64 * 4 / 2,
+        "{} bytes saved",
+        saved
+    );
+
+    // Full precision can't be recovered from the codes: every chunk is re-embedded
+    let db = ccsearch::db::Database::open(&path, &Config::default()).unwrap();
+    assert_eq!(db.chunk_embedding_bytes().unwrap(), (0, 0));
+    assert_eq!(db.count_pending_chunks().unwrap() as u64, vectors);
+}
+
+#[test]
+fn test_vector_store_matches_database_scan() {
+    use ccsearch::config::Config;
+    use ccsearch::db::vector_store::{self, VectorStore};
+    use ccsearch::search::filter::SearchFilter;
+    use ccsearch::search::{ann, vector};
+
+    const DIM: usize = 24;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let store_path = vector_store::store_path(&path);
+    assert_eq!(store_path, dir.path().join("index.vectors"));
+
+    let config = Config {
+        ann_min_vectors: 60,
+        ann_probes: 2,
+        ..Default::default()
+    };
+    let db = ccsearch::db::Database::open(&path, &config).unwrap();
+    let mut rng = fastrand::Rng::with_seed(0x5);
+    for s in 0..30 {
+        let embeddings: Vec<Vec<f32>> = (0..3)
+            .map(|_| noisy_unit_vector(&mut rng, DIM, s % DIM))
+            .collect();
+        add_embedded_session(&db, &format!("s{}", s), &embeddings);
+    }
+
+    let queries: Vec<Vec<f32>> = (0..4)
+        .map(|axis| noisy_unit_vector(&mut rng, DIM, axis * 5))
+        .collect();
+    let filter = SearchFilter {
+        session_id: Some("s1".to_string()),
+        ..Default::default()
+    };
+    let scan = |db: &ccsearch::db::Database, filter: &SearchFilter| -> Vec<Vec<(String, f64)>> {
+        queries
+            .iter()
+            .map(|q| {
+                db.vec_search(q, 5, filter, None)
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| (r.session_id, r.distance))
+                    .collect()
+            })
+            .collect()
+    };
+    let assert_same = |a: &[Vec<(String, f64)>], b: &[Vec<(String, f64)>]| {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            let ids = |r: &[(String, f64)]| r.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+            assert_eq!(ids(a), ids(b));
+            for ((_, x), (_, y)) in a.iter().zip(b) {
+                assert!((x - y).abs() < 1e-5);
+            }
+        }
+    };
+
+    // Without a store, searches scan chunk_embeddings
+    assert!(!store_path.exists());
+    let expected = scan(&db, &SearchFilter::default());
+    let expected_filtered = scan(&db, &filter);
+
+    assert!(db.sync_vector_store().unwrap());
+    assert!(!db.sync_vector_store().unwrap());
+    let store = VectorStore::open(&store_path).unwrap().unwrap();
+    assert_eq!(store.rows(), 90);
+    assert_same(&scan(&db, &SearchFilter::default()), &expected);
+    assert_same(&scan(&db, &filter), &expected_filtered);
+
+    // A new embedding makes the store stale; searches fall back until it is rebuilt
+    let target = noisy_unit_vector(&mut rng, DIM, 7);
+    add_embedded_session(&db, "new", std::slice::from_ref(&target));
+    let results = db
+        .vec_search(&target, 1, &SearchFilter::default(), None)
+        .unwrap();
+    assert_eq!(results[0].session_id, "new");
+    assert!(db.sync_vector_store().unwrap());
+    assert_eq!(VectorStore::open(&store_path).unwrap().unwrap().rows(), 91);
+    let results = db
+        .vec_search(&target, 1, &SearchFilter::default(), None)
+        .unwrap();
+    assert_eq!(results[0].session_id, "new");
+
+    // Rows are grouped by ANN list, so probing works on the store too
+    assert!(ann::maintain(&db, &config).unwrap() > 0);
+    assert!(db.sync_vector_store().unwrap());
+    let probed = vector::search_embedding(&db, &target, 1, &SearchFilter::default(), 2).unwrap();
+    assert_eq!(probed[0].session_id, "new");
+
+    // Reopening the database keeps using the same store
+    drop(db);
+    let db = ccsearch::db::Database::open(&path, &config).unwrap();
+    assert!(!db.sync_vector_store().unwrap());
+
+    // A store at the current generation but with a different row count is stale
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let generation: String = conn
+            .query_row(
+                "SELECT value FROM index_meta WHERE key = 'vector_generation'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        conn.execute(
+            "DELETE FROM chunk_embeddings WHERE rowid = (SELECT MIN(rowid) FROM chunk_embeddings)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE index_meta SET value = ?1 WHERE key = 'vector_generation'",
+            [generation],
+        )
+        .unwrap();
+    }
+    assert!(db.sync_vector_store().unwrap());
+    assert_eq!(VectorStore::open(&store_path).unwrap().unwrap().rows(), 90);
+
+    // Without chunk embeddings there is nothing to store
+    for s in 0..30 {
+        db.delete_session(&format!("s{}", s)).unwrap();
+    }
+    db.delete_session("new").unwrap();
+    assert!(db.sync_vector_store().unwrap());
+    assert!(!store_path.exists());
+}
+
+#[test]
+fn test_quantized_vector_store_matches_database_scan() {
+    use ccsearch::config::{Config, VectorQuantization};
+    use ccsearch::db::vector_store::{self, VectorStore};
+    use ccsearch::search::filter::SearchFilter;
+
+    const DIM: usize = 64;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let store_path = vector_store::store_path(&path);
+    let mut rng = fastrand::Rng::with_seed(0x11);
+
+    // Random unit vectors around one topic per session, with mixed signs so the
+    // binary codes tell them apart
+    let mut unit = |base: Option<&Vec<f32>>, noise: f32| -> Vec<f32> {
+        let v: Vec<f32> = (0..DIM)
+            .map(|i| base.map_or(0.0, |b| b[i]) + (rng.f32() - 0.5) * noise)
+            .collect();
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.iter().map(|x| x / norm).collect()
+    };
+    let topics: Vec<Vec<f32>> = (0..40).map(|_| unit(None, 1.0)).collect();
+    let sessions: Vec<Vec<Vec<f32>>> = topics
+        .iter()
+        .map(|topic| (0..3).map(|_| unit(Some(topic), 0.2)).collect())
+        .collect();
+    let queries: Vec<Vec<f32>> = (0..6).map(|t| unit(Some(&topics[t * 6]), 0.2)).collect();
+    let filter = SearchFilter::default();
+
+    for mode in [VectorQuantization::Int8, VectorQuantization::Binary] {
+        let config = Config {
+            vector_quantization: mode,
+            ..Default::default()
+        };
+        let db = ccsearch::db::Database::open(&path, &config).unwrap();
+        if db.count_chunk_embeddings().unwrap() == 0 {
+            for (s, embeddings) in sessions.iter().enumerate() {
+                add_embedded_session(&db, &format!("s{}", s), embeddings);
+            }
+        }
+        let scan: Vec<_> = queries
+            .iter()
+            .map(|q| db.vec_search(q, 3, &filter, None).unwrap())
+            .collect();
+
+        // A store built for another encoding is stale
+        assert!(db.sync_vector_store().unwrap(), "{:?}", mode);
+        assert!(!db.sync_vector_store().unwrap());
+        let store = VectorStore::open(&store_path).unwrap().unwrap();
+        assert_eq!(store.quantization(), mode);
+        assert_eq!(store.rows(), 120);
+
+        for (query, expected) in queries.iter().zip(&scan) {
+            let results = db.vec_search(query, 3, &filter, None).unwrap();
+            assert_eq!(results[0].session_id, expected[0].session_id, "{:?}", mode);
+            assert!((results[0].distance - expected[0].distance).abs() < 0.02);
+        }
+    }
+}
+
+#[test]
+fn test_index_param_changes_invalidate_affected_parts() {
+    use ccsearch::config::Config;
+    use ccsearch::db::Database;
+    use ccsearch::indexer::parser::ParseCheckpoint;
+    use ccsearch::search::filter::SearchFilter;
+    use ccsearch::search::vector;
+    use std::time::Duration;
+
+    const DIM: usize = 8;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("index.db");
+    let mut rng = fastrand::Rng::with_seed(0x22);
+    let checkpoint = ParseCheckpoint {
+        byte_offset: 10,
+        line_count: 1,
+        message_count: 1,
+        prefix_hash: "abc".to_string(),
+    };
+
+    {
+        let db = Database::open(&path, &Config::default()).unwrap();
+        assert!(db.index_drift().chunking.is_none());
+        assert!(db.index_drift().embedding_model.is_none());
+        // The lock holder records the parameters the index is built with
+        let _lock = db.lock_index(Duration::ZERO).unwrap().unwrap();
+        let embeddings: Vec<Vec<f32>> = (0..3)
+            .map(|axis| noisy_unit_vector(&mut rng, DIM, axis))
+            .collect();
+        add_embedded_session(&db, "s1", &embeddings);
+        db.conn()
+            .execute("UPDATE sessions SET file_mtime = 100", [])
+            .unwrap();
+        db.set_parse_checkpoint("s1", &checkpoint).unwrap();
+    }
+
+    // Reopening with the same settings changes nothing
+    let db = Database::open(&path, &Config::default()).unwrap();
+    assert!(db.index_drift().chunking.is_none());
+    assert_eq!(db.get_session_mtime("s1").unwrap(), Some(100));
+    drop(db);
+
+    // New chunk size: sessions are re-chunked on the next run, vectors stay usable.
+    // Opening only detects the change; the lock holder invalidates.
+    let config = Config {
+        chunk_size: 1000,
+        ..Default::default()
+    };
+    let db = Database::open(&path, &config).unwrap();
+    let (old, new) = db.index_drift().chunking.clone().unwrap();
+    assert!(old.contains("chunk_size=4000"));
+    assert!(new.contains("chunk_size=1000"));
+    assert!(db.index_drift().embedding_model.is_none());
+    assert_eq!(db.get_session_mtime("s1").unwrap(), Some(100));
+    assert!(db.get_parse_checkpoint("s1").unwrap().is_some());
+    let lock = db.lock_index(Duration::ZERO).unwrap();
+    assert!(lock.is_some());
+    assert_eq!(db.get_session_mtime("s1").unwrap(), Some(0));
+    assert!(db.get_parse_checkpoint("s1").unwrap().is_none());
+    assert_eq!(db.count_chunk_embeddings().unwrap(), 3);
+    assert_eq!(db.count_pending_chunks().unwrap(), 0);
+    drop(lock);
+    drop(db);
+
+    // New embedding model: vectors are dropped and every chunk waits for re-embedding
+    let config = Config {
+        chunk_size: 1000,
+        embedding_model: "bge-small-en-v1.5".to_string(),
+        ..Default::default()
+    };
+    let db = Database::open(&path, &config).unwrap();
+    assert!(db.index_drift().chunking.is_none());
+    let (old, new) = db.index_drift().embedding_model.clone().unwrap();
+    assert!(old.starts_with("all-MiniLM-L6-v2 "));
+    assert!(new.starts_with("bge-small-en-v1.5 "));
+
+    // Until a lock holder drops the old vectors, vector search refuses them
+    assert_eq!(db.count_chunk_embeddings().unwrap(), 3);
+    let query = noisy_unit_vector(&mut rng, DIM, 0);
+    let err = vector::search_embedding(&db, &query, 5, &SearchFilter::default(), 0).unwrap_err();
+    assert!(err.to_string().contains("re-embed"));
+    let lock = db.lock_index(Duration::ZERO).unwrap();
+    assert!(lock.is_some());
+    assert!(db.stale_embedding_model().unwrap().is_none());
+    assert_eq!(db.count_chunk_embeddings().unwrap(), 0);
+    assert_eq!(db.count_pending_chunks().unwrap(), 3);
+    drop(lock);
+    drop(db);
+
+    let db = Database::open(&path, &config).unwrap();
+    assert!(db.index_drift().embedding_model.is_none());
+
+    // Vectors of another dimension are refused rather than compared
+    let chunk_id = db.get_pending_chunks(1).unwrap()[0].chunk_id;
+    db.upsert_chunk_embedding(chunk_id, "s1", &noisy_unit_vector(&mut rng, DIM, 0))
+        .unwrap();
+    let filter = SearchFilter::default();
+    let query = noisy_unit_vector(&mut rng, DIM * 2, 0);
+    let err = vector::search_embedding(&db, &query, 5, &filter, 0).unwrap_err();
+    assert!(err.to_string().contains("8-dimensional"));
+    let query = noisy_unit_vector(&mut rng, DIM, 0);
+    assert_eq!(
+        vector::search_embedding(&db, &query, 5, &filter, 0)
+            .unwrap()
+            .len(),
+        1
+    );
+}